@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use crate::util::spectrum::{SpectrumBins, SPECTRUM_BINS, SPECTRUM_MAX_FREQ};
+
+pub const SAMPLE_DURATION: Duration = Duration::from_secs(30);
+// levels are sampled at fixed rate, so frame rate doesn't skew percentiles
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(20);
+// main volume puts this percentile of level at target
+const LOUD_PERCENTILE: f32 = 0.95;
+pub const TARGET_LEVEL: f32 = 0.9;
+// quietest levels, treated as noise floor
+const NOISE_PERCENTILE: f32 = 0.1;
+// minimum is set this much above noise floor
+pub const NOISE_MARGIN: f32 = 0.02;
+// low pass keeps this share of energy
+const ENERGY_SHARE: f32 = 0.9;
+// main volume slider range, as multiplier
+pub const MAX_MAIN_VOLUME: f32 = 5.0;
+
+/// Settings proposed from sampled audio
+#[derive(Clone, Copy)]
+pub struct Proposal {
+    pub main_volume: f32,
+    /// Device minimum, after proposed main volume
+    pub min: f32,
+    pub low_pass_freq: f32,
+}
+
+/// Collects levels and spectrum of current audio, to propose settings
+pub struct AutoSetup {
+    start: Instant,
+    last_sample: Option<Instant>,
+    /// Levels before main volume
+    levels: Vec<f32>,
+    /// Summed energy of each spectrum bin
+    energy: [f32; SPECTRUM_BINS],
+}
+
+impl AutoSetup {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last_sample: None,
+            levels: vec![],
+            energy: [0.0; SPECTRUM_BINS],
+        }
+    }
+
+    /// 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        (self.start.elapsed().as_secs_f32() / SAMPLE_DURATION.as_secs_f32())
+            .min(1.0)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.start.elapsed() >= SAMPLE_DURATION
+    }
+
+    pub fn record(&mut self, level: f32, bins: &[f32; SPECTRUM_BINS]) {
+        if self.is_finished()
+            || self
+                .last_sample
+                .is_some_and(|t| t.elapsed() < SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.last_sample = Some(Instant::now());
+        self.levels.push(level);
+        for (energy, bin) in self.energy.iter_mut().zip(bins) {
+            *energy += bin * bin;
+        }
+    }
+
+    /// Returns `None` if nothing was playing
+    pub fn proposal(&self) -> Option<Proposal> {
+        let mut levels = self.levels.clone();
+        levels.sort_by(f32::total_cmp);
+        let loud = percentile(&levels, LOUD_PERCENTILE)?;
+        if loud <= 0.0 {
+            return None;
+        }
+        // level is scaled by square of main volume
+        let main_volume = (TARGET_LEVEL / loud).sqrt().min(MAX_MAIN_VOLUME);
+        let gain = main_volume.powi(2);
+        let noise = percentile(&levels, NOISE_PERCENTILE)?;
+        let min = ((noise * gain).min(1.0) + NOISE_MARGIN).min(TARGET_LEVEL);
+        Some(Proposal {
+            main_volume,
+            min,
+            low_pass_freq: self.low_pass_freq(),
+        })
+    }
+
+    /// Upper edge of bin where energy reaches its share
+    fn low_pass_freq(&self) -> f32 {
+        let total: f32 = self.energy.iter().sum();
+        if total <= 0.0 {
+            return SPECTRUM_MAX_FREQ;
+        }
+        let mut sum = 0.0;
+        for (i, energy) in self.energy.iter().enumerate() {
+            sum += energy;
+            if sum >= total * ENERGY_SHARE {
+                return if i + 1 < SPECTRUM_BINS {
+                    SpectrumBins::bin_start(i + 1).round()
+                } else {
+                    SPECTRUM_MAX_FREQ
+                };
+            }
+        }
+        SPECTRUM_MAX_FREQ
+    }
+}
+
+/// `sorted` must be in ascending order
+pub fn percentile(sorted: &[f32], p: f32) -> Option<f32> {
+    let last = sorted.len().checked_sub(1)?;
+    Some(sorted[(last as f32 * p).round() as usize])
+}
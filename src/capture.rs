@@ -0,0 +1,935 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    settings::CaptureSettings,
+    util::{
+        self,
+        agc::AutoGain,
+        envelope::TransientFollower,
+        onset::OnsetDetector,
+        spectrum::{BandPowers, SpectrumAnalyzer, SpectrumBins, BAND_COUNT},
+        tempo::TempoTracker,
+        ChannelPowers, DetectionMode, LowPassFilter, NotchFilter, PeakHold,
+        SharedBool, SharedCounter, SharedF32, SharedString,
+    },
+    wasapi::{self, Capture, Format, SampleKind},
+};
+
+// sleeps aren't precise, so short intervals get some slack
+const MIN_OVERSLEEP: Duration = Duration::from_millis(20);
+// seconds, low pass cutoff follows its setting with this time constant
+const CUTOFF_GLIDE_TIME: f32 = 0.05;
+// how often unavailable device or app is retried
+const REOPEN_INTERVAL: Duration = Duration::from_secs(1);
+// poll interval while there's nothing to capture
+const IDLE_INTERVAL: Duration = Duration::from_millis(50);
+// mic level only needs to follow speech
+const MIC_POLL_INTERVAL: Duration = Duration::from_millis(20);
+// how long default device change stays shown
+const DEVICE_CHANGE_NOTICE_TIME: Duration = Duration::from_secs(5);
+const DEVICE_CHANGE_NOTICE: &str =
+    "Default audio device changed, reconnecting capture";
+// mute is toggled by hand, and checking app sessions isn't free
+const MUTE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Problems seen by capture thread, counted since start,
+/// and other diagnostics
+#[derive(Clone)]
+pub struct CaptureStats {
+    /// Polls that returned no samples, normal while nothing is playing
+    pub empty_reads: SharedCounter,
+    /// Polls that came more than twice as late as planned
+    pub oversleeps: SharedCounter,
+    /// Why chosen device isn't being captured, empty if it is
+    pub notice: SharedString,
+    /// Channel count of captured source, 0 before anything is captured
+    pub channels: SharedCounter,
+    /// Captured device or app is muted in Windows
+    pub is_muted: SharedBool,
+    /// RMS before and after filters, only measured while filter meters
+    /// are shown
+    pub unfiltered_power: SharedF32,
+    pub filtered_power: SharedF32,
+}
+
+impl CaptureStats {
+    pub fn new() -> Self {
+        Self {
+            empty_reads: SharedCounter::new(),
+            oversleeps: SharedCounter::new(),
+            notice: SharedString::default(),
+            channels: SharedCounter::new(),
+            is_muted: SharedBool::new(false),
+            unfiltered_power: SharedF32::new(0.0),
+            filtered_power: SharedF32::new(0.0),
+        }
+    }
+}
+
+/// Properties of captured audio, beyond latest level
+#[derive(Clone)]
+pub struct AudioFeatures {
+    /// Highest level since GUI last took it, kept with `peak_since_frame`
+    pub peak: SharedF32,
+    /// Dominant frequency in Hz, 0 without clear pitch
+    pub pitch: SharedF32,
+    /// 0 while tempo is unclear
+    pub bpm: SharedF32,
+    /// Position within current beat, 0.0 on the beat
+    pub beat_phase: SharedF32,
+    /// Sudden rises in level, after same detection and gain as level
+    pub transient: SharedF32,
+    /// Side to mid energy, 0 for mono, up to 1 for wide stereo
+    pub width: SharedF32,
+}
+
+impl AudioFeatures {
+    pub fn new() -> Self {
+        Self {
+            peak: SharedF32::new(0.0),
+            pitch: SharedF32::new(0.0),
+            bpm: SharedF32::new(0.0),
+            beat_phase: SharedF32::new(0.0),
+            transient: SharedF32::new(0.0),
+            width: SharedF32::new(0.0),
+        }
+    }
+
+    fn clear(&self) {
+        self.peak.store(0.0);
+        self.pitch.store(0.0);
+        self.bpm.store(0.0);
+        self.beat_phase.store(0.0);
+        self.transient.store(0.0);
+        self.width.store(0.0);
+    }
+}
+
+pub fn capture_thread(
+    sound_power: SharedF32,
+    band_powers: BandPowers,
+    channel_powers: ChannelPowers,
+    stats: CaptureStats,
+    spectrum: SpectrumBins,
+    features: AudioFeatures,
+    settings: CaptureSettings,
+) -> ! {
+    let dur = Duration::from_millis(1);
+    let mut source = Source::load(&settings);
+    let mut opened = open_source(&source, dur, &stats.notice);
+    let mut last_open = Instant::now();
+    let mut device_changed_at = None::<Instant>;
+
+    // longest wait for a packet, time to fill about half of capture buffer,
+    // reads stay regular when engine doesn't signal, like on silent loopback
+    let poll_interval = |buffer_frame_size: u32, sample_rate: u32| {
+        Duration::from_secs_f32(
+            dur.as_secs_f32() * buffer_frame_size as f32
+                / sample_rate as f32
+                / 1000.,
+        ) / 2
+    };
+    let mut actual_duration = IDLE_INTERVAL;
+
+    let mut window = CaptureWindow::new();
+    let mut analyzer = SpectrumAnalyzer::new();
+    let mut onset_detector = OnsetDetector::new();
+    let mut tempo_tracker = TempoTracker::new();
+    let mut transient_follower = TransientFollower::new();
+    let mut peak_hold = PeakHold::new();
+    let mut auto_gain = AutoGain::new();
+    let mut low_pass_filter = LowPassFilter::new();
+    let mut notch_filter = NotchFilter::new();
+    let mut cutoff = filter_cutoffs(&settings).0.max(1.0);
+
+    let clear_levels = || {
+        sound_power.store(0.0);
+        band_powers.store([0.0; BAND_COUNT]);
+        band_powers.store_cutoffs(|_| 0.0);
+        channel_powers.store([0.0; 2]);
+        features.clear();
+    };
+    let mut last_mute_check = Instant::now();
+    let mut last_poll = Instant::now();
+    loop {
+        match &opened.capture {
+            // wakes as soon as a packet is ready
+            Some(capture) => {
+                capture.wait(actual_duration);
+            }
+            None => std::thread::sleep(actual_duration),
+        }
+        let since_last_poll = last_poll.elapsed();
+        last_poll = Instant::now();
+        if since_last_poll > (actual_duration * 2).max(MIN_OVERSLEEP) {
+            stats.oversleeps.increment();
+        }
+        let wanted_source = Source::load(&settings);
+        if wanted_source != source {
+            source = wanted_source;
+            opened = open_source(&source, dur, &stats.notice);
+            last_open = Instant::now();
+        } else if last_open.elapsed() >= REOPEN_INTERVAL {
+            last_open = Instant::now();
+            let has_exited = opened
+                .process_id
+                .is_some_and(|id| !wasapi::is_process_running(id));
+            // chosen device might be plugged back in, or app started again,
+            // fallback keeps running until then
+            if has_exited || !opened.is_chosen {
+                match open_chosen(&source, dur) {
+                    Ok(chosen) => {
+                        opened = chosen;
+                        stats.notice.store(String::new());
+                    }
+                    Err(_) if has_exited || opened.capture.is_none() => {
+                        opened = open_source(&source, dur, &stats.notice);
+                    }
+                    Err(_) => (),
+                }
+            }
+            // old default keeps running, but goes silent or stops working
+            let default_changed =
+                opened.default_id.as_ref().is_some_and(|id| {
+                    wasapi::default_output().is_ok_and(|x| x != *id)
+                });
+            if default_changed {
+                opened = open_source(&source, dur, &stats.notice);
+                // fallback notice is more useful than this one
+                if opened.is_chosen {
+                    stats.notice.store(DEVICE_CHANGE_NOTICE.to_owned());
+                    device_changed_at = Some(Instant::now());
+                }
+            }
+            if device_changed_at
+                .is_some_and(|x| x.elapsed() >= DEVICE_CHANGE_NOTICE_TIME)
+            {
+                device_changed_at = None;
+                if stats.notice.load() == DEVICE_CHANGE_NOTICE {
+                    stats.notice.store(String::new());
+                }
+            }
+        }
+        let Some(capture) = &mut opened.capture else {
+            // don't leave stale levels behind
+            window.clear();
+            clear_levels();
+            stats.is_muted.store(false);
+            actual_duration = IDLE_INTERVAL;
+            continue;
+        };
+        // filters and detectors start over along with window
+        if window.set_format(capture.format()) {
+            stats.channels.store(window.format.channels as u32);
+            low_pass_filter = LowPassFilter::new();
+            notch_filter = NotchFilter::new();
+            onset_detector = OnsetDetector::new();
+            tempo_tracker = TempoTracker::new();
+            transient_follower = TransientFollower::new();
+            peak_hold = PeakHold::new();
+        }
+        let format = window.format;
+        actual_duration =
+            poll_interval(capture.buffer_frame_size, format.sample_rate);
+        let channels = format.channels as usize;
+        // when window grows, buffer just fills up with new samples,
+        // instead of being padded with silence
+        let buffer_size = window_size(
+            settings.analysis_window_ms.load(),
+            format.sample_rate,
+            channels,
+        );
+        let slope = settings.filter_slope.load();
+        if let Some(sections) = slope.sections() {
+            // moving cutoff gradually keeps slider drags from spiking power
+            let target = filter_cutoffs(&settings).0.max(1.0);
+            let t = 1.0
+                - (-since_last_poll.as_secs_f32() / CUTOFF_GLIDE_TIME).exp();
+            cutoff *= (target / cutoff).powf(t);
+            low_pass_filter.configure(
+                cutoff,
+                sections,
+                format.sample_rate,
+                channels,
+            );
+        }
+        // side needs both channels, mono has nothing to subtract
+        let use_side = settings.use_side.load() && channels == 2;
+        let use_notch = settings.use_notch.load();
+        if use_notch {
+            notch_filter.configure(
+                settings.notch_freq.load(),
+                settings.notch_q.load(),
+                format.sample_rate,
+                channels,
+            );
+        } else {
+            // starts fresh when turned back on
+            notch_filter = NotchFilter::new();
+        }
+        // mics are much quieter than loopback
+        let gain = if capture.is_input() {
+            settings.input_gain.load()
+        } else {
+            1.0
+        };
+        let mut read_count = 0;
+        let result = capture.read_samples(|samples| {
+            read_count += samples.len();
+            let side: Vec<_>;
+            let samples = if use_side {
+                side = samples
+                    .chunks_exact(2)
+                    .flat_map(|x| [(x[0] - x[1]) / 2.0; 2])
+                    .collect();
+                &side[..]
+            } else {
+                samples
+            };
+            let notched;
+            let samples = if use_notch {
+                notched = notch_filter.process(samples);
+                &notched[..]
+            } else {
+                samples
+            };
+            let low_passed = low_pass_filter.process(samples);
+            window.push(
+                samples.iter().map(|x| x * gain),
+                low_passed.into_iter().map(|x| x * gain),
+                buffer_size,
+            );
+        });
+        // device was most likely unplugged or disabled
+        if result.is_err() {
+            opened = open_source(&source, dur, &stats.notice);
+            last_open = Instant::now();
+            continue;
+        }
+        if read_count == 0 {
+            stats.empty_reads.increment();
+        }
+        if last_mute_check.elapsed() >= MUTE_CHECK_INTERVAL {
+            last_mute_check = Instant::now();
+            stats.is_muted.store(capture.is_muted());
+        }
+        // buffer keeps draining, so unmuting doesn't replay old audio
+        if stats.is_muted.load() && settings.pause_when_muted.load() {
+            window.clear();
+            clear_levels();
+            continue;
+        }
+
+        // nothing captured yet since format change
+        if window.samples.len() < channels {
+            continue;
+        }
+        let buf = window.samples.make_contiguous();
+        let powers = analyzer.analyze(buf, channels, format.sample_rate);
+        band_powers.store(powers);
+        band_powers.store_cutoffs(|cutoff| analyzer.power_below(cutoff));
+        let pitch = analyzer.dominant_freq().unwrap_or(0.0);
+        features.pitch.store(pitch);
+        // rejecting center leaves identical channels, so it reads as mono
+        let width = if channels == 2 {
+            util::stereo_width(buf)
+        } else {
+            0.0
+        };
+        features.width.store(width);
+        if spectrum.is_enabled.load() {
+            spectrum.store(analyzer.log_bins());
+        }
+        // doubles power calculation, so it's skipped unless shown
+        let show_filter_meters = settings.show_filter_meters.load();
+        if show_filter_meters {
+            let unfiltered = util::avg(&util::calculate_power(buf, channels));
+            stats.unfiltered_power.store(unfiltered);
+        }
+        let mode = settings.detection_mode.load();
+        let power_band = settings.power_band.load();
+        let (power, channel_levels) = match power_band.split_index() {
+            Some(i) => {
+                if show_filter_meters {
+                    stats.filtered_power.store(powers[i]);
+                }
+                (powers[i], None)
+            }
+            None => {
+                let mut filtered = if slope.sections().is_some() {
+                    let low_passed = window.low_passed.make_contiguous();
+                    apply_filters(
+                        low_passed,
+                        format.sample_rate,
+                        channels,
+                        &settings,
+                        true,
+                    )
+                } else {
+                    apply_filters(
+                        buf,
+                        format.sample_rate,
+                        channels,
+                        &settings,
+                        false,
+                    )
+                };
+                if settings.use_a_weighting.load() {
+                    filtered = util::a_weighting(
+                        &filtered,
+                        format.sample_rate,
+                        channels,
+                    );
+                }
+                let emphasis = settings.bass_emphasis.load();
+                if emphasis > 0.0 {
+                    filtered = util::bass_emphasis(
+                        &filtered,
+                        emphasis,
+                        format.sample_rate,
+                        channels,
+                    );
+                }
+                if show_filter_meters {
+                    let filtered =
+                        util::avg(&util::calculate_power(&filtered, channels));
+                    stats.filtered_power.store(filtered);
+                }
+                let speeds = if mode.uses_peak() {
+                    util::calculate_peak(&filtered, channels)
+                } else {
+                    util::calculate_power(&filtered, channels)
+                };
+                let weights = settings
+                    .downmix
+                    .load()
+                    .weights(format.channels, &settings.downmix_weights);
+                let power = util::weighted_avg(&speeds, &weights);
+                (power.clamp(0.0, 1.0), Some(speeds))
+            }
+        };
+        let raw_power = power;
+        let beat = tempo_tracker.process(raw_power);
+        features.bpm.store(beat.map_or(0.0, |x| x.bpm));
+        features.beat_phase.store(beat.map_or(0.0, |x| x.phase));
+        let power = match mode {
+            DetectionMode::Rms | DetectionMode::Peak => power,
+            DetectionMode::PeakHold => {
+                let windows = settings.peak_hold_windows.load() as usize;
+                peak_hold.process(power, windows)
+            }
+            DetectionMode::Onset => {
+                onset_detector.process(power, settings.onset_sensitivity.load())
+            }
+        };
+        let power = if settings.use_agc.load() {
+            auto_gain.process(
+                power,
+                settings.agc_target.load(),
+                settings.agc_adaptation_time.load(),
+                settings.agc_floor.load(),
+            )
+        } else {
+            power
+        };
+        publish_level(
+            &sound_power,
+            &features.peak,
+            power,
+            settings.peak_since_frame.load(),
+        );
+        features.transient.store(transient_follower.process(power));
+        channel_powers.store(stereo_powers(
+            channel_levels.as_deref(),
+            power,
+            raw_power,
+        ));
+    }
+}
+
+/// Captures default voice chat mic while `is_enabled`,
+/// storing its level for ducking
+pub fn mic_thread(mic_level: SharedF32, is_enabled: SharedBool) -> ! {
+    let dur = Duration::from_millis(1);
+    let mut capture = None;
+    let mut last_open = None::<Instant>;
+    loop {
+        if !is_enabled.load() {
+            // lets go of mic, so it isn't shown as in use
+            capture = None;
+            mic_level.store(0.0);
+            std::thread::sleep(IDLE_INTERVAL);
+            continue;
+        }
+        std::thread::sleep(MIC_POLL_INTERVAL);
+        let is_recent =
+            last_open.is_some_and(|x| x.elapsed() < REOPEN_INTERVAL);
+        if capture.is_none() && !is_recent {
+            last_open = Some(Instant::now());
+            capture = wasapi::default_voice_input()
+                .and_then(|id| Capture::open(Some(id.as_str()), dur))
+                .ok();
+        }
+        let Some(mic) = &mut capture else {
+            mic_level.store(0.0);
+            continue;
+        };
+        let channels = mic.format().channels as usize;
+        let mut samples = vec![];
+        if mic.read_samples(|x| samples.extend_from_slice(x)).is_err() {
+            capture = None;
+            mic_level.store(0.0);
+            continue;
+        }
+        // nothing new, keep last level
+        if samples.len() < channels {
+            continue;
+        }
+        let level = util::avg(&util::calculate_power(&samples, channels));
+        mic_level.store(level.clamp(0.0, 1.0));
+    }
+}
+
+/// What capture thread is reading from
+#[derive(PartialEq, Eq)]
+enum Source {
+    /// Output device id, empty for default device
+    Device(String),
+    /// Executable name of app
+    App(String),
+}
+
+impl Source {
+    fn load(settings: &CaptureSettings) -> Self {
+        let app = settings.capture_app.load();
+        if app.is_empty() {
+            Self::Device(settings.audio_device.load())
+        } else {
+            Self::App(app)
+        }
+    }
+}
+
+struct Opened {
+    capture: Option<Capture>,
+    /// Otherwise it's a fallback, or nothing, and chosen source is retried
+    is_chosen: bool,
+    /// Captured app's process, watched for exit
+    process_id: Option<u32>,
+    /// Id of default device when capturing it, watched for change
+    default_id: Option<String>,
+}
+
+fn open_chosen(source: &Source, dur: Duration) -> Result<Opened, String> {
+    let mut default_id = None;
+    let (capture, process_id) = match source {
+        Source::Device(id) if id.is_empty() => {
+            default_id = wasapi::default_output().ok();
+            (Capture::open(None, dur), None)
+        }
+        Source::Device(id) => (Capture::open(Some(id.as_str()), dur), None),
+        Source::App(name) => {
+            let id = wasapi::find_process(name)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| {
+                    format!("{name} isn't running, waiting for it to start")
+                })?;
+            (Capture::open_process(id, dur), Some(id))
+        }
+    };
+    Ok(Opened {
+        capture: Some(capture.map_err(|e| e.to_string())?),
+        is_chosen: true,
+        process_id,
+        default_id,
+    })
+}
+
+/// Opens chosen source, falling back to default device if chosen device
+/// is unavailable. Apps don't fall back, as that would let through sounds
+/// user wanted to skip.
+fn open_source(
+    source: &Source,
+    dur: Duration,
+    notice: &SharedString,
+) -> Opened {
+    let error = match open_chosen(source, dur) {
+        Ok(opened) => {
+            notice.store(String::new());
+            return opened;
+        }
+        Err(e) => e,
+    };
+    let mut opened = Opened {
+        capture: None,
+        is_chosen: false,
+        process_id: None,
+        default_id: None,
+    };
+    match source {
+        Source::App(_) => notice.store(error),
+        Source::Device(id) if id.is_empty() => {
+            notice.store(format!("Can't capture default device: {error}"));
+        }
+        Source::Device(_) => match Capture::open(None, dur) {
+            Ok(capture) => {
+                opened.default_id = wasapi::default_output().ok();
+                notice.store(format!(
+                    "Chosen audio device is unavailable ({error}), \
+                    capturing default device"
+                ));
+                opened.capture = Some(capture);
+            }
+            Err(e) => {
+                notice.store(format!("Can't capture default device: {e}"));
+            }
+        },
+    }
+    opened
+}
+
+/// Stores level of latest window. With `keep_peak`, highest one is kept
+/// until GUI takes it with [`take_level`], as short hits between frames
+/// would be lost otherwise.
+fn publish_level(
+    latest: &SharedF32,
+    peak: &SharedF32,
+    power: f32,
+    keep_peak: bool,
+) {
+    latest.store(power);
+    if keep_peak {
+        peak.fetch_max(power);
+    } else {
+        // no stale peak once turned on
+        peak.store(0.0);
+    }
+}
+
+/// Level for GUI frame, highest since last frame with `keep_peak`
+pub fn take_level(
+    latest: &SharedF32,
+    peak: &SharedF32,
+    keep_peak: bool,
+) -> f32 {
+    let latest = latest.load();
+    if keep_peak {
+        peak.take_max(latest)
+    } else {
+        latest
+    }
+}
+
+/// Number of samples in analysis window
+fn window_size(window_ms: f32, sample_rate: u32, channels: usize) -> usize {
+    let frames = (sample_rate as f32 * window_ms / 1000.0) as usize;
+    frames.max(1) * channels
+}
+
+/// Analysis windows of captured samples, started over on format change
+struct CaptureWindow {
+    format: Format,
+    samples: VecDeque<f32>,
+    /// Low passed as samples arrive, same layout as `samples`
+    low_passed: VecDeque<f32>,
+}
+
+impl CaptureWindow {
+    fn new() -> Self {
+        Self {
+            // nothing captured yet, first capture resets state
+            format: Format {
+                channels: 0,
+                sample_rate: 0,
+                sample_kind: SampleKind::F32,
+            },
+            samples: VecDeque::new(),
+            low_passed: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether format changed. Other source might have different
+    /// format, old samples would be interleaved wrong, so windows start over.
+    fn set_format(&mut self, format: Format) -> bool {
+        let is_changed = format.channels != self.format.channels
+            || format.sample_rate != self.format.sample_rate;
+        if is_changed {
+            self.format = format;
+            self.clear();
+        }
+        is_changed
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.low_passed.clear();
+    }
+
+    fn push(
+        &mut self,
+        samples: impl IntoIterator<Item = f32>,
+        low_passed: impl IntoIterator<Item = f32>,
+        size: usize,
+    ) {
+        push_window(&mut self.samples, samples, size);
+        push_window(&mut self.low_passed, low_passed, size);
+    }
+}
+
+/// Appends `samples` to analysis window, dropping oldest ones over `size`,
+/// so window stays in capture order, with frames kept whole
+fn push_window(
+    window: &mut VecDeque<f32>,
+    samples: impl IntoIterator<Item = f32>,
+    size: usize,
+) {
+    window.extend(samples);
+    let excess = window.len().saturating_sub(size);
+    window.drain(..excess);
+}
+
+/// Left and right levels. Channels go through same detection and gain
+/// as mix, keeping their share of it. Mono gives mix for both.
+fn stereo_powers(
+    channel_levels: Option<&[f32]>,
+    power: f32,
+    raw_power: f32,
+) -> [f32; 2] {
+    match channel_levels {
+        Some(&[left, right, ..]) if raw_power > 0.0 => {
+            let gain = power / raw_power;
+            [left, right].map(|x| (x * gain).clamp(0.0, 1.0))
+        }
+        _ => [power; 2],
+    }
+}
+
+/// Returns (low pass, high pass) cutoffs
+fn filter_cutoffs(settings: &CaptureSettings) -> (f32, f32) {
+    let (low_pass_freq, high_pass_freq) = if settings.use_band_pass.load() {
+        (settings.band_high.load(), settings.band_low.load())
+    } else {
+        (
+            settings.low_pass_freq.load(),
+            settings.high_pass_freq.load(),
+        )
+    };
+    (low_pass_freq, high_pass_freq)
+}
+
+/// Skips low pass if `samples` were already low passed by biquad filter
+fn apply_filters(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    settings: &CaptureSettings,
+    is_low_passed: bool,
+) -> Vec<f32> {
+    let (low_pass_freq, high_pass_freq) = filter_cutoffs(settings);
+    let filtered = if is_low_passed {
+        samples.to_vec()
+    } else {
+        util::low_pass(samples, sample_rate, low_pass_freq, channels)
+    };
+    if high_pass_freq > 0.0 {
+        util::high_pass(&filtered, sample_rate, high_pass_freq, channels)
+    } else {
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_size_is_whole_frames() {
+        assert_eq!(window_size(10.0, 48000, 2), 960);
+        assert_eq!(window_size(10.0, 44100, 1), 441);
+        assert_eq!(window_size(20.0, 48000, 6), 5760);
+        assert_eq!(window_size(10.0, 44100, 8) % 8, 0);
+        // never empty, even for tiny windows
+        assert_eq!(window_size(0.0, 48000, 2), 2);
+    }
+
+    #[test]
+    fn window_keeps_frames_whole() {
+        for channels in [1, 2, 6, 8] {
+            let size = window_size(1.0, 48000, channels);
+            let mut window = VecDeque::new();
+            // reads of uneven length, each value is its frame number
+            for (start, len) in [(0, 7), (7, 30), (37, 100), (137, 3)] {
+                let frames =
+                    (start..start + len).flat_map(|x| vec![x as f32; channels]);
+                push_window(&mut window, frames, size);
+            }
+            let window = window.make_contiguous();
+            assert_eq!(window.len(), size, "{channels}");
+            for (i, frame) in window.chunks_exact(channels).enumerate() {
+                let expected = (140 - size / channels + i) as f32;
+                assert!(frame.iter().all(|&x| x == expected), "{channels}");
+            }
+        }
+    }
+
+    #[test]
+    fn window_grows_without_padding() {
+        let mut window = VecDeque::new();
+        push_window(&mut window, [1.0, 2.0], 4);
+        assert_eq!(window.make_contiguous(), [1.0, 2.0]);
+    }
+
+    #[test]
+    fn window_keeps_capture_order() {
+        let mut window = VecDeque::new();
+        push_window(&mut window, [1.0, 2.0, 3.0, 4.0], 4);
+        push_window(&mut window, [5.0, 6.0], 4);
+        assert_eq!(window.make_contiguous(), [3.0, 4.0, 5.0, 6.0]);
+    }
+
+    /// Feeds few reads of repeated `frame`, returns whether format changed
+    fn feed(window: &mut CaptureWindow, format: Format, frame: &[f32]) -> bool {
+        let is_changed = window.set_format(format);
+        let channels = format.channels as usize;
+        let size = window_size(10.0, format.sample_rate, channels);
+        for _ in 0..4 {
+            let samples = frame.iter().copied().cycle().take(channels * 100);
+            window.push(samples.clone(), samples, size);
+        }
+        is_changed
+    }
+
+    #[test]
+    fn format_change_starts_window_over() {
+        let stereo = Format {
+            channels: 2,
+            sample_rate: 48000,
+            sample_kind: SampleKind::F32,
+        };
+        let surround = Format {
+            channels: 6,
+            ..stereo
+        };
+        let mut window = CaptureWindow::new();
+        assert!(feed(&mut window, stereo, &[0.8, 0.0]));
+        assert!(!feed(&mut window, stereo, &[0.8, 0.0]));
+        // source switches mid-stream, like when default device changes
+        assert!(feed(&mut window, surround, &[0.3; 6]));
+        let mut fresh = CaptureWindow::new();
+        feed(&mut fresh, surround, &[0.3; 6]);
+        for (buf, fresh) in [
+            (&mut window.samples, &mut fresh.samples),
+            (&mut window.low_passed, &mut fresh.low_passed),
+        ] {
+            let powers = util::calculate_power(buf.make_contiguous(), 6);
+            // no leftover stereo samples interleaved into other channels
+            assert_eq!(buf.len() % 6, 0);
+            assert!(powers.iter().all(|&x| x == powers[0] && x > 0.0));
+            let expected = util::calculate_power(fresh.make_contiguous(), 6);
+            assert_eq!(powers, expected);
+        }
+    }
+
+    #[test]
+    fn panned_left_stays_left() {
+        let mut window = VecDeque::new();
+        // interleaved stereo, sound only on left
+        let frames = (0..1000).flat_map(|i| [(i as f32 * 0.1).sin(), 0.0]);
+        push_window(&mut window, frames, 512);
+        let [left, right] = util::calculate_power(window.make_contiguous(), 2)
+            .try_into()
+            .unwrap();
+        assert!(left > 0.1, "{left}");
+        assert_eq!(right, 0.0);
+    }
+
+    #[test]
+    fn lfe_lands_on_lfe_channel() {
+        let mut window = VecDeque::new();
+        // 5.1 frames, only subwoofer playing
+        let frames = (0..1000).flat_map(|i| {
+            let mut frame = [0.0; 6];
+            frame[3] = (i as f32 * 0.01).sin();
+            frame
+        });
+        push_window(&mut window, frames, window_size(10.0, 48000, 6));
+        let powers = util::calculate_power(window.make_contiguous(), 6);
+        let loudest = (0..6).max_by(|&a, &b| powers[a].total_cmp(&powers[b]));
+        assert_eq!(loudest.map(util::channel_name).as_deref(), Some("LFE"));
+    }
+
+    /// Stereo powers of window with tone panned by `pan`,
+    /// -1 is left, 1 is right
+    fn panned(pan: f32) -> [f32; 2] {
+        let left = (1.0 - pan) / 2.0;
+        let right = (1.0 + pan) / 2.0;
+        let frames = (0..2000).flat_map(|i| {
+            let x = (i as f32 * 0.1).sin();
+            [x * left, x * right]
+        });
+        let mut window = VecDeque::new();
+        push_window(&mut window, frames, window_size(10.0, 48000, 2));
+        let levels = util::calculate_power(window.make_contiguous(), 2);
+        let raw_power = util::avg(&levels);
+        stereo_powers(Some(&levels), raw_power, raw_power)
+    }
+
+    #[test]
+    fn pan_sweeps_between_channels() {
+        let [left, right] = panned(-1.0);
+        assert!(left > 0.1 && right == 0.0, "{left} {right}");
+        let [left, right] = panned(1.0);
+        assert!(right > 0.1 && left == 0.0, "{left} {right}");
+        let [left, right] = panned(0.0);
+        assert!((left - right).abs() < 1e-6, "{left} {right}");
+        // moving right, left only falls and right only rises
+        let sweep: Vec<_> =
+            (-10..=10).map(|i| panned(i as f32 / 10.0)).collect();
+        for pair in sweep.windows(2) {
+            assert!(pair[1][0] <= pair[0][0] && pair[1][1] >= pair[0][1]);
+        }
+    }
+
+    #[test]
+    fn stereo_powers_keep_share_of_gain() {
+        // detection halved mix, channels follow
+        assert_eq!(stereo_powers(Some(&[0.8, 0.4]), 0.3, 0.6), [0.4, 0.2]);
+    }
+
+    #[test]
+    fn mono_maps_to_both_channels() {
+        assert_eq!(stereo_powers(Some(&[0.5]), 0.7, 0.5), [0.7, 0.7]);
+        assert_eq!(stereo_powers(None, 0.4, 0.4), [0.4, 0.4]);
+        // nothing to share out
+        assert_eq!(stereo_powers(Some(&[0.0, 0.0]), 0.2, 0.0), [0.2, 0.2]);
+    }
+
+    #[test]
+    fn spike_between_frames_is_kept() {
+        let latest = SharedF32::new(0.0);
+        let peak = SharedF32::new(0.0);
+        // capture windows between two frames of GUI
+        let publish = |levels: &[f32], keep_peak| {
+            for &level in levels {
+                publish_level(&latest, &peak, level, keep_peak);
+            }
+        };
+        publish(&[0.1, 0.9, 0.2], true);
+        assert_eq!(take_level(&latest, &peak, false), 0.2);
+        assert_eq!(take_level(&latest, &peak, true), 0.9);
+        // nothing from last frame carries over
+        publish(&[0.1], true);
+        assert_eq!(take_level(&latest, &peak, true), 0.1);
+        publish(&[0.3, 0.05], true);
+        assert_eq!(take_level(&latest, &peak, true), 0.3);
+        // no new window since last frame gives latest level, not silence
+        assert_eq!(take_level(&latest, &peak, true), 0.05);
+        // turning it off drops peak
+        publish(&[0.7, 0.4], false);
+        publish(&[0.2], true);
+        assert_eq!(take_level(&latest, &peak, true), 0.2);
+    }
+}
@@ -0,0 +1,651 @@
+use std::{mem, sync::Arc};
+
+use buttplug::client::{
+    ButtplugClient, ButtplugClientDevice, ButtplugClientError,
+    ButtplugClientEvent,
+};
+use futures::StreamExt;
+use tokio::{runtime::Runtime, task::JoinHandle};
+
+use crate::{
+    settings::ConnectionSettings,
+    util::{self, ServerKind, SharedBool},
+};
+
+pub const PRIMARY_CONNECTION_NAME: &str = "Default";
+
+type ConnectResult = Result<(ButtplugClient, ServerKind), String>;
+
+pub enum ConnectionState {
+    /// Not connected yet, or disconnected on purpose
+    Idle,
+    Connecting(JoinHandle<ConnectResult>),
+    Connected(ButtplugClient, ServerKind),
+    Failed(String),
+}
+
+type ScanResult = Result<(), ButtplugClientError>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanState {
+    Idle,
+    Starting,
+    Scanning,
+    Stopping,
+}
+
+impl ScanState {
+    pub fn is_pending(self) -> bool {
+        matches!(self, Self::Starting | Self::Stopping)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Idle => "Scan",
+            Self::Starting => "Starting scan...",
+            Self::Scanning => "Scanning",
+            Self::Stopping => "Stopping scan...",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScanEvent {
+    /// Scanning turned on or off by user
+    Wanted(bool),
+    Started(Result<(), String>),
+    Stopped(Result<(), String>),
+    /// Server stopped scanning by itself
+    Finished,
+}
+
+/// Scanning state, without tasks driving it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Scan {
+    pub state: ScanState,
+    /// Whether scanning is wanted, `state` catches up to it
+    pub is_wanted: bool,
+    /// Last failed scan start or stop, shown in status
+    pub error: Option<String>,
+}
+
+impl Scan {
+    fn new(is_wanted: bool) -> Self {
+        Self {
+            state: ScanState::Idle,
+            is_wanted,
+            error: None,
+        }
+    }
+
+    /// State after `event`. It changes one step at a time, so overlapping
+    /// start and stop requests can't race each other. Caller starts or
+    /// stops scanning when state becomes `Starting` or `Stopping`.
+    pub fn next(mut self, event: ScanEvent) -> Self {
+        match (self.state, event) {
+            (_, ScanEvent::Wanted(is_wanted)) => self.is_wanted = is_wanted,
+            (ScanState::Starting, ScanEvent::Started(Ok(()))) => {
+                self.state = ScanState::Scanning;
+                self.error = None;
+            }
+            (ScanState::Starting, ScanEvent::Started(Err(e))) => {
+                self.state = ScanState::Idle;
+                self.is_wanted = false;
+                self.error = Some(format!("Couldn't start scanning: {e}"));
+            }
+            (ScanState::Stopping, ScanEvent::Stopped(Ok(()))) => {
+                self.state = ScanState::Idle;
+                self.error = None;
+            }
+            // server keeps scanning, so it's shown as such
+            // instead of being stopped again and again
+            (ScanState::Stopping, ScanEvent::Stopped(Err(e))) => {
+                self.state = ScanState::Scanning;
+                self.is_wanted = true;
+                self.error = Some(format!("Couldn't stop scanning: {e}"));
+            }
+            (ScanState::Scanning, ScanEvent::Finished) => {
+                self.state = ScanState::Idle;
+                self.is_wanted = false;
+            }
+            // finishing while starting is delivered again once scanning
+            _ => (),
+        }
+        // catch up with changes requested during transition
+        self.state = match (self.state, self.is_wanted) {
+            (ScanState::Idle, true) => ScanState::Starting,
+            (ScanState::Scanning, false) => ScanState::Stopping,
+            (state, _) => state,
+        };
+        self
+    }
+}
+
+pub struct Connection {
+    pub id: u32,
+    pub name: String,
+    /// Primary connection falls back to in-process server
+    pub is_primary: bool,
+    /// `None` means default address
+    pub addr: Option<String>,
+    /// Used only if falling back to in-process server
+    pub user_device_config: Option<String>,
+    pub state: ConnectionState,
+    pub scan: Scan,
+    /// Starting or stopping scanning
+    scan_task: Option<JoinHandle<ScanResult>>,
+    /// Set by server event
+    scanning_finished: SharedBool,
+    /// Indices of devices removed by server, until taken
+    removed_devices: Arc<parking_lot::Mutex<Vec<u32>>>,
+    event_task: Option<JoinHandle<()>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceKey {
+    pub connection: u32,
+    pub index: u32,
+}
+
+impl Connection {
+    fn new(
+        id: u32,
+        name: String,
+        is_primary: bool,
+        addr: Option<String>,
+        user_device_config: Option<String>,
+        is_scanning: bool,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            is_primary,
+            addr,
+            user_device_config,
+            state: ConnectionState::Idle,
+            scan: Scan::new(is_scanning),
+            scan_task: None,
+            scanning_finished: SharedBool::new(false),
+            removed_devices: Default::default(),
+            event_task: None,
+        }
+    }
+
+    fn connect(&mut self, runtime: &Runtime) {
+        let addr = self.addr.clone();
+        let user_device_config = self.user_device_config.clone();
+        let task = if self.is_primary {
+            runtime.spawn(async move {
+                util::start_bp_server(addr, user_device_config)
+                    .await
+                    .map_err(|e| e.to_string())
+            })
+        } else {
+            runtime.spawn(async move {
+                let client = util::connect_external_server(addr.as_deref())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok((client, ServerKind::External))
+            })
+        };
+        self.state = ConnectionState::Connecting(task);
+    }
+
+    pub fn reconnect(&mut self, runtime: &Runtime) {
+        self.disconnect(runtime);
+        self.connect(runtime);
+    }
+
+    fn disconnect(&mut self, runtime: &Runtime) {
+        self.scan.state = ScanState::Idle;
+        if let Some(task) = self.scan_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.event_task.take() {
+            task.abort();
+        }
+        let state = mem::replace(&mut self.state, ConnectionState::Idle);
+        match state {
+            ConnectionState::Connecting(task) => task.abort(),
+            ConnectionState::Connected(client, _) => {
+                // devices are left running by server otherwise
+                runtime.spawn(async move {
+                    let _ = client.stop_all_devices().await;
+                    client.disconnect().await
+                });
+            }
+            ConnectionState::Idle | ConnectionState::Failed(_) => (),
+        }
+    }
+
+    fn poll(&mut self, runtime: &Runtime) {
+        self.poll_scan(runtime);
+        let task = match &mut self.state {
+            ConnectionState::Connecting(task) if task.is_finished() => task,
+            _ => return,
+        };
+        self.state = match runtime.block_on(task) {
+            Ok(Ok((client, kind))) => ConnectionState::Connected(client, kind),
+            Ok(Err(e)) => ConnectionState::Failed(e),
+            Err(e) => ConnectionState::Failed(e.to_string()),
+        };
+        self.on_connected(runtime);
+    }
+
+    fn on_connected(&mut self, runtime: &Runtime) {
+        let Some(client) = self.client() else {
+            return;
+        };
+        let mut events = Box::pin(client.event_stream());
+        let scanning_finished = self.scanning_finished.clone();
+        // indices from previous client would match wrong devices
+        self.removed_devices.lock().clear();
+        let removed_devices = self.removed_devices.clone();
+        self.event_task = Some(runtime.spawn(async move {
+            while let Some(event) = events.next().await {
+                match event {
+                    ButtplugClientEvent::ScanningFinished => {
+                        scanning_finished.store(true);
+                    }
+                    ButtplugClientEvent::DeviceRemoved(device) => {
+                        removed_devices.lock().push(device.index());
+                    }
+                    _ => (),
+                }
+            }
+        }));
+        self.scan = Scan::new(self.scan.is_wanted);
+        self.scan_task = None;
+        self.update_scan(runtime, ScanEvent::Wanted(self.scan.is_wanted));
+    }
+
+    fn poll_scan(&mut self, runtime: &Runtime) {
+        let event = match &mut self.scan_task {
+            Some(task) if task.is_finished() => {
+                let result = runtime
+                    .block_on(task)
+                    .map_err(|e| e.to_string())
+                    .and_then(|x| x.map_err(|e| e.to_string()));
+                self.scan_task = None;
+                match self.scan.state {
+                    ScanState::Starting => ScanEvent::Started(result),
+                    _ => ScanEvent::Stopped(result),
+                }
+            }
+            None if self.scanning_finished.load() => ScanEvent::Finished,
+            _ => return,
+        };
+        self.update_scan(runtime, event);
+    }
+
+    /// Spawns start or stop of scanning when state moves to it
+    fn update_scan(&mut self, runtime: &Runtime, event: ScanEvent) {
+        if self.client().is_none() {
+            // catches up once connected
+            if let ScanEvent::Wanted(is_wanted) = event {
+                self.scan.is_wanted = is_wanted;
+            }
+            return;
+        }
+        let old_state = self.scan.state;
+        self.scan = self.scan.clone().next(event);
+        if self.scan.state == old_state {
+            return;
+        }
+        if self.scan.state == ScanState::Starting {
+            self.scanning_finished.store(false);
+        }
+        let Some(client) = self.client() else {
+            return;
+        };
+        let task = match self.scan.state {
+            ScanState::Starting => Some(runtime.spawn(client.start_scanning())),
+            ScanState::Stopping => Some(runtime.spawn(client.stop_scanning())),
+            ScanState::Idle | ScanState::Scanning => None,
+        };
+        self.scan_task = task;
+    }
+
+    pub fn client(&self) -> Option<&ButtplugClient> {
+        match &self.state {
+            ConnectionState::Connected(client, _) => Some(client),
+            _ => None,
+        }
+    }
+
+    pub fn server_kind(&self) -> Option<ServerKind> {
+        match &self.state {
+            ConnectionState::Connected(_, kind) => Some(*kind),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> String {
+        let status = self.connection_status();
+        match &self.scan.error {
+            Some(e) => format!("{status} ({e})"),
+            None => status,
+        }
+    }
+
+    fn connection_status(&self) -> String {
+        match &self.state {
+            ConnectionState::Idle => "Not connected".into(),
+            ConnectionState::Connecting(_) => "Connecting...".into(),
+            ConnectionState::Connected(client, _) if !client.connected() => {
+                "Disconnected".into()
+            }
+            ConnectionState::Connected(client, kind) => {
+                let server_name = client.server_name();
+                let server_name = server_name.as_deref().unwrap_or("<unknown>");
+                match kind {
+                    ServerKind::External => {
+                        format!("Connected to {server_name}")
+                    }
+                    ServerKind::InProcess => "Using in-process server".into(),
+                }
+            }
+            ConnectionState::Failed(e) => format!("Failed: {e}"),
+        }
+    }
+
+    /// Transitions that are already in progress finish first
+    pub fn set_scanning(&mut self, runtime: &Runtime, is_scanning: bool) {
+        self.update_scan(runtime, ScanEvent::Wanted(is_scanning));
+    }
+
+    /// Replaces client, used when in-process server gets rebuilt
+    pub fn replace_client(
+        &mut self,
+        runtime: &Runtime,
+        client: ButtplugClient,
+        user_device_config: Option<String>,
+    ) {
+        // old client was already disconnected by caller
+        if let Some(task) = self.event_task.take() {
+            task.abort();
+        }
+        self.user_device_config = user_device_config;
+        self.state = ConnectionState::Connected(client, ServerKind::InProcess);
+        self.on_connected(runtime);
+    }
+}
+
+pub struct ConnectionManager {
+    pub connections: Vec<Connection>,
+    next_id: u32,
+}
+
+impl ConnectionManager {
+    pub fn new(
+        runtime: &Runtime,
+        server_addr: Option<String>,
+        user_device_config: Option<String>,
+        extra: &[ConnectionSettings],
+        is_scanning: bool,
+        auto_connect: bool,
+    ) -> Self {
+        let mut this = Self {
+            connections: vec![],
+            next_id: 0,
+        };
+        let id = this.next_id();
+        let mut primary = Connection::new(
+            id,
+            PRIMARY_CONNECTION_NAME.into(),
+            true,
+            server_addr,
+            user_device_config,
+            is_scanning,
+        );
+        if auto_connect {
+            primary.connect(runtime);
+        }
+        this.connections.push(primary);
+        this.sync(runtime, extra);
+        this
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn poll(&mut self, runtime: &Runtime) {
+        for connection in &mut self.connections {
+            connection.poll(runtime);
+        }
+    }
+
+    /// Brings extra connections in line with settings, leaving primary
+    /// connection and unchanged ones alone
+    pub fn sync(&mut self, runtime: &Runtime, extra: &[ConnectionSettings]) {
+        let mut i = 1;
+        while i < self.connections.len() {
+            let connection = &mut self.connections[i];
+            match extra.iter().find(|x| x.name == connection.name) {
+                Some(settings)
+                    if connection.addr.as_deref()
+                        == Some(settings.address.as_str()) =>
+                {
+                    i += 1
+                }
+                Some(settings) => {
+                    connection.addr = Some(settings.address.clone());
+                    connection.reconnect(runtime);
+                    i += 1;
+                }
+                None => {
+                    self.connections.remove(i).disconnect(runtime);
+                }
+            }
+        }
+        for settings in extra {
+            let exists = self
+                .connections
+                .iter()
+                .skip(1)
+                .any(|x| x.name == settings.name);
+            if exists || settings.name.is_empty() {
+                continue;
+            }
+            let id = self.next_id();
+            let mut connection = Connection::new(
+                id,
+                settings.name.clone(),
+                false,
+                Some(settings.address.clone()),
+                None,
+                false,
+            );
+            connection.connect(runtime);
+            self.connections.push(connection);
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Connection> {
+        self.connections.iter().find(|x| x.id == id)
+    }
+
+    pub fn in_process_mut(&mut self) -> Option<&mut Connection> {
+        self.connections
+            .iter_mut()
+            .find(|x| x.server_kind() == Some(ServerKind::InProcess))
+    }
+
+    pub fn has_in_process(&self) -> bool {
+        self.connections
+            .iter()
+            .any(|x| x.server_kind() == Some(ServerKind::InProcess))
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.connections.iter().any(|x| x.scan.is_wanted)
+    }
+
+    pub fn is_scan_pending(&self) -> bool {
+        self.connections.iter().any(|x| x.scan.state.is_pending())
+    }
+
+    pub fn set_scanning(&mut self, runtime: &Runtime, is_scanning: bool) {
+        for connection in &mut self.connections {
+            connection.set_scanning(runtime, is_scanning);
+        }
+    }
+
+    /// Stops scanning and cancels pending connections. With `disconnect`,
+    /// established connections are closed too. Returns ids of connections
+    /// to bring back with [`Self::resume`].
+    pub fn halt(&mut self, runtime: &Runtime, disconnect: bool) -> Vec<u32> {
+        self.set_scanning(runtime, false);
+        let mut halted = vec![];
+        for connection in &mut self.connections {
+            let is_halted = match connection.state {
+                ConnectionState::Connecting(_) => true,
+                ConnectionState::Connected(..) => disconnect,
+                ConnectionState::Idle | ConnectionState::Failed(_) => false,
+            };
+            if is_halted {
+                connection.disconnect(runtime);
+                halted.push(connection.id);
+            }
+        }
+        halted
+    }
+
+    pub fn resume(&mut self, runtime: &Runtime, halted: &[u32]) {
+        for connection in &mut self.connections {
+            if halted.contains(&connection.id) {
+                connection.connect(runtime);
+            }
+        }
+    }
+
+    pub fn stop_all_devices(&self, runtime: &Runtime) {
+        for client in self.connections.iter().filter_map(|x| x.client()) {
+            runtime.spawn(client.stop_all_devices());
+        }
+    }
+
+    /// Devices removed by servers since last call
+    pub fn take_removed_devices(&self) -> Vec<DeviceKey> {
+        let mut removed = vec![];
+        for connection in &self.connections {
+            let indices = mem::take(&mut *connection.removed_devices.lock());
+            removed.extend(indices.into_iter().map(|index| DeviceKey {
+                connection: connection.id,
+                index,
+            }));
+        }
+        removed
+    }
+
+    pub fn devices(
+        &self,
+    ) -> impl Iterator<Item = (DeviceKey, Arc<ButtplugClientDevice>)> + '_ {
+        self.connections.iter().flat_map(|connection| {
+            let id = connection.id;
+            let devices =
+                connection.client().map(|x| x.devices()).unwrap_or_default();
+            devices.into_iter().map(move |device| {
+                let key = DeviceKey {
+                    connection: id,
+                    index: device.index(),
+                };
+                (key, device)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(scan: Scan, events: impl IntoIterator<Item = ScanEvent>) -> Scan {
+        events.into_iter().fold(scan, Scan::next)
+    }
+
+    fn scanning() -> Scan {
+        run(
+            Scan::new(false),
+            [ScanEvent::Wanted(true), ScanEvent::Started(Ok(()))],
+        )
+    }
+
+    #[test]
+    fn scan_starts_and_stops() {
+        let scan = Scan::new(false).next(ScanEvent::Wanted(true));
+        assert_eq!(scan.state, ScanState::Starting);
+        let scan = scan.next(ScanEvent::Started(Ok(())));
+        assert_eq!(scan.state, ScanState::Scanning);
+        let scan = scan.next(ScanEvent::Wanted(false));
+        assert_eq!(scan.state, ScanState::Stopping);
+        let scan = scan.next(ScanEvent::Stopped(Ok(())));
+        assert_eq!(scan, Scan::new(false));
+    }
+
+    #[test]
+    fn toggles_wait_for_transition() {
+        let toggles = [false, true, false].map(ScanEvent::Wanted);
+        let scan = run(Scan::new(false).next(ScanEvent::Wanted(true)), toggles);
+        assert_eq!(scan.state, ScanState::Starting);
+        // last wish wins once start finishes
+        let scan = scan.next(ScanEvent::Started(Ok(())));
+        assert_eq!(scan.state, ScanState::Stopping);
+        let scan = run(scan, [true, false, true].map(ScanEvent::Wanted));
+        assert_eq!(scan.state, ScanState::Stopping);
+        let scan = scan.next(ScanEvent::Stopped(Ok(())));
+        assert_eq!(scan.state, ScanState::Starting);
+        assert!(scan.is_wanted);
+    }
+
+    #[test]
+    fn finish_while_starting_waits_for_start() {
+        let scan = Scan::new(false)
+            .next(ScanEvent::Wanted(true))
+            .next(ScanEvent::Finished);
+        assert_eq!(scan.state, ScanState::Starting);
+        assert!(scan.is_wanted);
+        let scan = scan.next(ScanEvent::Started(Ok(())));
+        assert_eq!(scan.state, ScanState::Scanning);
+        let scan = scan.next(ScanEvent::Finished);
+        assert_eq!(scan, Scan::new(false));
+    }
+
+    #[test]
+    fn failed_start_gives_up() {
+        let scan = Scan::new(false)
+            .next(ScanEvent::Wanted(true))
+            .next(ScanEvent::Started(Err("no adapter".to_owned())));
+        assert_eq!(scan.state, ScanState::Idle);
+        assert!(!scan.is_wanted);
+        assert_eq!(
+            scan.error.as_deref(),
+            Some("Couldn't start scanning: no adapter")
+        );
+        // error stays until next attempt works
+        let scan = scan.next(ScanEvent::Wanted(true));
+        assert!(scan.error.is_some());
+        assert!(scan.next(ScanEvent::Started(Ok(()))).error.is_none());
+    }
+
+    #[test]
+    fn failed_stop_keeps_scanning() {
+        let scan = scanning()
+            .next(ScanEvent::Wanted(false))
+            .next(ScanEvent::Stopped(Err("timed out".to_owned())));
+        assert_eq!(scan.state, ScanState::Scanning);
+        // shown as scanning, so it can be stopped again
+        assert!(scan.is_wanted);
+        assert_eq!(
+            scan.error.as_deref(),
+            Some("Couldn't stop scanning: timed out")
+        );
+        let scan = scan
+            .next(ScanEvent::Wanted(false))
+            .next(ScanEvent::Stopped(Ok(())));
+        assert_eq!(scan, Scan::new(false));
+    }
+}
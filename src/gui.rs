@@ -1,21 +1,27 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    cell::Cell,
+    collections::{BTreeSet, HashMap, VecDeque},
+    fs,
     iter::from_fn,
+    ops::RangeInclusive,
     sync::Arc,
     thread::JoinHandle,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
-use audio_capture::win::capture::AudioCapture;
 use buttplug::{
-    client::{ButtplugClient, ButtplugClientDevice, VibrateCommand},
+    client::{
+        ButtplugClientDevice, LinearCommand, RotateCommand, ScalarCommand,
+        VibrateCommand,
+    },
     core::message::ActuatorType,
 };
 use clap::Parser;
 use eframe::{
     egui::{
-        self, Button, Color32, ProgressBar, RichText, SelectableLabel, Slider,
-        TextFormat, Ui, Visuals, Window,
+        self, pos2, vec2, Button, Color32, DragValue, Key, Label, ProgressBar,
+        Rect, Response, RichText, SelectableLabel, Sense, Slider, Stroke,
+        TextEdit, TextFormat, Ui, Visuals, Window,
     },
     epaint::text::LayoutJob,
     CreationContext, Storage,
@@ -23,14 +29,50 @@ use eframe::{
 use tokio::runtime::Runtime;
 
 use crate::{
-    settings::Settings,
-    util::{self, MinCutoff, SharedF32},
+    auto_setup::{self, AutoSetup, Proposal},
+    capture::{self, capture_thread, mic_thread, AudioFeatures, CaptureStats},
+    connection::{ConnectionManager, ConnectionState, DeviceKey, ScanState},
+    history::{self, ClipHistory, IntensityTimeline, LevelHistory},
+    hotkey::{Hotkey, HotkeyListener, HotkeyStatus},
+    latency::{self, LatencyTest, TestCommand},
+    radio::{BluetoothWatch, RadioStatus},
+    settings::{
+        AlternateSnapshot, BatteryPolling, CaptureSettings, ConnectionSettings,
+        DeviceCalibration, DeviceGroup, DeviceImport, DevicePersistence,
+        DevicePreset, DeviceSnapshot, EndSessionSettings, ImportStatus,
+        LowBatterySettings, RotatorSnapshot, ScalarSnapshot, Sections,
+        Settings, SliderPreview, Snapshot, StrokeSnapshot, ThrottlePolicy,
+        VibratorSnapshot, MIN_PITCH_FACTOR,
+    },
+    throttle::Throttle,
+    util::{
+        self,
+        delay::DelayLine,
+        drop_boost::DropBoost,
+        dynamics::{Compressor, Ducker, DynamicsMode},
+        envelope::{Envelope, Smoother},
+        jitter::Jitter,
+        normalize::RollingMax,
+        pattern::{Alternation, Pattern, PatternShape},
+        spectrum::{
+            Band, BandPowers, BandWeights, CutoffPowers, SpectrumBins,
+            BAND_COUNT, SPECTRUM_BINS, SPECTRUM_MAX_FREQ, SPECTRUM_MIN_FREQ,
+        },
+        tempo::BeatPulse,
+        Channel, ChannelPowers, DetectionMode, Downmix, FilterSlope, Gate,
+        PeakMarker, SharedBool, SharedF32,
+    },
+    wasapi::{self, Endpoint},
 };
 
 #[derive(Parser, Default)]
 pub struct Gui {
     #[clap(short, long)]
     server_addr: Option<String>,
+    /// Start with default settings, without connecting or scanning.
+    /// Saved settings can then be repaired or reset from within the app.
+    #[clap(long)]
+    safe_mode: bool,
 }
 
 pub fn gui(args: Gui) {
@@ -38,40 +80,460 @@ pub fn gui(args: Gui) {
     eframe::run_native(
         "Music Vibes",
         native_options,
-        Box::new(|ctx| Box::new(GuiApp::new(args.server_addr, ctx))),
+        Box::new(|ctx| Box::new(GuiApp::new(args, ctx))),
     );
 }
 
 struct GuiApp {
     runtime: tokio::runtime::Runtime,
-    client: ButtplugClient,
-    devices: HashMap<u32, DeviceProps>,
+    connections: ConnectionManager,
+    devices: HashMap<DeviceKey, DeviceProps>,
+    // props of devices from before device config reload, keyed by identifier
+    detached_devices: HashMap<String, DeviceProps>,
+    /// Devices removed by server, until they come back or are dismissed
+    disconnected_devices: Vec<DisconnectedDevice>,
+    device_config_error: Option<String>,
+    device_config_modified: Option<SystemTime>,
+    device_config_last_check: Instant,
     current_sound_power: SharedF32,
+    band_powers: BandPowers,
+    channel_powers: ChannelPowers,
+    capture_health: CaptureHealth,
+    spectrum: SpectrumBins,
+    features: AudioFeatures,
+    audio_sources: AudioSources,
     _capture_thread: JoinHandle<()>,
-    is_scanning: bool,
+    mic_level: SharedF32,
+    _mic_thread: JoinHandle<()>,
+    ducker: Ducker,
     show_settings: bool,
+    /// Only this device gets commands, others are muted
+    solo_device: Option<DeviceKey>,
+    was_dragging_output: bool,
+    latency_test: LatencyTestWindow,
+    auto_setup: AutoSetupWindow,
+    calibration: Option<Calibration>,
+    snapshots: SnapshotsWindow,
+    bluetooth: BluetoothWatch,
+    stop_hotkey: HotkeyListener,
+    /// Offer to scan, after Bluetooth was turned on
+    show_bluetooth_prompt: bool,
+    /// Names of devices that got low on battery, until dismissed
+    low_battery_alerts: Vec<String>,
+    end_session: Option<EndSession>,
+    /// Set by stop all button, until resumed
+    halted: Option<Halted>,
+    meter_display: MeterDisplay,
+    peak_marker: PeakMarker,
+    silence: SilenceWatch,
+    timeline: IntensityTimeline,
+    levels: LevelHistory,
+    /// Raw input is also drawn on level graph
+    show_input_level: bool,
+    /// Minimum of device under pointer last frame, shown on level graph
+    hovered_min: Option<f32>,
+    /// Frames where any enabled device clipped
+    clips: ClipHistory,
+    /// Raw (main, channel, band, transient, cutoff) powers,
+    /// delayed before any processing
+    level_delay:
+        DelayLine<(f32, [f32; 2], [f32; BAND_COUNT], f32, CutoffPowers)>,
+    normalizer: RollingMax,
+    smoother: Smoother,
+    pattern: Pattern,
+    jitter: Jitter,
+    compressor: Compressor,
+    drop_boost: DropBoost,
+    envelope: Envelope,
+    band_envelopes: [Envelope; BAND_COUNT],
+    /// By low pass cutoff, for devices overriding it
+    cutoff_envelopes: HashMap<u32, Envelope>,
+    safe_mode: Option<SafeMode>,
     // persistent settings
     settings: Settings,
 }
 
 struct DeviceProps {
+    /// Key of persisted data, see `device_identifier`
+    identifier: String,
+    /// Shown instead of identifier, `None` shows it
+    alias: Option<String>,
+    /// Text of alias being edited
+    alias_edit: Option<String>,
     is_enabled: bool,
     battery_state: BatteryState,
+    band: Band,
+    channel: Channel,
+    /// Low pass cutoff in Hz, replacing band and channel,
+    /// `None` follows global filters
+    low_pass_override: Option<f32>,
+    /// Mix of bands replacing single band and channel, `None` uses them
+    band_weights: Option<BandWeights>,
+    /// Response curve exponent, applied before multiplier
+    gamma: f32,
     multiplier: f32,
-    min: f32,
+    gate: Gate,
     max: f32,
+    /// Base of presets, set by applying level calibration
+    calibration: Option<DeviceCalibration>,
+    /// Output is scaled by dominant frequency, lower is stronger
+    use_pitch: bool,
+    /// 0 follows sustained level, 1 only sudden rises
+    transient_blend: f32,
+    /// Quiet parts drive device stronger, loud ones weaker
+    is_inverted: bool,
+    /// How much stereo width boosts output, 0 ignores it
+    width_depth: f32,
+    /// Lines up device with slower ones, 0 disables it
+    delay_ms: f32,
+    /// Output follows `manual_level` instead of audio,
+    /// still bounded by multiplier, min and max
+    is_manual: bool,
+    manual_level: f32,
+    /// Only filled while delay is on
+    delay: DelayLine<SourceLevels>,
     vibrators: Vec<VibratorProps>,
+    /// Rate and depth of vibrators taking turns
+    alternate: AlternateSnapshot,
+    alternation: Alternation,
+    rotators: Vec<RotatorProps>,
+    /// Only for linear devices
+    stroke: Option<StrokeState>,
+    /// Scalar actuators other than vibrators, like constrict
+    scalars: Vec<ScalarProps>,
+    // (speed, is cut off), refreshed at display rate
+    displayed_output: (f32, bool),
+    throttle: Throttle,
+    clips: ClipHistory,
+    peak_marker: PeakMarker,
+    /// Low battery was already reported, until it's charged again
+    is_low_battery_reported: bool,
+    /// Output fades in from here, after being enabled on reconnect
+    ramp_start: Option<Instant>,
+    /// Output fades out from here, then device gets disabled
+    fade_out_start: Option<Instant>,
+    /// Levels of all actuators from last sent commands,
+    /// `None` when nothing was sent since device was last running
+    last_sent: Option<Vec<f64>>,
+    /// Running test pulse, audio commands wait for it
+    test: Option<tokio::task::JoinHandle<()>>,
+}
+
+/// Response of device to level, before gate
+#[derive(Clone, Copy)]
+struct DeviceCurve {
+    gamma: f32,
+    multiplier: f32,
+    max: f32,
+}
+
+impl DeviceCurve {
+    /// Output would go over `max`, before any vibrator limits
+    fn is_clipping(&self, input: f32) -> bool {
+        let curved = input.clamp(0.0, 1.0).powf(self.gamma);
+        curved * self.multiplier > self.max
+    }
+
+    fn apply(&self, input: f32) -> f32 {
+        let curved = input.clamp(0.0, 1.0).powf(self.gamma);
+        (curved * self.multiplier).clamp(0.0, self.max)
+    }
+}
+
+/// Output of `input` level going through group multiplier, device `curve`,
+/// group limits, then battery `ceiling`. Group only scales what device gets,
+/// so it can't push output over device max or ceiling.
+fn grouped_output(
+    input: f32,
+    group: Option<&DeviceGroup>,
+    curve: impl FnOnce(f32) -> f32,
+    ceiling: f32,
+) -> f32 {
+    let input = group.map_or(input, |g| g.scale_input(input));
+    let power = curve(input);
+    let power = group.map_or(power, |g| g.limit(power));
+    power.min(ceiling)
+}
+
+/// Output of device source, with device gate between curve and group limits
+fn gated_output(
+    input: f32,
+    curve: DeviceCurve,
+    gate: &mut Gate,
+    group: Option<&DeviceGroup>,
+    ceiling: f32,
+) -> f32 {
+    grouped_output(input, group, |x| gate.process(curve.apply(x)), ceiling)
+}
+
+/// Quiet level becomes strong and loud one weak
+fn invert_level(level: f32) -> f32 {
+    1.0 - level.clamp(0.0, 1.0)
+}
+
+/// Levels of device and of vibrators following their own source,
+/// delayed together
+#[derive(Clone)]
+struct SourceLevels {
+    device: f32,
+    /// `None` for vibrators following device
+    vibrators: Vec<Option<f32>>,
+}
+
+struct DisconnectedDevice {
+    identifier: String,
+    /// Alias or identifier
+    name: String,
+    was_enabled: bool,
+    since: Instant,
+}
+
+/// What was running before stop all
+struct Halted {
+    enabled_devices: Vec<DeviceKey>,
+    was_scanning: bool,
+    connections: Vec<u32>,
+}
+
+// milliseconds per stroke of linear devices, at full and lowest level
+const STROKE_MIN_DURATION: f32 = 250.0;
+const STROKE_MAX_DURATION: f32 = 1500.0;
+// how often idle stroke task checks level
+const STROKE_IDLE_INTERVAL: Duration = Duration::from_millis(50);
+// pulse of test button, so devices can be told apart,
+// also position linear devices move to
+const TEST_PULSE_SPEED: f64 = 0.5;
+const TEST_PULSE_DURATION: Duration = Duration::from_millis(500);
+// smallest change of level worth sending, for actuators without known steps
+const SEND_EPSILON: f64 = 1e-3;
+// devices coming back within this are enabled again, if they were before
+const RECONNECT_GRACE: Duration = Duration::from_secs(120);
+// seconds of fade in after that
+const RECONNECT_RAMP: f32 = 3.0;
+// wait after failed battery check
+const BATTERY_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+// how often battery task checks if polling was turned back on
+const BATTERY_IDLE_INTERVAL: Duration = Duration::from_secs(1);
+// starting point of device low pass override, in Hz
+const DEVICE_LOW_PASS: f32 = 150.0;
+// split bands are analyzed from mix of all channels
+const MONO_BAND_HINT: &str = "Main level follows a single band, which \
+    is analyzed in mono, so left and right would be the same as mix.\n\
+    Set \"Driven by\" to full range to pick channels";
+// above typical noise floor of idle output, so silence stop can trigger
+const SILENCE_THRESHOLD: f32 = 0.02;
+
+/// Detects audio staying quiet, to stop devices until it returns
+struct SilenceWatch {
+    below_since: Option<Instant>,
+    is_paused: bool,
+}
+
+impl SilenceWatch {
+    fn new() -> Self {
+        Self {
+            below_since: None,
+            is_paused: false,
+        }
+    }
+
+    /// `timeout` in seconds. Returns `true` when pause just started.
+    fn update(&mut self, power: f32, is_enabled: bool, timeout: f32) -> bool {
+        if !is_enabled || power >= SILENCE_THRESHOLD {
+            self.below_since = None;
+            self.is_paused = false;
+            return false;
+        }
+        let since = *self.below_since.get_or_insert_with(Instant::now);
+        let was_paused = self.is_paused;
+        self.is_paused = since.elapsed().as_secs_f32() >= timeout;
+        self.is_paused && !was_paused
+    }
+}
+
+/// Values shown on meters, sampled at display rate instead of every frame,
+/// so numbers stay readable
+struct MeterDisplay {
+    last_update: Instant,
+    sound_power: f32,
+    band_powers: [f32; BAND_COUNT],
+}
+
+impl MeterDisplay {
+    fn new() -> Self {
+        Self {
+            last_update: Instant::now(),
+            sound_power: 0.0,
+            band_powers: [0.0; BAND_COUNT],
+        }
+    }
+
+    /// Returns `true` if displayed values should be refreshed this frame
+    fn tick(&mut self, display_rate: f32) -> bool {
+        let is_due =
+            self.last_update.elapsed().as_secs_f32() >= display_rate.recip();
+        if is_due {
+            self.last_update = Instant::now();
+        }
+        is_due
+    }
+}
+
+// how far back problem counts are shown
+const HEALTH_WINDOW: Duration = Duration::from_secs(60);
+const HEALTH_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+// this many late polls in this time shows a hint, once per session
+const HEALTH_BURST_COUNT: u32 = 5;
+const HEALTH_BURST_WINDOW: Duration = Duration::from_secs(10);
+
+/// Rolling counts of capture problems, from snapshots of total counts
+struct CaptureHealth {
+    stats: CaptureStats,
+    /// (time, empty reads, oversleeps), oldest first
+    snapshots: VecDeque<(Instant, u32, u32)>,
+    show_hint: bool,
+    was_hint_shown: bool,
+}
+
+impl CaptureHealth {
+    fn new(stats: CaptureStats) -> Self {
+        Self {
+            stats,
+            snapshots: VecDeque::new(),
+            show_hint: false,
+            was_hint_shown: false,
+        }
+    }
+
+    fn update(&mut self) {
+        let now = Instant::now();
+        let is_recent = self
+            .snapshots
+            .back()
+            .is_some_and(|(t, ..)| now - *t < HEALTH_SNAPSHOT_INTERVAL);
+        if !is_recent {
+            self.snapshots.push_back((
+                now,
+                self.stats.empty_reads.load(),
+                self.stats.oversleeps.load(),
+            ));
+        }
+        while self
+            .snapshots
+            .front()
+            .is_some_and(|(t, ..)| now - *t > HEALTH_WINDOW)
+        {
+            self.snapshots.pop_front();
+        }
+        let (_, oversleeps) = self.recent(HEALTH_BURST_WINDOW);
+        if !self.was_hint_shown && oversleeps >= HEALTH_BURST_COUNT {
+            self.show_hint = true;
+            self.was_hint_shown = true;
+        }
+    }
+
+    /// Returns (empty reads, oversleeps) within `window`
+    fn recent(&self, window: Duration) -> (u32, u32) {
+        let Some((_, empty_reads, oversleeps)) =
+            self.snapshots.iter().find(|(t, ..)| t.elapsed() <= window)
+        else {
+            return (0, 0);
+        };
+        (
+            self.stats.empty_reads.load() - empty_reads,
+            self.stats.oversleeps.load() - oversleeps,
+        )
+    }
+}
+
+/// Per-frame values shared by all device widgets
+struct FrameState<'a> {
+    sound_power: f32,
+    /// Left and right channel power, after same processing as `sound_power`
+    channel_powers: [f32; 2],
+    /// Main level follows split band, which is analyzed in mono,
+    /// so channel powers are equal to it
+    is_mono_band: bool,
+    /// Power of each band in [`Band::SPLIT`], with main volume and envelope
+    band_powers: [f32; BAND_COUNT],
+    /// Power below low pass cutoffs of devices, same processing as bands
+    cutoff_powers: CutoffPowers,
+    /// Scales output of devices following pitch, `None` without clear pitch
+    pitch_factor: Option<f32>,
+    /// Sudden rises in level, with main volume
+    transient_power: f32,
+    /// Stereo width of source, 0 for mono
+    stereo_width: f32,
+    end_session: Option<&'a EndSessionStage>,
+    refresh_display: bool,
+    slider_preview: SliderPreview,
+    preview_ceiling: f32,
+    throttle_policy: ThrottlePolicy,
+    /// Devices were stopped, nothing is sent until audio returns
+    is_silence_paused: bool,
+    was_dragging_output: bool,
+    /// Set by sliders affecting output
+    is_dragging_output: Cell<bool>,
+    /// Set by enabled devices clamped to their max
+    is_clipping: Cell<bool>,
+    /// Set by device under pointer
+    hovered_min: Cell<Option<f32>>,
+    presets: &'a [DevicePreset],
+    low_battery: LowBatterySettings,
+    fade_out_ms: f32,
+}
+
+impl FrameState<'_> {
+    /// Power below `cutoff` Hz, 0 until capture thread picks it up
+    fn cutoff_power(&self, cutoff: f32) -> f32 {
+        let key = cutoff_key(cutoff);
+        self.cutoff_powers.get(&key).copied().unwrap_or(0.0)
+    }
+
+    /// Split bands are mono, so `channel` only applies to full range
+    fn source_power(&self, band: Band, channel: Channel) -> f32 {
+        if let Some(i) = band.split_index() {
+            return self.band_powers[i];
+        }
+        match channel {
+            Channel::Mix => self.sound_power,
+            Channel::Left => self.channel_powers[0],
+            Channel::Right => self.channel_powers[1],
+        }
+    }
+
+    fn is_dragging_output(&self) -> bool {
+        self.was_dragging_output || self.is_dragging_output.get()
+    }
+
+    /// Limits output while sliders are being dragged, `None` for no limit
+    fn output_limit(&self) -> Option<f32> {
+        let is_limited = self.slider_preview == SliderPreview::Limit;
+        (is_limited && self.is_dragging_output())
+            .then_some(self.preview_ceiling)
+    }
+
+    /// Should device commands be held until sliders are released
+    fn is_output_frozen(&self) -> bool {
+        self.slider_preview == SliderPreview::Freeze
+            && self.is_dragging_output()
+    }
 }
 
 // TEMP: if readout returned an error, SharedF32 will be set to NaN
 struct BatteryState(SharedF32, tokio::task::JoinHandle<()>);
 
 impl BatteryState {
-    pub fn new(runtime: &Runtime, device: Arc<ButtplugClientDevice>) -> Self {
-        let shared_level = SharedF32::new(0.0);
+    pub fn new(
+        runtime: &Runtime,
+        device: Arc<ButtplugClientDevice>,
+        polling: BatteryPolling,
+    ) -> Self {
+        // unknown until first check
+        let shared_level = SharedF32::new(f32::NAN);
         let task = {
             let shared_level = shared_level.clone();
-            runtime.spawn(battery_check_bg_task(device, shared_level))
+            runtime.spawn(battery_check_bg_task(device, shared_level, polling))
         };
         Self(shared_level, task)
     }
@@ -86,205 +548,1401 @@ impl BatteryState {
     }
 }
 
+impl Drop for BatteryState {
+    fn drop(&mut self) {
+        self.1.abort();
+    }
+}
+
+/// Stroke settings of linear device, and its stroke task while enabled
+struct StrokeState {
+    /// Milliseconds per stroke at full level
+    min_duration: f32,
+    /// Milliseconds per stroke at lowest level
+    max_duration: f32,
+    /// Quiet parts make strokes shorter, not only slower
+    use_depth: bool,
+    shared: StrokeShared,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+/// Read by stroke task before each stroke
+#[derive(Clone)]
+struct StrokeShared {
+    level: SharedF32,
+    min_duration: SharedF32,
+    max_duration: SharedF32,
+    use_depth: SharedBool,
+}
+
+impl StrokeState {
+    fn new() -> Self {
+        Self {
+            min_duration: STROKE_MIN_DURATION,
+            max_duration: STROKE_MAX_DURATION,
+            use_depth: false,
+            shared: StrokeShared {
+                level: SharedF32::new(0.0),
+                min_duration: SharedF32::new(STROKE_MIN_DURATION),
+                max_duration: SharedF32::new(STROKE_MAX_DURATION),
+                use_depth: SharedBool::new(false),
+            },
+            task: None,
+        }
+    }
+
+    /// Starts or stops stroke task, and passes it current level
+    fn update(
+        &mut self,
+        runtime: &Runtime,
+        device: &Arc<ButtplugClientDevice>,
+        is_enabled: bool,
+        level: f32,
+    ) {
+        self.shared.level.store(level);
+        self.shared.min_duration.store(self.min_duration);
+        self.shared.max_duration.store(self.max_duration);
+        self.shared.use_depth.store(self.use_depth);
+        if !is_enabled {
+            self.stop();
+        } else if self.task.is_none() {
+            let task = stroke_bg_task(device.clone(), self.shared.clone());
+            self.task = Some(runtime.spawn(task));
+        }
+    }
+
+    /// Next update starts it again, if still enabled
+    fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for StrokeState {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Moves linear device between ends, faster with higher level,
+/// and also further with `use_depth`. Waits while level is 0.
+async fn stroke_bg_task(
+    device: Arc<ButtplugClientDevice>,
+    shared: StrokeShared,
+) {
+    let mut is_up = false;
+    loop {
+        let level = shared.level.load().clamp(0.0, 1.0);
+        if level <= 0.0 {
+            tokio::time::sleep(STROKE_IDLE_INTERVAL).await;
+            continue;
+        }
+        let start = tokio::time::Instant::now();
+        let fastest = shared.min_duration.load();
+        let slowest = shared.max_duration.load();
+        let duration = (slowest + (fastest - slowest) * level).max(1.0) as u32;
+        let depth = if shared.use_depth.load() { level } else { 1.0 };
+        is_up = !is_up;
+        let offset = if is_up { depth / 2.0 } else { -depth / 2.0 };
+        let position = (0.5 + offset) as f64;
+        let _ = device
+            .linear(&LinearCommand::Linear(duration, position))
+            .await;
+        let stroke_time = Duration::from_millis(duration as u64);
+        tokio::time::sleep_until(start + stroke_time).await;
+    }
+}
+
+/// Levels of test pulse for each kind of actuator,
+/// kinds left empty aren't sent
+struct TestPulse {
+    vibrators: Vec<f64>,
+    rotators: Vec<(f64, bool)>,
+    scalars: HashMap<u32, (f64, ActuatorType)>,
+    is_linear: bool,
+}
+
+/// Runs actuators of `pulse` for a moment, then stops device
+async fn test_pulse_bg_task(
+    device: Arc<ButtplugClientDevice>,
+    pulse: TestPulse,
+) {
+    // devices reject commands for actuators they don't have
+    if !pulse.vibrators.is_empty() {
+        let command = VibrateCommand::SpeedVec(pulse.vibrators);
+        let _ = device.vibrate(&command).await;
+    }
+    if !pulse.rotators.is_empty() {
+        let command = RotateCommand::RotateVec(pulse.rotators);
+        let _ = device.rotate(&command).await;
+    }
+    if !pulse.scalars.is_empty() {
+        let command = ScalarCommand::ScalarMap(pulse.scalars);
+        let _ = device.scalar(&command).await;
+    }
+    if pulse.is_linear {
+        let duration = TEST_PULSE_DURATION.as_millis() as u32;
+        let command = LinearCommand::Linear(duration, TEST_PULSE_SPEED);
+        let _ = device.linear(&command).await;
+    }
+    tokio::time::sleep(TEST_PULSE_DURATION).await;
+    let _ = device.stop().await;
+}
+
+/// Checks battery level until device disconnects,
+/// retrying less often after errors
 async fn battery_check_bg_task(
     device: Arc<ButtplugClientDevice>,
     shared_level: SharedF32,
+    polling: BatteryPolling,
 ) {
-    let mut interval = tokio::time::interval(Duration::from_secs(5));
-    loop {
-        interval.tick().await;
-        match device.battery_level().await {
-            Ok(level) => shared_level.store(level as f32),
+    while device.connected() {
+        if !polling.is_enabled.load() {
+            shared_level.store(f32::NAN);
+            tokio::time::sleep(BATTERY_IDLE_INTERVAL).await;
+            continue;
+        }
+        let wait = match device.battery_level().await {
+            Ok(level) => {
+                shared_level.store(level as f32);
+                let interval = polling.interval.load().max(1.0);
+                Duration::from_secs_f32(interval)
+            }
             Err(_) => {
                 shared_level.store(f32::NAN);
-                break;
+                BATTERY_RETRY_INTERVAL
             }
-        }
+        };
+        tokio::time::sleep(wait).await;
     }
+    shared_level.store(f32::NAN);
 }
 
 impl DeviceProps {
-    fn new(runtime: &Runtime, device: Arc<ButtplugClientDevice>) -> Self {
-        let vibe_count = device
+    /// Starts from values in `saved` included in mask, if given
+    fn new(
+        runtime: &Runtime,
+        device: Arc<ButtplugClientDevice>,
+        identifier: String,
+        saved: Option<(&DeviceSnapshot, &DevicePersistence)>,
+        polling: BatteryPolling,
+    ) -> Self {
+        let vibrators = device
             .message_attributes()
             .scalar_cmd()
-            .as_ref()
+            .iter()
+            .flatten()
+            .filter(|x| x.actuator_type() == &ActuatorType::Vibrate)
             .map(|x| {
-                x.iter()
-                    .filter(|x| x.actuator_type() == &ActuatorType::Vibrate)
-                    .count()
+                VibratorProps::new(
+                    x.feature_descriptor().clone(),
+                    *x.step_count(),
+                )
             })
-            .unwrap_or_default();
-        let vibrators = from_fn(|| Some(VibratorProps::default()))
-            .take(vibe_count)
             .collect();
-        Self {
+        let rotate_count = device
+            .message_attributes()
+            .rotate_cmd()
+            .as_ref()
+            .map_or(0, |x| x.len());
+        let rotators = from_fn(|| Some(RotatorProps::default()))
+            .take(rotate_count)
+            .collect();
+        let has_linear = device
+            .message_attributes()
+            .linear_cmd()
+            .as_ref()
+            .is_some_and(|x| !x.is_empty());
+        let stroke = has_linear.then(StrokeState::new);
+        // vibrators are driven by vibrate commands instead
+        let scalars = device
+            .message_attributes()
+            .scalar_cmd()
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, x)| x.actuator_type() != &ActuatorType::Vibrate)
+            .map(|(i, x)| ScalarProps::new(i as u32, *x.actuator_type()))
+            .collect();
+        let mut props = Self {
+            identifier,
+            alias: None,
+            alias_edit: None,
             is_enabled: false,
-            battery_state: BatteryState::new(runtime, device),
+            battery_state: BatteryState::new(runtime, device, polling),
+            band: Band::FullRange,
+            channel: Channel::Mix,
+            low_pass_override: None,
+            band_weights: None,
+            gamma: 1.0,
             multiplier: 1.0,
-            min: 0.0,
+            gate: Gate::default(),
             max: 1.0,
+            calibration: None,
+            use_pitch: false,
+            transient_blend: 0.0,
+            is_inverted: false,
+            width_depth: 0.0,
+            delay_ms: 0.0,
+            is_manual: false,
+            manual_level: 0.0,
+            delay: DelayLine::new(),
+            vibrators,
+            alternate: AlternateSnapshot::default(),
+            alternation: Alternation::new(),
+            rotators,
+            stroke,
+            scalars,
+            displayed_output: (0.0, false),
+            throttle: Throttle::new(),
+            clips: ClipHistory::new(),
+            peak_marker: PeakMarker::new(),
+            is_low_battery_reported: false,
+            ramp_start: None,
+            fade_out_start: None,
+            last_sent: None,
+            test: None,
+        };
+        if let Some((saved, mask)) = saved {
+            let mut values = props.snapshot();
+            mask.apply(saved, &mut values);
+            // never turn devices on by themselves
+            props.restore(&values, false);
+            props.alias = saved.alias.clone();
+        }
+        props
+    }
+
+    fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.identifier)
+    }
+
+    fn reattach(
+        mut self,
+        runtime: &Runtime,
+        device: Arc<ButtplugClientDevice>,
+        polling: BatteryPolling,
+    ) -> Self {
+        self.battery_state = BatteryState::new(runtime, device, polling);
+        self.throttle = Throttle::new();
+        // task still holds old device
+        if let Some(stroke) = &mut self.stroke {
+            stroke.stop();
+        }
+        self
+    }
+
+    fn snapshot(&self) -> DeviceSnapshot {
+        let vibrators = self
+            .vibrators
+            .iter()
+            .map(|x| VibratorSnapshot {
+                is_enabled: x.is_enabled,
+                multiplier: x.multiplier,
+                gate: x.gate.clone(),
+                max: x.max,
+                band: x.band,
+                channel: x.channel,
+            })
+            .collect();
+        let rotators = self
+            .rotators
+            .iter()
+            .map(|x| RotatorSnapshot {
+                is_enabled: x.is_enabled,
+                multiplier: x.multiplier,
+                gate: x.gate.clone(),
+                max: x.max,
+                clockwise: x.clockwise,
+            })
+            .collect();
+        let scalars = self
+            .scalars
+            .iter()
+            .map(|x| ScalarSnapshot {
+                index: x.index,
+                actuator: x.actuator_name(),
+                is_enabled: x.is_enabled,
+                multiplier: x.multiplier,
+                gate: x.gate.clone(),
+                max: x.max,
+            })
+            .collect();
+        DeviceSnapshot {
+            identifier: self.identifier.clone(),
+            alias: self.alias.clone(),
+            is_enabled: self.is_enabled,
+            band: self.band,
+            channel: self.channel,
+            low_pass_override: self.low_pass_override,
+            band_weights: self.band_weights,
+            gamma: self.gamma,
+            multiplier: self.multiplier,
+            gate: self.gate.clone(),
+            max: self.max,
+            use_pitch: self.use_pitch,
+            transient_blend: self.transient_blend,
+            is_inverted: self.is_inverted,
+            width_depth: self.width_depth,
+            delay_ms: self.delay_ms,
+            is_manual: self.is_manual,
+            manual_level: self.manual_level,
             vibrators,
+            rotators,
+            stroke: self.stroke.as_ref().map(|x| StrokeSnapshot {
+                min_duration: x.min_duration,
+                max_duration: x.max_duration,
+                use_depth: x.use_depth,
+            }),
+            alternate: self.alternate,
+            scalars,
+            calibration: self.calibration,
+        }
+    }
+
+    fn restore(&mut self, snapshot: &DeviceSnapshot, restore_enabled: bool) {
+        if restore_enabled {
+            self.is_enabled = snapshot.is_enabled;
+        }
+        self.band = snapshot.band;
+        self.channel = snapshot.channel;
+        self.low_pass_override = snapshot.low_pass_override;
+        self.band_weights = snapshot.band_weights;
+        self.gamma = snapshot.gamma;
+        self.multiplier = snapshot.multiplier;
+        self.gate = snapshot.gate.clone();
+        self.max = snapshot.max;
+        self.calibration = snapshot.calibration;
+        self.use_pitch = snapshot.use_pitch;
+        self.transient_blend = snapshot.transient_blend;
+        self.is_inverted = snapshot.is_inverted;
+        self.width_depth = snapshot.width_depth;
+        self.delay_ms = snapshot.delay_ms;
+        self.is_manual = snapshot.is_manual;
+        self.manual_level = snapshot.manual_level;
+        for (props, x) in self.vibrators.iter_mut().zip(&snapshot.vibrators) {
+            props.is_enabled = x.is_enabled;
+            props.multiplier = x.multiplier;
+            props.gate = x.gate.clone();
+            props.max = x.max;
+            props.band = x.band;
+            props.channel = x.channel;
+        }
+        for (props, x) in self.rotators.iter_mut().zip(&snapshot.rotators) {
+            props.is_enabled = x.is_enabled;
+            props.multiplier = x.multiplier;
+            props.gate = x.gate.clone();
+            props.max = x.max;
+            props.clockwise = x.clockwise;
+        }
+        self.alternate = snapshot.alternate;
+        if let (Some(props), Some(x)) = (&mut self.stroke, snapshot.stroke) {
+            props.min_duration = x.min_duration;
+            props.max_duration = x.max_duration;
+            props.use_depth = x.use_depth;
+        }
+        for props in &mut self.scalars {
+            let saved = snapshot.scalars.iter().find(|x| {
+                x.index == props.index && x.actuator == props.actuator_name()
+            });
+            if let Some(x) = saved {
+                props.is_enabled = x.is_enabled;
+                props.multiplier = x.multiplier;
+                props.gate = x.gate.clone();
+                props.max = x.max;
+            }
         }
     }
 }
 
 impl DeviceProps {
-    fn calculate_visual_output(&self, input: f32) -> (f32, bool) {
-        let power = (input * self.multiplier).clamp(0.0, self.max);
-        (power, power < self.min)
+    fn calculate_visual_output(
+        &self,
+        input: f32,
+        group: Option<&DeviceGroup>,
+        low_battery: &LowBatterySettings,
+    ) -> (f32, bool) {
+        let curve = self.curve();
+        let mut is_cut_off = false;
+        let power = grouped_output(
+            input,
+            group,
+            |x| {
+                let power = curve.apply(x);
+                is_cut_off = !self.gate.is_passing(power);
+                power
+            },
+            self.battery_ceiling(low_battery),
+        );
+        (power, is_cut_off)
+    }
+
+    /// Flips level in invert mode, before multiplier and limits
+    fn invert(&self, level: f32) -> f32 {
+        if self.is_inverted {
+            invert_level(level)
+        } else {
+            level
+        }
     }
 
-    fn calculate_output(&self, input: f32) -> f32 {
-        (input * self.multiplier)
-            .clamp(0.0, self.max)
-            .min_cutoff(self.min)
+    /// Mixes sustained `level` with `transient` by transient blend
+    fn blend_transient(&self, level: f32, transient: f32) -> f32 {
+        let blend = self.transient_blend.clamp(0.0, 1.0);
+        level * (1.0 - blend) + transient * blend
     }
-}
 
-fn capture_thread(sound_power: SharedF32, low_pass_freq: SharedF32) -> ! {
-    let dur = Duration::from_millis(1);
-    let mut capture = AudioCapture::init(dur).unwrap();
+    /// Source levels of device and its vibrators, before delay
+    fn source_levels(&self, frame_state: &FrameState) -> SourceLevels {
+        let device = match (self.low_pass_override, self.band_weights) {
+            (Some(cutoff), _) => frame_state.cutoff_power(cutoff),
+            (None, Some(weights)) => weights.mix(&frame_state.band_powers),
+            (None, None) => frame_state.source_power(self.band, self.channel),
+        };
+        let vibrators = self
+            .vibrators
+            .iter()
+            .map(|v| match (v.band, v.channel) {
+                (None, None) => None,
+                (band, channel) => Some(frame_state.source_power(
+                    band.unwrap_or(self.band),
+                    channel.unwrap_or(self.channel),
+                )),
+            })
+            .collect();
+        SourceLevels { device, vibrators }
+    }
 
-    let format = capture.format().unwrap();
-    // time to fill about half of AudioCapture's buffer
-    let actual_duration = Duration::from_secs_f32(
-        dur.as_secs_f32() * capture.buffer_frame_size as f32
-            / format.sample_rate as f32
-            / 1000.,
-    ) / 2;
+    /// Source level shaped by transient blend, pitch and width, then
+    /// inverted, so ramp and fade work on what's felt
+    fn shape_level(&self, level: f32, frame_state: &FrameState) -> f32 {
+        let level = self.blend_transient(level, frame_state.transient_power);
+        // unclear pitch falls back to loudness, so devices don't drop out
+        let level = match frame_state.pitch_factor {
+            Some(factor) if self.use_pitch => level * factor,
+            _ => level,
+        };
+        let width = frame_state.stereo_width.clamp(0.0, 1.0);
+        self.invert(level * (1.0 + self.width_depth * width))
+    }
 
-    let buffer_duration = Duration::from_millis(20);
-    let buffer_size = (format.sample_rate as f32
-        * buffer_duration.as_secs_f32()) as usize
-        * format.channels as usize;
-    let mut buf = VecDeque::new();
-    buf.resize(buffer_size, 0.0);
+    /// Delayed level after reconnect `ramp` and manual level,
+    /// ready for curve and limits
+    fn finish_level(&self, level: f32, ramp: f32) -> f32 {
+        if self.is_manual {
+            self.manual_level
+        } else {
+            level * ramp
+        }
+    }
 
-    capture.start().unwrap();
-    loop {
-        std::thread::sleep(actual_duration);
-        capture
-            .read_samples::<(), _>(|samples, _| {
-                for value in samples {
-                    buf.push_front(*value);
-                }
-                buf.truncate(buffer_size);
-                Ok(())
+    /// Levels of device and vibrators following their own source,
+    /// all going through same processing
+    fn process_levels(&mut self, frame_state: &FrameState) -> SourceLevels {
+        let SourceLevels { device, vibrators } =
+            self.source_levels(frame_state);
+        let shape = |x: f32| self.shape_level(x, frame_state);
+        let levels = SourceLevels {
+            device: shape(device),
+            vibrators: vibrators.into_iter().map(|x| x.map(shape)).collect(),
+        };
+        let levels = if self.delay_ms > 0.0 {
+            let delay = Duration::from_secs_f32(self.delay_ms / 1000.0);
+            self.delay.process(levels, delay)
+        } else {
+            self.delay.clear();
+            levels
+        };
+        let ramp = self.ramp_factor();
+        SourceLevels {
+            device: self.finish_level(levels.device, ramp),
+            vibrators: levels
+                .vibrators
+                .into_iter()
+                .map(|x| x.map(|x| self.finish_level(x, ramp)))
+                .collect(),
+        }
+    }
+
+    fn calculate_output(
+        &mut self,
+        input: f32,
+        group: Option<&DeviceGroup>,
+        low_battery: &LowBatterySettings,
+    ) -> f32 {
+        let ceiling = self.battery_ceiling(low_battery);
+        gated_output(input, self.curve(), &mut self.gate, group, ceiling)
+    }
+
+    /// Output of vibrator following its own source. Device gate is set
+    /// for device source, so it's skipped.
+    fn calculate_vibrator_output(
+        &self,
+        input: f32,
+        group: Option<&DeviceGroup>,
+        low_battery: &LowBatterySettings,
+    ) -> f32 {
+        let curve = self.curve();
+        let ceiling = self.battery_ceiling(low_battery);
+        grouped_output(input, group, |x| curve.apply(x), ceiling)
+    }
+
+    fn is_battery_low(&self, low_battery: &LowBatterySettings) -> bool {
+        let level = self.battery_state.get_level();
+        level.is_some_and(|x| x < low_battery.threshold)
+    }
+
+    /// Output limit from low battery derating, 1 when not limited
+    fn battery_ceiling(&self, low_battery: &LowBatterySettings) -> f32 {
+        if low_battery.derate && self.is_battery_low(low_battery) {
+            low_battery.ceiling
+        } else {
+            1.0
+        }
+    }
+
+    /// Clears test once it's finished
+    fn is_testing(&mut self) -> bool {
+        if self.test.as_ref().is_some_and(|x| x.is_finished()) {
+            self.test = None;
+        }
+        self.test.is_some()
+    }
+
+    /// Pulses vibrator at `vibrator`, or all of them for `None`
+    fn has_actuators(&self) -> bool {
+        !self.vibrators.is_empty()
+            || !self.rotators.is_empty()
+            || !self.scalars.is_empty()
+            || self.stroke.is_some()
+    }
+
+    /// Pulses all actuators, or only `vibrator` if given
+    fn start_test(
+        &mut self,
+        runtime: &Runtime,
+        device: Arc<ButtplugClientDevice>,
+        vibrator: Option<usize>,
+    ) {
+        let vibrators = (0..self.vibrators.len())
+            .map(|i| {
+                if vibrator.is_none() || vibrator == Some(i) {
+                    TEST_PULSE_SPEED
+                } else {
+                    0.0
+                }
             })
-            .unwrap();
+            .collect();
+        let is_whole = vibrator.is_none();
+        let rotators = self
+            .rotators
+            .iter()
+            .filter(|_| is_whole)
+            .map(|r| (TEST_PULSE_SPEED, r.clockwise))
+            .collect();
+        let scalars = self
+            .scalars
+            .iter()
+            .filter(|_| is_whole)
+            .map(|s| (s.index, (TEST_PULSE_SPEED, s.actuator)))
+            .collect();
+        let pulse = TestPulse {
+            vibrators,
+            rotators,
+            scalars,
+            is_linear: is_whole && self.stroke.is_some(),
+        };
+        // stroke task gets 0 while testing, but could be mid stroke
+        if let (true, Some(stroke)) = (is_whole, &mut self.stroke) {
+            stroke.stop();
+        }
+        self.test = Some(runtime.spawn(test_pulse_bg_task(device, pulse)));
+    }
+
+    /// Output factor of fade in after reconnect, 1 when done
+    fn ramp_factor(&mut self) -> f32 {
+        let Some(start) = self.ramp_start else {
+            return 1.0;
+        };
+        let factor = start.elapsed().as_secs_f32() / RECONNECT_RAMP;
+        if factor >= 1.0 || !self.is_enabled {
+            self.ramp_start = None;
+            return 1.0;
+        }
+        factor
+    }
+
+    fn is_fading_out(&self) -> bool {
+        self.fade_out_start.is_some()
+    }
+
+    /// Output factor of fade out, 1 when not fading
+    fn fade_out_factor(&self, fade_ms: f32) -> f32 {
+        self.fade_out_start.map_or(1.0, |start| {
+            let elapsed = start.elapsed().as_secs_f32() * 1000.0;
+            (1.0 - elapsed / fade_ms).max(0.0)
+        })
+    }
+
+    /// Disables device, after fading out over `fade_ms` if it's over 0
+    fn disable(
+        &mut self,
+        runtime: &Runtime,
+        device: &ButtplugClientDevice,
+        fade_ms: f32,
+    ) {
+        if fade_ms > 0.0 && self.is_enabled {
+            self.fade_out_start.get_or_insert_with(Instant::now);
+        } else {
+            self.is_enabled = false;
+            self.fade_out_start = None;
+            runtime.spawn(device.stop());
+        }
+    }
+
+    /// Returns `true` once each time battery gets low
+    fn update_low_battery(&mut self, low_battery: &LowBatterySettings) -> bool {
+        let is_low = self.is_battery_low(low_battery);
+        let is_new = is_low && !self.is_low_battery_reported;
+        self.is_low_battery_reported = is_low;
+        is_new
+    }
+
+    fn curve(&self) -> DeviceCurve {
+        DeviceCurve {
+            gamma: self.gamma,
+            multiplier: self.multiplier,
+            max: self.max,
+        }
+    }
+
+    fn apply_preset(&mut self, preset: &DevicePreset) {
+        let preset = preset.scaled(self.calibration);
+        self.multiplier = preset.multiplier;
+        self.gate.open = preset.min;
+        self.gate.close = preset.min;
+        self.max = preset.max;
+        self.gamma = preset.gamma;
+    }
 
-        let buf = buf.make_contiguous();
-        let rc = 1.0 / low_pass_freq.load();
-        let filtered = util::low_pass(buf, dur, rc, format.channels as _);
-        let speeds = util::calculate_power(&filtered, format.channels as _);
-        let avg = util::avg(&speeds).clamp(0.0, 1.0);
-        sound_power.store(avg);
+    fn matches_preset(&self, preset: &DevicePreset) -> bool {
+        let preset = preset.scaled(self.calibration);
+        let approx_eq = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        approx_eq(self.multiplier, preset.multiplier)
+            && approx_eq(self.gate.open, preset.min)
+            && approx_eq(self.gate.close, preset.min)
+            && approx_eq(self.max, preset.max)
+            && approx_eq(self.gamma, preset.gamma)
+    }
+}
+
+// seconds
+const END_SESSION_PULSE_PERIOD: f32 = 0.8;
+const END_SESSION_PULSE_ON: f32 = 0.3;
+
+struct EndSession {
+    start: Instant,
+}
+
+enum EndSessionStage {
+    RampDown(f32),
+    Pulse(f32),
+    Finished,
+}
+
+impl EndSession {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    fn stage(&self, settings: &EndSessionSettings) -> EndSessionStage {
+        let elapsed = self.start.elapsed().as_secs_f32();
+        if elapsed < settings.duration {
+            return EndSessionStage::RampDown(
+                1.0 - elapsed / settings.duration,
+            );
+        }
+        let pulses = (elapsed - settings.duration) / END_SESSION_PULSE_PERIOD;
+        if pulses < settings.pulse_count as f32 {
+            let is_on = pulses.fract()
+                < END_SESSION_PULSE_ON / END_SESSION_PULSE_PERIOD;
+            let level = if is_on { settings.pulse_strength } else { 0.0 };
+            return EndSessionStage::Pulse(level);
+        }
+        EndSessionStage::Finished
+    }
+}
+
+impl EndSessionStage {
+    fn apply(&self, level: f32) -> f32 {
+        match *self {
+            Self::RampDown(factor) => level * factor,
+            Self::Pulse(pulse) => pulse,
+            Self::Finished => 0.0,
+        }
     }
 }
 
 impl GuiApp {
-    fn new(server_addr: Option<String>, ctx: &CreationContext) -> Self {
+    fn new(args: Gui, ctx: &CreationContext) -> Self {
+        let saved_settings = ctx.storage.map(Settings::load);
+        let (settings, safe_mode) = if args.safe_mode {
+            (Settings::default(), Some(SafeMode::new(saved_settings)))
+        } else {
+            (saved_settings.unwrap_or_default(), None)
+        };
+
         let runtime = tokio::runtime::Runtime::new().unwrap();
-        let client = runtime
-            .block_on(util::start_bp_server(server_addr))
-            .unwrap();
+        let (user_device_config, device_config_error) = match settings
+            .read_user_device_config()
+            .transpose()
+        {
+            Ok(config) => (config, None),
+            Err(e) => (None, Some(format!("Couldn't read device config: {e}"))),
+        };
+        let connections = ConnectionManager::new(
+            &runtime,
+            args.server_addr,
+            user_device_config,
+            &settings.connections,
+            settings.start_scanning_on_startup,
+            !args.safe_mode,
+        );
         let devices = Default::default();
         let current_sound_power = SharedF32::new(0.0);
         let current_sound_power2 = current_sound_power.clone();
+        let band_powers = BandPowers::new();
+        let band_powers2 = band_powers.clone();
+        let channel_powers = ChannelPowers::new();
+        let channel_powers2 = channel_powers.clone();
+        let capture_stats = CaptureStats::new();
+        let capture_stats2 = capture_stats.clone();
+        let spectrum = SpectrumBins::new();
+        let spectrum2 = spectrum.clone();
+        let features = AudioFeatures::new();
+        let features2 = features.clone();
 
-        let settings = ctx.storage.map(Settings::load).unwrap_or_default();
-        let low_pass_freq = settings.low_pass_freq.clone();
+        let capture_settings = settings.capture.clone();
 
         let _capture_thread = std::thread::spawn(|| {
-            capture_thread(current_sound_power2, low_pass_freq)
+            capture_thread(
+                current_sound_power2,
+                band_powers2,
+                channel_powers2,
+                capture_stats2,
+                spectrum2,
+                features2,
+                capture_settings,
+            )
         });
 
-        let is_scanning = settings.start_scanning_on_startup;
-        if is_scanning {
-            runtime.spawn(client.start_scanning());
-        }
+        let mic_level = SharedF32::new(0.0);
+        let mic_level2 = mic_level.clone();
+        let use_mic_ducking = settings.capture.use_mic_ducking.clone();
+        let _mic_thread =
+            std::thread::spawn(|| mic_thread(mic_level2, use_mic_ducking));
+
+        let device_config_modified =
+            fs::metadata(&settings.user_device_config_path)
+                .and_then(|x| x.modified())
+                .ok();
 
         GuiApp {
             runtime,
-            client,
+            connections,
             devices,
+            detached_devices: Default::default(),
+            disconnected_devices: vec![],
+            device_config_error,
+            device_config_modified,
+            device_config_last_check: Instant::now(),
             current_sound_power,
+            band_powers,
+            channel_powers,
+            capture_health: CaptureHealth::new(capture_stats),
+            spectrum,
+            features,
+            audio_sources: AudioSources::new(),
             _capture_thread,
-            is_scanning,
+            mic_level,
+            _mic_thread,
+            ducker: Ducker::new(),
             show_settings: false,
+            solo_device: None,
+            was_dragging_output: false,
+            latency_test: LatencyTestWindow::default(),
+            auto_setup: AutoSetupWindow::default(),
+            calibration: None,
+            snapshots: SnapshotsWindow::default(),
+            bluetooth: BluetoothWatch::new(),
+            stop_hotkey: HotkeyListener::new(
+                ctx.egui_ctx.clone(),
+                settings.stop_hotkey,
+            ),
+            show_bluetooth_prompt: false,
+            low_battery_alerts: vec![],
+            end_session: None,
+            halted: None,
+            meter_display: MeterDisplay::new(),
+            peak_marker: PeakMarker::new(),
+            silence: SilenceWatch::new(),
+            timeline: IntensityTimeline::new(),
+            levels: LevelHistory::new(),
+            show_input_level: false,
+            hovered_min: None,
+            clips: ClipHistory::new(),
+            level_delay: DelayLine::new(),
+            normalizer: RollingMax::new(),
+            smoother: Smoother::new(),
+            pattern: Pattern::new(),
+            jitter: Jitter::new(),
+            compressor: Compressor::new(),
+            drop_boost: DropBoost::new(),
+            envelope: Envelope::new(),
+            band_envelopes: std::array::from_fn(|_| Envelope::new()),
+            cutoff_envelopes: HashMap::new(),
+            safe_mode,
             settings,
         }
     }
-}
 
-impl eframe::App for GuiApp {
-    fn save(&mut self, storage: &mut dyn Storage) {
-        self.settings.save(storage);
-        storage.flush();
-    }
+    fn stop_all_devices(&mut self) {
+        self.end_session = None;
+        self.solo_device = None;
+        self.latency_test.test = None;
+        let fade_ms = self.settings.fade_out_ms;
+        // emergency stop stays immediate, unless asked otherwise
+        let is_fading = self.settings.stop_all_fades && fade_ms > 0.0;
+        if !is_fading {
+            self.connections.stop_all_devices(&self.runtime);
+        }
+        let hidden = &self.settings.hidden_devices;
+        for (key, device) in self.connections.devices() {
+            let props = self
+                .devices
+                .get_mut(&key)
+                .filter(|x| !hidden.contains(&x.identifier));
+            let Some(props) = props else {
+                if is_fading {
+                    self.runtime.spawn(device.stop());
+                }
+                continue;
+            };
+            props.is_manual = false;
+            props.manual_level = 0.0;
+            if is_fading {
+                props.disable(&self.runtime, &device, fade_ms);
+            } else {
+                props.is_enabled = false;
+                props.fade_out_start = None;
+            }
+        }
+    }
+
+    /// Stops devices, scanning and pending connections, remembering
+    /// what was running, so it can be resumed
+    fn halt(&mut self) {
+        let enabled_devices = self
+            .devices
+            .iter()
+            .filter(|(_, props)| props.is_enabled)
+            .map(|(key, _)| *key)
+            .collect();
+        let was_scanning = self.connections.is_scanning();
+        self.stop_all_devices();
+        let connections = self
+            .connections
+            .halt(&self.runtime, self.settings.stop_all_disconnects);
+        self.halted = Some(Halted {
+            enabled_devices,
+            was_scanning,
+            connections,
+        });
+    }
+
+    fn resume(&mut self) {
+        let Some(halted) = self.halted.take() else {
+            return;
+        };
+        self.connections.resume(&self.runtime, &halted.connections);
+        if halted.was_scanning {
+            self.connections.set_scanning(&self.runtime, true);
+        }
+        for key in halted.enabled_devices {
+            if let Some(props) = self.devices.get_mut(&key) {
+                props.is_enabled = true;
+                props.fade_out_start = None;
+            }
+        }
+    }
+
+    fn reload_device_config(&mut self) {
+        let config = match self.settings.read_user_device_config().transpose() {
+            Ok(config) => config,
+            Err(e) => {
+                self.device_config_error =
+                    Some(format!("Couldn't read device config: {e}"));
+                return;
+            }
+        };
+        let server = match util::build_in_process_server(config.clone()) {
+            Ok(server) => server,
+            Err(e) => {
+                self.device_config_error =
+                    Some(format!("Couldn't load device config: {e}"));
+                return;
+            }
+        };
+        self.device_config_error = None;
+
+        let Some(connection) = self.connections.in_process_mut() else {
+            return;
+        };
+        if let Some(client) = connection.client() {
+            for device in client.devices() {
+                let key = DeviceKey {
+                    connection: connection.id,
+                    index: device.index(),
+                };
+                if let Some(props) = self.devices.remove(&key) {
+                    self.detached_devices
+                        .insert(props.identifier.clone(), props);
+                }
+            }
+            // old server has to let go of devices before new one can find them
+            if let Err(e) = self.runtime.block_on(client.disconnect()) {
+                self.device_config_error =
+                    Some(format!("Couldn't disconnect from old server: {e}"));
+            }
+        }
+        self.devices
+            .retain(|key, _| key.connection != connection.id);
+        match self
+            .runtime
+            .block_on(util::connect_in_process_server(server))
+        {
+            Ok(client) => {
+                connection.replace_client(&self.runtime, client, config);
+                connection.set_scanning(&self.runtime, true);
+            }
+            Err(e) => {
+                self.device_config_error =
+                    Some(format!("Couldn't start server: {e}"));
+            }
+        }
+    }
+
+    fn check_device_config_changes(&mut self) {
+        let can_reload = self.connections.has_in_process();
+        if !can_reload
+            || !self.settings.watch_user_device_config
+            || self.device_config_last_check.elapsed() < Duration::from_secs(1)
+        {
+            return;
+        }
+        self.device_config_last_check = Instant::now();
+        let modified = fs::metadata(&self.settings.user_device_config_path)
+            .and_then(|x| x.modified())
+            .ok();
+        if modified != self.device_config_modified {
+            self.device_config_modified = modified;
+            self.reload_device_config();
+        }
+    }
+}
+
+impl eframe::App for GuiApp {
+    fn save(&mut self, storage: &mut dyn Storage) {
+        // don't touch saved settings until user decides what to do with them
+        if self.safe_mode.is_some() {
+            return;
+        }
+        if self.settings.remember_device_settings {
+            for (key, _) in self.connections.devices() {
+                if let Some(props) = self.devices.get(&key) {
+                    self.settings.remember_device(props.snapshot());
+                }
+            }
+        }
+        self.settings.save(storage);
+        storage.flush();
+    }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let end_session_stage = self
+            .end_session
+            .as_ref()
+            .map(|x| x.stage(&self.settings.end_session));
+        if let Some(EndSessionStage::Finished) = end_session_stage {
+            self.stop_all_devices();
+            if self.settings.end_session.exit_after {
+                frame.close();
+            }
+        }
+        if self.stop_hotkey.hotkey() != self.settings.stop_hotkey {
+            self.stop_hotkey =
+                HotkeyListener::new(ctx.clone(), self.settings.stop_hotkey);
+        }
+        if self.stop_hotkey.take_pressed() {
+            // halting again would lose what resume brings back
+            if self.halted.is_some() {
+                self.stop_all_devices();
+            } else {
+                self.halt();
+            }
+        }
+        self.connections.poll(&self.runtime);
+        for key in self.connections.take_removed_devices() {
+            self.on_device_removed(key);
+        }
+        // don't keep everyone muted after soloed device goes away
+        if let Some(solo) = self.solo_device {
+            if !self.connections.devices().any(|(key, _)| key == solo) {
+                self.solo_device = None;
+            }
+        }
+        self.check_device_config_changes();
+        self.capture_health.update();
+        if self.bluetooth.poll() && !self.connections.is_scanning() {
+            self.show_bluetooth_prompt = true;
+        }
         let visuals = match self.settings.use_dark_mode {
             true => Visuals::dark(),
             false => Visuals::light(),
         };
         ctx.set_visuals(visuals);
+        let mut device_imports = vec![];
+        let safe_mode_action = self.safe_mode.as_mut().and_then(|x| {
+            let connected: Vec<_> =
+                self.devices.values().map(DeviceProps::snapshot).collect();
+            device_imports = x.device_imports(&self.settings, &connected);
+            safe_mode_widget(ctx, x, &mut device_imports)
+        });
+        if let Some(action) = safe_mode_action {
+            let safe_mode = self.safe_mode.take().unwrap();
+            if let (SafeModeAction::Repair, Some(saved)) =
+                (action, safe_mode.saved)
+            {
+                self.settings.import_sections(saved, safe_mode.sections);
+                self.settings.import_devices(&device_imports);
+                let connected = device_imports.iter().filter(|x| {
+                    x.accept && x.status == ImportStatus::Connected
+                });
+                for import in connected {
+                    let props = self
+                        .devices
+                        .values_mut()
+                        .find(|x| x.identifier == import.snapshot.identifier);
+                    if let Some(props) = props {
+                        props.restore(&import.snapshot, false);
+                    }
+                }
+                self.connections
+                    .sync(&self.runtime, &self.settings.connections);
+            }
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
-                let scan_label = if self.is_scanning {
-                    "Stop scanning"
-                } else {
-                    "Start scanning"
+                let is_scanning = self.connections.is_scanning();
+                let is_pending = self.connections.is_scan_pending();
+                let scan_label = match (is_pending, is_scanning) {
+                    (true, true) => "Starting scan...",
+                    (true, false) => "Stopping scan...",
+                    (false, true) => "Stop scanning",
+                    (false, false) => "Start scanning",
                 };
-                if ui.selectable_label(self.is_scanning, scan_label).clicked() {
-                    self.is_scanning = !self.is_scanning;
-                    if self.is_scanning {
-                        self.runtime.spawn(self.client.start_scanning());
-                    } else {
-                        self.runtime.spawn(self.client.stop_scanning());
-                    }
+                let scan_button = SelectableLabel::new(is_scanning, scan_label);
+                let can_scan = !is_pending && self.halted.is_none();
+                if ui.add_enabled(can_scan, scan_button).clicked() {
+                    self.connections.set_scanning(&self.runtime, !is_scanning);
                 }
 
                 if ui.button("Settings").clicked() {
                     self.show_settings = true;
+                    self.audio_sources = AudioSources::new();
                 }
 
-                let stop_button_width = 120.0;
-                ui.add_space(ui.available_width() - stop_button_width);
+                if ui.button("Latency test").clicked() {
+                    self.latency_test.is_open = true;
+                }
 
-                let stop_button = Button::new(
-                    RichText::new("Stop all devices").color(Color32::BLACK),
-                )
-                .fill(Color32::from_rgb(240, 0, 0));
+                if ui.button("Auto setup").clicked() {
+                    self.auto_setup.is_open = true;
+                }
+
+                if ui.button("Snapshots").clicked() {
+                    self.snapshots.is_open = true;
+                }
+
+                let end_session_label = if self.end_session.is_some() {
+                    "Ending session..."
+                } else {
+                    "End session"
+                };
+                let end_session_button = Button::new(end_session_label);
                 if ui
-                    .add_sized([stop_button_width, 30.0], stop_button)
+                    .add_enabled(self.end_session.is_none(), end_session_button)
+                    .on_hover_text(
+                        "Gently ramps down all devices, then disables them",
+                    )
                     .clicked()
                 {
-                    self.runtime.spawn(self.client.stop_all_devices());
-                    for device in self.devices.values_mut() {
-                        device.is_enabled = false;
+                    self.end_session = Some(EndSession::new());
+                }
+
+                if self.solo_device.is_some() {
+                    let solo_button = Button::new(
+                        RichText::new("SOLO").strong().color(Color32::BLACK),
+                    )
+                    .fill(Color32::YELLOW);
+                    if ui
+                        .add(solo_button)
+                        .on_hover_text("Click to unmute all devices")
+                        .clicked()
+                    {
+                        self.solo_device = None;
+                    }
+                }
+
+                if self.bluetooth.status() == RadioStatus::Off {
+                    ui.colored_label(Color32::YELLOW, "Bluetooth is off")
+                        .on_hover_text(
+                            "Scanning won't find Bluetooth devices \
+                            until it's turned on",
+                        );
+                }
+                if self.stop_hotkey.status() == HotkeyStatus::Failed {
+                    ui.colored_label(Color32::YELLOW, "Stop hotkey unavailable")
+                        .on_hover_text(
+                            "It might be used by another app, \
+                            a different one can be set in settings",
+                        );
+                }
+
+                if self.halted.is_some() {
+                    let text = RichText::new("ALL STOPPED").strong();
+                    ui.label(text.color(Color32::RED));
+                } else if self.silence.is_paused {
+                    ui.weak("Paused (silence)").on_hover_text(
+                        "Devices resume when audio comes back",
+                    );
+                }
+
+                let stop_button_width = 120.0;
+                ui.add_space(ui.available_width() - stop_button_width);
+
+                if self.halted.is_some() {
+                    let resume_button = Button::new(
+                        RichText::new("Resume").color(Color32::BLACK),
+                    )
+                    .fill(Color32::from_rgb(0, 200, 0));
+                    if ui
+                        .add_sized([stop_button_width, 30.0], resume_button)
+                        .on_hover_text(
+                            "Brings back enabled devices, scanning \
+                            and connections from before stopping",
+                        )
+                        .clicked()
+                    {
+                        self.resume();
+                    }
+                } else {
+                    let stop_button = Button::new(
+                        RichText::new("Stop all devices").color(Color32::BLACK),
+                    )
+                    .fill(Color32::from_rgb(240, 0, 0));
+                    if ui
+                        .add_sized([stop_button_width, 30.0], stop_button)
+                        .on_hover_text(
+                            "Also stops scanning and pending connections",
+                        )
+                        .clicked()
+                    {
+                        self.halt();
                     }
                 }
             });
+            connections_widget(ui, &mut self.connections, &self.runtime);
             ui.separator();
             let main_mul = self.settings.main_volume.powi(2);
+            let current_power = capture::take_level(
+                &self.current_sound_power,
+                &self.features.peak,
+                self.settings.capture.peak_since_frame.load(),
+            );
+            let wanted_cutoffs: BTreeSet<_> = self
+                .devices
+                .values()
+                .filter_map(|x| x.low_pass_override)
+                .map(cutoff_key)
+                .collect();
+            self.band_powers.want_cutoffs(&wanted_cutoffs);
+            self.cutoff_envelopes
+                .retain(|cutoff, _| wanted_cutoffs.contains(cutoff));
+            // delayed before hold and decay, so they work on delayed levels
+            let (
+                raw_power,
+                raw_channel_powers,
+                raw_band_powers,
+                transient,
+                raw_cutoff_powers,
+            ) = self.level_delay.process(
+                (
+                    current_power,
+                    self.channel_powers.load(),
+                    self.band_powers.load(),
+                    self.features.transient.load(),
+                    self.band_powers.load_cutoffs(),
+                ),
+                self.settings.level_delay(),
+            );
+            // always updated, so maximum is ready when turned on
+            let normalized = self
+                .normalizer
+                .process(raw_power, self.settings.normalize_window);
+            let level = if self.settings.use_normalize {
+                normalized
+            } else {
+                raw_power
+            };
+            let sound_power = (level * main_mul).clamp(0.0, 1.0);
+            let sound_power = self
+                .compressor
+                .process(sound_power, self.settings.compressor_params());
+            let sound_power = self
+                .smoother
+                .process(sound_power, self.settings.smoothing_time());
+            let envelope_params = self.settings.envelope_params();
             let sound_power =
-                (self.current_sound_power.load() * main_mul).clamp(0.0, 1.0);
+                self.envelope.process(sound_power, envelope_params);
+            let (boost, fired) = self
+                .drop_boost
+                .process(raw_power, self.settings.drop_boost_params());
+            if fired && self.settings.use_drop_boost {
+                self.timeline.add_marker();
+            }
+            let sound_power = if self.settings.use_drop_boost {
+                (sound_power * boost).clamp(0.0, 1.0)
+            } else {
+                sound_power
+            };
+            // unclear tempo keeps following level, instead of pulsing wrong
+            let bpm = self.features.bpm.load();
+            let sound_power = if bpm > 0.0 {
+                // phase is fresh, so it's moved back to match delayed levels
+                let delay = self.settings.level_delay().as_secs_f32();
+                let phase = self.features.beat_phase.load();
+                let phase = (phase - delay * bpm / 60.0).rem_euclid(1.0);
+                sound_power * self.settings.beat_pulse.gain(phase)
+            } else {
+                sound_power
+            };
+            // shared by all devices, so they vary together
+            let jitter = self.jitter.process(
+                self.settings.jitter_amount,
+                self.settings.jitter_rate_hz,
+            );
+            let sound_power = (sound_power * jitter).clamp(0.0, 1.0);
+            let pattern = self.pattern.process(self.settings.pattern_params());
+            let blend = self.settings.pattern_blend.clamp(0.0, 1.0);
+            let sound_power = sound_power * (1.0 - blend) + pattern * blend;
+            let duck_gain = self
+                .ducker
+                .process(self.mic_level.load(), self.settings.duck_params());
+            let sound_power = sound_power * duck_gain;
+            // pattern, hold and boosts would keep going on silence
+            let is_source_muted = self.capture_health.stats.is_muted.load()
+                && self.settings.capture.pause_when_muted.load();
+            let sound_power = if is_source_muted { 0.0 } else { sound_power };
+            // channels follow mix envelope, keeping their share of it
+            let channel_powers = raw_channel_powers.map(|x| {
+                if raw_power > 0.0 {
+                    (sound_power * x / raw_power).clamp(0.0, 1.0)
+                } else {
+                    sound_power
+                }
+            });
+            // same gain as level, but not smoothed, that would blunt it
+            let level_gain = if raw_power > 0.0 {
+                level / raw_power
+            } else {
+                1.0
+            };
+            let transient_power =
+                (transient * level_gain * main_mul).clamp(0.0, 1.0);
+            let band_powers = std::array::from_fn(|i| {
+                let power = (raw_band_powers[i] * main_mul).clamp(0.0, 1.0);
+                let power =
+                    self.band_envelopes[i].process(power, envelope_params);
+                if is_source_muted {
+                    0.0
+                } else {
+                    power
+                }
+            });
+            let cutoff_powers = raw_cutoff_powers
+                .into_iter()
+                .map(|(cutoff, power)| {
+                    let power = (power * main_mul).clamp(0.0, 1.0);
+                    let power = self
+                        .cutoff_envelopes
+                        .entry(cutoff)
+                        .or_insert_with(Envelope::new)
+                        .process(power, envelope_params);
+                    (cutoff, if is_source_muted { 0.0 } else { power })
+                })
+                .collect();
+            self.timeline.record(sound_power);
+            self.levels
+                .record(sound_power, (raw_power * main_mul).clamp(0.0, 1.0));
+            let silence_started = self.silence.update(
+                sound_power,
+                self.settings.use_silence_stop,
+                self.settings.silence_timeout,
+            );
+            if silence_started {
+                for (key, device) in self.connections.devices() {
+                    let props = self.devices.get(&key);
+                    if props.is_some_and(|x| x.is_enabled && !x.is_inverted) {
+                        self.runtime.spawn(device.stop());
+                    }
+                }
+            }
+            // every frame, so short peaks between refreshes aren't missed
+            let peak = self.peak_marker.process(sound_power);
+            let refresh_display =
+                self.meter_display.tick(self.settings.display_rate);
+            if refresh_display {
+                self.meter_display.sound_power = sound_power;
+                self.meter_display.band_powers = raw_band_powers;
+            }
             ui.horizontal(|ui| {
+                let displayed_power = self.meter_display.sound_power;
                 ui.label(format!(
                     "Current volume: {:.2}%",
-                    sound_power * 100.0
+                    displayed_power * 100.0
                 ));
-                ui.add(ProgressBar::new(sound_power));
+                let bar = ui.add(
+                    ProgressBar::new(displayed_power)
+                        .desired_width(ui.available_width() / 2.0),
+                );
+                peak_marker_widget(ui, bar.rect, peak);
+                band_meters_widget(ui, &self.meter_display.band_powers);
+                clip_light_widget(ui, &self.clips);
             });
 
+            let is_dragging_output = Cell::new(false);
             ui.horizontal(|ui| {
                 let r1 = ui.label("Main volume: ");
                 let mut volume_as_percent = self.settings.main_volume * 100.0;
-                let r2 = ui.add(
-                    Slider::new(&mut volume_as_percent, 0.0..=500.0)
-                        .suffix("%"),
+                let r2 = output_slider(
+                    ui,
+                    &mut volume_as_percent,
+                    |x| Slider::new(x, 0.0..=500.0).suffix("%"),
+                    &is_dragging_output,
                 );
                 self.settings.main_volume = volume_as_percent / 100.0;
                 let mut text = LayoutJob::default();
@@ -311,180 +1969,4175 @@ impl eframe::App for GuiApp {
                          // Warning!!! It's exponential, so 200% is 4 times stronger",
                 );
 
-                let mut low_pass_freq = self.settings.low_pass_freq.load();
-                let r1 = ui.label("Low pass freq.: ");
-                let r2 = ui.add(
-                    Slider::new(&mut low_pass_freq, 0.0..=20_000.0)
-                        .logarithmic(true)
-                        .integer(),
+                filter_widget(ui, &self.settings.capture);
+            });
+            if self.settings.capture.show_filter_meters.load() {
+                let stats = &self.capture_health.stats;
+                filter_meters_widget(
+                    ui,
+                    stats.unfiltered_power.load(),
+                    stats.filtered_power.load(),
                 );
-                r1.union(r2).on_hover_text_at_pointer(
-                    "Filters out frequencies above this one,\n\
-                    leaving only lower frequencies.\n\
-                    Defaults to max (20_000 Hz)",
+            }
+            smoothing_widget(ui, &mut self.settings);
+            power_band_widget(ui, &self.settings.capture);
+            bass_emphasis_widget(ui, &self.settings.capture);
+            pattern_widget(ui, &mut self.settings);
+            beat_pulse_widget(ui, &mut self.settings, self.features.bpm.load());
+            ui.collapsing("Session timeline", |ui| {
+                timeline_widget(ui, &self.timeline);
+            });
+            ui.collapsing("Level graph", |ui| {
+                ui.checkbox(&mut self.show_input_level, "Show input level")
+                    .on_hover_text(
+                        "Level before attack, hold, decay and compressor",
+                    );
+                level_graph_widget(
+                    ui,
+                    &self.levels,
+                    self.show_input_level,
+                    self.hovered_min,
+                );
+            });
+            let notice = self.capture_health.stats.notice.load();
+            if !notice.is_empty() {
+                ui.colored_label(Color32::YELLOW, notice);
+            }
+            if is_source_muted {
+                ui.colored_label(Color32::YELLOW, "Source muted")
+                    .on_hover_text(
+                        "Captured device or app is muted in Windows, \
+                        so output is paused. Can be turned off in settings",
+                    );
+            }
+            if self.settings.capture.use_mic_ducking.load() {
+                mic_meter_widget(
+                    ui,
+                    self.mic_level.load(),
+                    self.settings.duck_threshold,
+                    self.ducker.is_ducking(),
                 );
-                self.settings.low_pass_freq.store(low_pass_freq);
+            }
+            let channels = self.capture_health.stats.channels.load() as u16;
+            if self.settings.capture.use_side.load() && channels == 1 {
+                ui.weak("Source is mono, so center rejection has no effect");
+            }
+            // stereo is always averaged
+            if channels > 2 {
+                downmix_widget(ui, &self.settings.capture, channels);
+            }
+            capture_hint_widget(ui, &mut self.capture_health.show_hint);
+            ui.collapsing("Capture diagnostics", |ui| {
+                capture_health_widget(ui, &self.capture_health);
+            });
+            // capture thread skips spectrum bins while it's collapsed
+            let bins = self.spectrum.load();
+            let spectrum = ui.collapsing("Spectrum", |ui| {
+                spectrum_widget(ui, &bins, &self.settings.capture);
             });
+            let is_spectrum_shown = spectrum.body_returned.is_some();
+            let is_sampling = self.auto_setup.sampling.is_some();
+            self.spectrum
+                .is_enabled
+                .store(is_spectrum_shown || is_sampling);
             ui.separator();
 
             ui.heading("Devices");
-            for device in self.client.devices() {
-                let props =
-                    self.devices.entry(device.index()).or_insert_with(|| {
-                        DeviceProps::new(&self.runtime, device.clone())
-                    });
-                device_widget(ui, device, props, sound_power, &self.runtime);
+            // nothing can be turned back on, except through resume
+            ui.set_enabled(self.halted.is_none());
+            let connected_devices: Vec<_> = self
+                .connections
+                .devices()
+                .filter_map(|(key, _)| self.devices.get(&key))
+                .map(|x| x.identifier.clone())
+                .collect();
+            for (i, group) in self.settings.groups.iter_mut().enumerate() {
+                if group.connected_count(&connected_devices) > 0 {
+                    group_header_widget(ui, i, group);
+                }
+            }
+            let presets = self.settings.presets.clone();
+            let groups = self.settings.groups.clone();
+            let frame_state = FrameState {
+                sound_power,
+                channel_powers,
+                is_mono_band: self
+                    .settings
+                    .capture
+                    .power_band
+                    .load()
+                    .split_index()
+                    .is_some(),
+                band_powers,
+                cutoff_powers,
+                pitch_factor: self
+                    .settings
+                    .pitch_factor(self.features.pitch.load()),
+                transient_power,
+                stereo_width: self.features.width.load(),
+                end_session: end_session_stage.as_ref(),
+                refresh_display,
+                slider_preview: self.settings.slider_preview,
+                preview_ceiling: self.settings.preview_ceiling,
+                throttle_policy: self.settings.throttle_policy,
+                is_silence_paused: self.silence.is_paused,
+                was_dragging_output: self.was_dragging_output,
+                is_dragging_output,
+                is_clipping: Cell::new(false),
+                hovered_min: Cell::new(None),
+                presets: &presets,
+                low_battery: self.settings.low_battery,
+                fade_out_ms: self.settings.fade_out_ms,
+            };
+            let show_connection = self.connections.connections.len() > 1;
+            for (key, device) in self.connections.devices() {
+                if !self.devices.contains_key(&key) {
+                    let props = self.attach_device(device.clone());
+                    self.devices.insert(key, props);
+                }
+                let Some(props) = self.devices.get_mut(&key) else {
+                    continue;
+                };
+                if self.settings.hidden_devices.contains(&props.identifier) {
+                    continue;
+                }
+                let low_battery = &self.settings.low_battery;
+                if props.update_low_battery(low_battery) && low_battery.notify
+                {
+                    let name = props.display_name().to_owned();
+                    self.low_battery_alerts.push(name);
+                }
+                let slot = self.settings.device_slot(&props.identifier);
+                let connection = self
+                    .connections
+                    .get(key.connection)
+                    .filter(|_| show_connection)
+                    .map(|x| x.name.as_str());
+                let solo = match self.solo_device {
+                    None => SoloState::Off,
+                    Some(solo) if solo == key => SoloState::Soloed,
+                    Some(_) => SoloState::Muted,
+                };
+                let info = DeviceInfo {
+                    slot,
+                    connection,
+                    solo,
+                    groups: &groups,
+                };
+                let action = device_widget(
+                    ui,
+                    device.clone(),
+                    props,
+                    &info,
+                    &frame_state,
+                    &self.runtime,
+                );
+                match action {
+                    Some(DeviceAction::ToggleSolo) => {
+                        self.solo_device = match solo {
+                            SoloState::Soloed => None,
+                            _ => Some(key),
+                        };
+                    }
+                    Some(DeviceAction::Hide) => {
+                        if let Some(stroke) = &mut props.stroke {
+                            stroke.stop();
+                        }
+                        self.runtime.spawn(device.stop());
+                        if solo == SoloState::Soloed {
+                            self.solo_device = None;
+                        }
+                        self.settings
+                            .hidden_devices
+                            .insert(props.identifier.clone());
+                    }
+                    Some(DeviceAction::SetGroup(index)) => {
+                        DeviceGroup::assign(
+                            &mut self.settings.groups,
+                            &props.identifier,
+                            index,
+                        );
+                    }
+                    None => (),
+                }
             }
+            disconnected_devices_widget(ui, &mut self.disconnected_devices);
+            self.was_dragging_output = frame_state.is_dragging_output.get();
+            self.clips.record(frame_state.is_clipping.get());
+            self.hovered_min = frame_state.hovered_min.get();
         });
-        settings_window_widget(
+        let connected_devices: Vec<_> = self
+            .connections
+            .devices()
+            .filter_map(|(key, _)| self.devices.get(&key))
+            .map(|x| x.identifier.clone())
+            .collect();
+        let response = settings_window_widget(
             ctx,
             &mut self.show_settings,
             &mut self.settings,
+            self.connections.has_in_process(),
+            self.device_config_error.as_deref(),
+            &connected_devices,
+            &self.audio_sources,
+        );
+        if response.start_calibration {
+            self.calibration = Some(Calibration::new());
+        }
+        if response.refresh_audio_sources {
+            self.audio_sources = AudioSources::new();
+        }
+        if response.reload_device_config {
+            self.reload_device_config();
+        }
+        if response.apply_connections {
+            self.connections
+                .sync(&self.runtime, &self.settings.connections);
+            let connections = &self.connections;
+            self.devices
+                .retain(|key, _| connections.get(key.connection).is_some());
+        }
+        let hidden = &self.settings.hidden_devices;
+        let devices: Vec<_> = self
+            .connections
+            .devices()
+            .filter(|(key, _)| {
+                !self
+                    .devices
+                    .get(key)
+                    .is_some_and(|x| hidden.contains(&x.identifier))
+            })
+            .map(|(key, device)| {
+                let name = self
+                    .devices
+                    .get(&key)
+                    .map_or(device.name().as_str(), |x| x.display_name());
+                (key, name.to_owned())
+            })
+            .collect();
+        if bluetooth_prompt_widget(ctx, &mut self.show_bluetooth_prompt) {
+            self.connections.set_scanning(&self.runtime, true);
+        }
+        low_battery_widget(ctx, &mut self.low_battery_alerts);
+        match latency_test_widget(ctx, &mut self.latency_test, &devices) {
+            Some(LatencyTestAction::Start) => self.start_latency_test(),
+            Some(LatencyTestAction::LineUp) => self.line_up_device_delays(),
+            None => (),
+        }
+        self.update_latency_test();
+        let auto_setup_action =
+            auto_setup_widget(ctx, &mut self.auto_setup, &self.settings);
+        self.update_auto_setup(auto_setup_action);
+        let calibration_action = self
+            .calibration
+            .as_mut()
+            .and_then(|x| calibration_widget(ctx, x));
+        self.update_calibration(calibration_action);
+        let snapshot_action = snapshots_widget(
+            ctx,
+            &mut self.snapshots,
+            &self.settings.snapshots,
+            self.halted.is_some(),
         );
+        self.update_snapshots(snapshot_action);
         ctx.request_repaint();
     }
 }
 
-fn settings_window_widget(
-    ctx: &egui::Context,
-    show_settings: &mut bool,
-    settings: &mut Settings,
-) {
-    Window::new("Settings")
-        .open(show_settings)
-        .resizable(false)
-        .collapsible(false)
-        .show(ctx, |ui| {
-            ui.checkbox(&mut settings.use_dark_mode, "Use dark mode");
-            ui.checkbox(
-                &mut settings.start_scanning_on_startup,
-                "Start scanning on startup",
-            );
-        });
-}
+impl GuiApp {
+    fn start_latency_test(&mut self) {
+        let Some(key) = self.latency_test.device else {
+            return;
+        };
+        // test drives device on its own
+        if let Some(props) = self.devices.get_mut(&key) {
+            props.is_enabled = false;
+        }
+        self.latency_test.test = Some(LatencyTest::new(key));
+    }
 
-struct VibratorProps {
-    is_enabled: bool,
-    multiplier: f32,
-    min: f32,
-    max: f32,
+    fn update_latency_test(&mut self) {
+        let window = &mut self.latency_test;
+        let Some(test) = &mut window.test else {
+            return;
+        };
+        let device = self
+            .connections
+            .devices()
+            .find(|(key, _)| *key == test.device)
+            .map(|(_, device)| device);
+        let Some(device) = device else {
+            window.test = None;
+            return;
+        };
+        if !window.is_open {
+            self.runtime.spawn(device.stop());
+            window.test = None;
+            return;
+        }
+        match test.update(self.current_sound_power.load()) {
+            Some(TestCommand::PlayClick) => latency::play_click(),
+            Some(TestCommand::StartPulse) => {
+                let pulse = VibrateCommand::Speed(latency::PULSE_LEVEL);
+                self.runtime.spawn(device.vibrate(&pulse));
+            }
+            Some(TestCommand::StopPulse) => {
+                self.runtime.spawn(device.stop());
+            }
+            None => (),
+        }
+        if let Some(average) = test.device_average() {
+            if test.is_finished() {
+                window.measured.insert(test.device, average);
+            }
+        }
+    }
+
+    /// Delays faster devices, so all measured ones react together.
+    /// Reaction time is in every measurement, so it cancels out.
+    fn line_up_device_delays(&mut self) {
+        let measured = &self.latency_test.measured;
+        let Some(slowest) = measured.values().max().copied() else {
+            return;
+        };
+        for (key, latency) in measured {
+            if let Some(props) = self.devices.get_mut(key) {
+                let delay = (slowest - *latency).as_secs_f32() * 1000.0;
+                props.delay_ms = delay.min(500.0);
+            }
+        }
+    }
 }
 
-impl Default for VibratorProps {
-    fn default() -> Self {
-        Self {
-            is_enabled: true,
-            multiplier: 1.0,
-            min: 0.0,
-            max: 1.0,
+impl GuiApp {
+    fn update_auto_setup(&mut self, action: Option<AutoSetupAction>) {
+        let window = &mut self.auto_setup;
+        // closing window reverts proposal that wasn't accepted
+        let action = if window.is_open {
+            action
+        } else {
+            window.sampling = None;
+            Some(AutoSetupAction::Revert)
+        };
+        match action {
+            Some(AutoSetupAction::Start) => {
+                window.sampling = Some(AutoSetup::new());
+                window.was_silent = false;
+            }
+            Some(AutoSetupAction::Preview) => {
+                if let Some(review) = &window.review {
+                    apply_auto_setup_review(
+                        review,
+                        &mut self.settings,
+                        &mut self.devices,
+                    );
+                }
+            }
+            Some(AutoSetupAction::Accept) => window.review = None,
+            Some(AutoSetupAction::Revert) => {
+                if let Some(mut review) = window.review.take() {
+                    review.preview = [false; 3];
+                    apply_auto_setup_review(
+                        &review,
+                        &mut self.settings,
+                        &mut self.devices,
+                    );
+                }
+            }
+            None => (),
+        }
+        let Some(sampling) = &mut window.sampling else {
+            return;
+        };
+        // levels before main volume, so proposal doesn't depend on it
+        let level = self.current_sound_power.load();
+        sampling.record(level, &self.spectrum.load());
+        if !sampling.is_finished() {
+            return;
         }
+        let proposal = sampling.proposal();
+        window.sampling = None;
+        window.was_silent = proposal.is_none();
+        let Some(proposal) = proposal else {
+            return;
+        };
+        let original_gates = self
+            .devices
+            .iter()
+            .map(|(key, props)| (*key, (props.gate.open, props.gate.close)))
+            .collect();
+        let review = AutoSetupReview {
+            proposal,
+            original_main_volume: self.settings.main_volume,
+            original_low_pass_freq: self.settings.capture.low_pass_freq.load(),
+            original_gates,
+            preview: [true; 3],
+        };
+        apply_auto_setup_review(&review, &mut self.settings, &mut self.devices);
+        window.review = Some(review);
     }
 }
 
-fn device_widget(
-    ui: &mut Ui,
-    device: Arc<ButtplugClientDevice>,
-    props: &mut DeviceProps,
-    sound_power: f32,
-    runtime: &Runtime,
+/// Writes proposed values for previewed items, and original ones for rest
+fn apply_auto_setup_review(
+    review: &AutoSetupReview,
+    settings: &mut Settings,
+    devices: &mut HashMap<DeviceKey, DeviceProps>,
 ) {
-    ui.group(|ui| {
-        if cfg!(debug_assertions) {
-            ui.label(format!("({}) {}", device.index(), device.name()));
+    let [main_volume, min, low_pass] = review.preview;
+    let proposal = &review.proposal;
+    settings.main_volume = if main_volume {
+        proposal.main_volume
+    } else {
+        review.original_main_volume
+    };
+    settings.capture.low_pass_freq.store(if low_pass {
+        proposal.low_pass_freq
+    } else {
+        review.original_low_pass_freq
+    });
+    for (key, (open, close)) in &review.original_gates {
+        let Some(props) = devices.get_mut(key) else {
+            continue;
+        };
+        (props.gate.open, props.gate.close) = if min {
+            (proposal.min, proposal.min)
         } else {
-            ui.label(device.name());
-        }
+            (*open, *close)
+        };
+    }
+}
 
-        if let Some(bat) = props.battery_state.get_level() {
-            ui.label(format!("Battery: {}%", bat * 100.0));
-        }
+#[derive(Default)]
+struct AutoSetupWindow {
+    is_open: bool,
+    sampling: Option<AutoSetup>,
+    /// Last sampling heard nothing
+    was_silent: bool,
+    review: Option<AutoSetupReview>,
+}
 
-        let (speed, cutoff) = props.calculate_visual_output(sound_power);
+/// Proposal being previewed, with values it replaced
+struct AutoSetupReview {
+    proposal: Proposal,
+    original_main_volume: f32,
+    original_low_pass_freq: f32,
+    /// (open, close) gate levels, of devices connected when proposed
+    original_gates: HashMap<DeviceKey, (f32, f32)>,
+    /// Previewed items: main volume, minimum, low pass
+    preview: [bool; 3],
+}
 
-        ui.horizontal(|ui| {
-            let label = if props.is_enabled {
-                "Enabled"
-            } else {
-                "Enable"
-            };
-            let enable_button = SelectableLabel::new(props.is_enabled, label);
-            ui.group(|ui| {
-                if ui.add_sized([60.0, 60.0], enable_button).clicked() {
-                    props.is_enabled = !props.is_enabled;
-                    if !props.is_enabled {
-                        runtime.spawn(device.stop());
-                    }
-                }
-            });
-            ui.vertical(|ui| {
-                ui.horizontal(|ui| {
-                    ui.label(format!("{:.2}%", speed * 100.0));
-                    if cutoff {
-                        ui.visuals_mut().selection.bg_fill = Color32::RED;
-                    }
+enum AutoSetupAction {
+    Start,
+    /// Previewed items changed
+    Preview,
+    Accept,
+    Revert,
+}
+
+/// Returns `true` if previewed items changed
+fn auto_setup_review_widget(
+    ui: &mut Ui,
+    review: &mut AutoSetupReview,
+    settings: &Settings,
+) -> bool {
+    let proposal = &review.proposal;
+    let [main_volume, min, low_pass] = &mut review.preview;
+    let mut changed = false;
+    ui.label("Tick items to try them out, then accept the ticked ones.");
+    egui::Grid::new("auto_setup_review").show(ui, |ui| {
+        ui.label("");
+        ui.strong("Before");
+        ui.strong("Proposed");
+        ui.end_row();
+
+        changed |= ui.checkbox(main_volume, "Main volume").changed();
+        ui.label(format!("{:.0}%", review.original_main_volume * 100.0));
+        ui.label(format!("{:.0}%", proposal.main_volume * 100.0));
+        ui.end_row();
+
+        changed |= ui
+            .checkbox(min, "Device minimum")
+            .on_hover_text("Applies to all connected devices")
+            .changed();
+        ui.label("per device");
+        ui.label(format!("{:.2}", proposal.min));
+        ui.end_row();
+
+        changed |= ui.checkbox(low_pass, "Low pass freq.").changed();
+        ui.label(format!("{:.0} Hz", review.original_low_pass_freq));
+        ui.label(format!("{:.0} Hz", proposal.low_pass_freq));
+        ui.end_row();
+    });
+    if settings.capture.use_band_pass.load() {
+        ui.weak("Low pass has no effect while band pass is used");
+    }
+    changed
+}
+
+fn auto_setup_widget(
+    ctx: &egui::Context,
+    window: &mut AutoSetupWindow,
+    settings: &Settings,
+) -> Option<AutoSetupAction> {
+    let mut action = None;
+    let AutoSetupWindow {
+        is_open,
+        sampling,
+        was_silent,
+        review,
+    } = window;
+    Window::new("Auto setup")
+        .open(is_open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if let Some(sampling) = sampling {
+                ui.label("Listening, keep your usual content playing...");
+                ui.add(ProgressBar::new(sampling.progress()).show_percentage());
+                return;
+            }
+            if let Some(review) = review {
+                if auto_setup_review_widget(ui, review, settings) {
+                    action = Some(AutoSetupAction::Preview);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Accept").clicked() {
+                        action = Some(AutoSetupAction::Accept);
+                    }
+                    if ui.button("Revert").clicked() {
+                        action = Some(AutoSetupAction::Revert);
+                    }
+                });
+                return;
+            }
+            ui.label(format!(
+                "Play your usual content, then press start. \
+                After {} seconds of listening, settings are proposed \
+                for you to try out.",
+                auto_setup::SAMPLE_DURATION.as_secs()
+            ));
+            if *was_silent {
+                ui.colored_label(
+                    Color32::YELLOW,
+                    "Nothing was playing, try again",
+                );
+            }
+            if ui.button("Start").clicked() {
+                action = Some(AutoSetupAction::Start);
+            }
+        });
+    action
+}
+
+impl GuiApp {
+    /// Drops props of device, stopping its tasks. Values are remembered,
+    /// so they come back with the device.
+    fn on_device_removed(&mut self, key: DeviceKey) {
+        if self.solo_device == Some(key) {
+            self.solo_device = None;
+        }
+        let Some(props) = self.devices.remove(&key) else {
+            return;
+        };
+        if self.settings.remember_device_settings {
+            self.settings.remember_device(props.snapshot());
+        }
+        let identifier = props.identifier.clone();
+        self.disconnected_devices
+            .retain(|x| x.identifier != identifier);
+        if !self.settings.hidden_devices.contains(&identifier) {
+            self.disconnected_devices.push(DisconnectedDevice {
+                identifier,
+                name: props.display_name().to_owned(),
+                was_enabled: props.is_enabled,
+                since: Instant::now(),
+            });
+        }
+    }
+
+    /// Props for newly found device, from before device config reload,
+    /// or from saved settings
+    fn attach_device(
+        &mut self,
+        device: Arc<ButtplugClientDevice>,
+    ) -> DeviceProps {
+        // identical devices are numbered in order they're found
+        let mut occurrence = 0;
+        let is_taken = |identifier: &str| {
+            self.devices.values().any(|x| x.identifier == identifier)
+        };
+        while is_taken(&device_identifier(&device, occurrence)) {
+            occurrence += 1;
+        }
+        let identifier = device_identifier(&device, occurrence);
+        let disconnected = self
+            .disconnected_devices
+            .iter()
+            .position(|x| x.identifier == identifier)
+            .map(|i| self.disconnected_devices.remove(i));
+        if let Some(detached) = self.detached_devices.remove(&identifier) {
+            let polling = self.settings.battery_polling.clone();
+            return detached.reattach(&self.runtime, device, polling);
+        }
+        let saved = self
+            .settings
+            .saved_devices
+            .get(&identifier)
+            .filter(|_| self.settings.remember_device_settings)
+            .map(|x| (x, &self.settings.device_persistence));
+        let polling = self.settings.battery_polling.clone();
+        let mut props =
+            DeviceProps::new(&self.runtime, device, identifier, saved, polling);
+        // nothing gets turned back on while stopped
+        let is_restored = self.settings.restore_enabled_on_reconnect
+            && self.halted.is_none()
+            && disconnected.is_some_and(|x| {
+                x.was_enabled && x.since.elapsed() < RECONNECT_GRACE
+            });
+        if is_restored {
+            props.is_enabled = true;
+            props.ramp_start = Some(Instant::now());
+        }
+        props
+    }
+
+    fn update_snapshots(&mut self, action: Option<SnapshotAction>) {
+        match action {
+            Some(SnapshotAction::Take) => {
+                let devices = self
+                    .connections
+                    .devices()
+                    .filter_map(|(key, _)| {
+                        Some(self.devices.get(&key)?.snapshot())
+                    })
+                    .collect();
+                self.settings.add_snapshot(devices);
+            }
+            Some(SnapshotAction::Restore(i)) => {
+                let Some(snapshot) = self.settings.snapshots.get(i).cloned()
+                else {
+                    return;
+                };
+                self.settings.restore_snapshot(&snapshot);
+                // nothing gets turned back on while stopped
+                let restore_enabled =
+                    self.snapshots.restore_enabled && self.halted.is_none();
+                for (key, device) in self.connections.devices() {
+                    let Some(props) = self.devices.get_mut(&key) else {
+                        continue;
+                    };
+                    let saved = snapshot
+                        .devices
+                        .iter()
+                        .find(|x| x.identifier == props.identifier);
+                    let Some(saved) = saved else {
+                        continue;
+                    };
+                    let is_hidden = self
+                        .settings
+                        .hidden_devices
+                        .contains(&props.identifier);
+                    let was_enabled = props.is_enabled;
+                    props.restore(saved, restore_enabled && !is_hidden);
+                    if was_enabled && !props.is_enabled {
+                        self.runtime.spawn(device.stop());
+                    }
+                }
+            }
+            Some(SnapshotAction::Delete(i)) => {
+                if i < self.settings.snapshots.len() {
+                    self.settings.snapshots.remove(i);
+                }
+            }
+            None => (),
+        }
+    }
+}
+
+// seconds
+const CALIBRATION_SILENCE_TIME: f32 = 3.0;
+const CALIBRATION_LOUD_TIME: f32 = 5.0;
+// share of loud content reaching target, so short spikes don't decide it
+const CALIBRATION_PEAK_PERCENTILE: f32 = 0.95;
+
+/// Guided measurement of noise floor and peak level
+struct Calibration {
+    step: CalibrationStep,
+    /// Levels before main volume, within current step
+    levels: Vec<f32>,
+}
+
+enum CalibrationStep {
+    Silence { start: Instant },
+    Loud { start: Instant, noise: f32 },
+    Done(CalibrationResult),
+}
+
+#[derive(Clone, Copy)]
+struct CalibrationResult {
+    noise: f32,
+    peak: f32,
+    main_volume: f32,
+    multiplier: f32,
+    min: f32,
+}
+
+enum CalibrationAction {
+    Apply,
+    Cancel,
+}
+
+impl Calibration {
+    fn new() -> Self {
+        Self {
+            step: CalibrationStep::Silence {
+                start: Instant::now(),
+            },
+            levels: vec![],
+        }
+    }
+
+    /// 0.0 to 1.0, within current step
+    fn progress(&self) -> f32 {
+        let (start, duration) = match self.step {
+            CalibrationStep::Silence { start } => {
+                (start, CALIBRATION_SILENCE_TIME)
+            }
+            CalibrationStep::Loud { start, .. } => {
+                (start, CALIBRATION_LOUD_TIME)
+            }
+            CalibrationStep::Done(_) => return 1.0,
+        };
+        (start.elapsed().as_secs_f32() / duration).min(1.0)
+    }
+
+    fn record(&mut self, level: f32) {
+        if matches!(self.step, CalibrationStep::Done(_)) {
+            return;
+        }
+        self.levels.push(level);
+        if self.progress() < 1.0 {
+            return;
+        }
+        let mut levels = std::mem::take(&mut self.levels);
+        levels.sort_by(f32::total_cmp);
+        self.step = match self.step {
+            CalibrationStep::Silence { .. } => CalibrationStep::Loud {
+                start: Instant::now(),
+                noise: levels.last().copied().unwrap_or_default(),
+            },
+            CalibrationStep::Loud { noise, .. } => {
+                let peak = auto_setup::percentile(
+                    &levels,
+                    CALIBRATION_PEAK_PERCENTILE,
+                )
+                .unwrap_or_default();
+                CalibrationStep::Done(CalibrationResult::new(noise, peak))
+            }
+            CalibrationStep::Done(result) => CalibrationStep::Done(result),
+        };
+    }
+}
+
+impl CalibrationResult {
+    fn new(noise: f32, peak: f32) -> Self {
+        let target = auto_setup::TARGET_LEVEL;
+        // level is scaled by square of main volume,
+        // multiplier makes up for what main volume can't reach
+        let (main_volume, multiplier) = if peak > 0.0 {
+            let main_volume =
+                (target / peak).sqrt().min(auto_setup::MAX_MAIN_VOLUME);
+            let multiplier = (target / (peak * main_volume.powi(2))).max(1.0);
+            (main_volume, multiplier)
+        } else {
+            (1.0, 1.0)
+        };
+        let gain = main_volume.powi(2) * multiplier;
+        let min =
+            ((noise * gain).min(1.0) + auto_setup::NOISE_MARGIN).min(target);
+        Self {
+            noise,
+            peak,
+            main_volume,
+            multiplier,
+            min,
+        }
+    }
+}
+
+impl GuiApp {
+    fn update_calibration(&mut self, action: Option<CalibrationAction>) {
+        let Some(calibration) = &mut self.calibration else {
+            return;
+        };
+        match action {
+            Some(CalibrationAction::Apply) => {
+                if let CalibrationStep::Done(result) = calibration.step {
+                    self.settings.main_volume = result.main_volume;
+                    let hidden = &self.settings.hidden_devices;
+                    let shown = self
+                        .devices
+                        .values_mut()
+                        .filter(|x| !hidden.contains(&x.identifier));
+                    for props in shown {
+                        props.multiplier = result.multiplier;
+                        props.gate.open = result.min;
+                        props.gate.close = result.min;
+                        props.calibration = Some(DeviceCalibration {
+                            multiplier: result.multiplier,
+                            min: result.min,
+                        });
+                    }
+                }
+                self.calibration = None;
+            }
+            // nothing was written yet, so there's nothing to revert
+            Some(CalibrationAction::Cancel) => self.calibration = None,
+            None => calibration.record(self.current_sound_power.load()),
+        }
+    }
+}
+
+fn calibration_widget(
+    ctx: &egui::Context,
+    calibration: &mut Calibration,
+) -> Option<CalibrationAction> {
+    let mut action = None;
+    let mut is_open = true;
+    let progress = calibration.progress();
+    Window::new("Calibration")
+        .open(&mut is_open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            match &calibration.step {
+                CalibrationStep::Silence { .. } => {
+                    ui.label("1/2: Keep audio silent...");
+                    ui.add(ProgressBar::new(progress).show_percentage());
+                }
+                CalibrationStep::Loud { .. } => {
+                    ui.label("2/2: Play typical loud content...");
+                    ui.add(ProgressBar::new(progress).show_percentage());
+                }
+                CalibrationStep::Done(result) => {
+                    calibration_result_widget(ui, result);
+                    if ui
+                        .button("Apply")
+                        .on_hover_text(
+                            "Multiplier and minimum apply \
+                            to all connected devices",
+                        )
+                        .clicked()
+                    {
+                        action = Some(CalibrationAction::Apply);
+                    }
+                }
+            }
+            if ui.button("Cancel").clicked() {
+                action = Some(CalibrationAction::Cancel);
+            }
+        });
+    if !is_open {
+        action = Some(CalibrationAction::Cancel);
+    }
+    action
+}
+
+fn calibration_result_widget(ui: &mut Ui, result: &CalibrationResult) {
+    if result.peak <= 0.0 {
+        ui.colored_label(
+            Color32::YELLOW,
+            "Nothing was playing, suggestions are defaults",
+        );
+    }
+    egui::Grid::new("calibration_result").show(ui, |ui| {
+        ui.label("Noise floor");
+        ui.label(format!("{:.3}", result.noise));
+        ui.end_row();
+        ui.label("Peak");
+        ui.label(format!("{:.3}", result.peak));
+        ui.end_row();
+        ui.strong("Main volume");
+        ui.label(format!("{:.0}%", result.main_volume * 100.0));
+        ui.end_row();
+        ui.strong("Multiplier");
+        ui.label(format!("{:.2}", result.multiplier));
+        ui.end_row();
+        ui.strong("Minimum");
+        ui.label(format!("{:.2}", result.min));
+        ui.end_row();
+    });
+}
+
+#[derive(Default)]
+struct SnapshotsWindow {
+    is_open: bool,
+    /// Off by default, so restoring can't unexpectedly turn devices on
+    restore_enabled: bool,
+}
+
+enum SnapshotAction {
+    Take,
+    /// Index into snapshot list
+    Restore(usize),
+    Delete(usize),
+}
+
+fn snapshots_widget(
+    ctx: &egui::Context,
+    window: &mut SnapshotsWindow,
+    snapshots: &[Snapshot],
+    is_halted: bool,
+) -> Option<SnapshotAction> {
+    let mut action = None;
+    let SnapshotsWindow {
+        is_open,
+        restore_enabled,
+    } = window;
+    Window::new("Snapshots")
+        .open(is_open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if ui
+                .button("Take snapshot")
+                .on_hover_text(
+                    "Saves all settings and values of connected devices",
+                )
+                .clicked()
+            {
+                action = Some(SnapshotAction::Take);
+            }
+            ui.add_enabled(
+                !is_halted,
+                egui::Checkbox::new(restore_enabled, "Restore enabled devices"),
+            )
+            .on_hover_text(
+                "Otherwise devices stay enabled or disabled as they are",
+            );
+            ui.separator();
+            if snapshots.is_empty() {
+                ui.weak("No snapshots yet");
+            }
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs());
+            egui::Grid::new("snapshots").show(ui, |ui| {
+                // newest first
+                for (i, snapshot) in snapshots.iter().enumerate().rev() {
+                    let age = now.saturating_sub(snapshot.taken_at);
+                    ui.label(format_age(age));
+                    let active = snapshot
+                        .devices
+                        .iter()
+                        .filter(|x| x.is_enabled)
+                        .count();
+                    ui.label(format!(
+                        "{active}/{} devices active, volume {:.0}%",
+                        snapshot.devices.len(),
+                        snapshot.main_volume * 100.0
+                    ));
+                    if ui.button("Restore").clicked() {
+                        action = Some(SnapshotAction::Restore(i));
+                    }
+                    if ui.button("Delete").clicked() {
+                        action = Some(SnapshotAction::Delete(i));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    action
+}
+
+/// `age` in seconds
+fn format_age(age: u64) -> String {
+    match age {
+        0..=59 => "just now".to_owned(),
+        60..=3599 => format!("{} min ago", age / 60),
+        3600..=86399 => format!("{} h ago", age / 3600),
+        _ => format!("{} days ago", age / 86400),
+    }
+}
+
+#[derive(Default)]
+struct LatencyTestWindow {
+    is_open: bool,
+    device: Option<DeviceKey>,
+    test: Option<LatencyTest>,
+    /// Average device latency of each finished test
+    measured: HashMap<DeviceKey, Duration>,
+}
+
+enum LatencyTestAction {
+    Start,
+    /// Set device delays from measured latencies
+    LineUp,
+}
+
+fn latency_test_widget(
+    ctx: &egui::Context,
+    window: &mut LatencyTestWindow,
+    devices: &[(DeviceKey, String)],
+) -> Option<LatencyTestAction> {
+    let mut action = None;
+    let is_space_pressed = ctx.input().key_pressed(Key::Space);
+    let LatencyTestWindow {
+        is_open,
+        device,
+        test,
+        measured,
+    } = window;
+    Window::new("Latency test")
+        .open(is_open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(
+                "Plays clicks through default output device. Once capture \
+                picks one up, device gets a pulse. Keep other audio quiet.",
+            );
+            let selected_name = devices
+                .iter()
+                .find(|(key, _)| Some(*key) == *device)
+                .map_or("<none>", |(_, name)| name.as_str());
+            ui.add_enabled_ui(test.is_none(), |ui| {
+                egui::ComboBox::from_label("Device")
+                    .selected_text(selected_name)
+                    .show_ui(ui, |ui| {
+                        for (key, name) in devices {
+                            ui.selectable_value(device, Some(*key), name);
+                        }
+                    });
+            });
+            ui.separator();
+            match test {
+                Some(running) if !running.is_finished() => {
+                    ui.label(format!(
+                        "Trial {}/{}: press space or click below, \
+                        as soon as you feel a pulse",
+                        running.trials.len() + 1,
+                        latency::TRIAL_COUNT,
+                    ));
+                    let felt_button = ui.add_sized(
+                        [ui.available_width(), 60.0],
+                        Button::new("Felt it!"),
+                    );
+                    if felt_button.clicked() || is_space_pressed {
+                        running.tap();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        *test = None;
+                    }
+                }
+                _ => {
+                    if let Some(finished) = test {
+                        latency_results_widget(ui, finished);
+                    }
+                    let start_button = Button::new("Start");
+                    if ui.add_enabled(device.is_some(), start_button).clicked()
+                    {
+                        action = Some(LatencyTestAction::Start);
+                    }
+                }
+            }
+            let is_running = test.as_ref().is_some_and(|x| !x.is_finished());
+            if !is_running {
+                measured.retain(|key, _| devices.iter().any(|x| x.0 == *key));
+                if measured_latencies_widget(ui, measured, devices) {
+                    action = Some(LatencyTestAction::LineUp);
+                }
+            }
+        });
+    action
+}
+
+fn latency_results_widget(ui: &mut Ui, test: &LatencyTest) {
+    let millis = |x: Option<Duration>| {
+        x.map_or("missed".to_owned(), |x| format!("{} ms", x.as_millis()))
+    };
+    for (i, trial) in test.trials.iter().enumerate() {
+        match trial.capture {
+            Some(_) => {
+                ui.label(format!(
+                    "Trial {}: capture {}, device {}",
+                    i + 1,
+                    millis(trial.capture),
+                    millis(trial.device),
+                ));
+            }
+            None => {
+                ui.weak(format!("Trial {}: click wasn't captured", i + 1));
+            }
+        }
+    }
+    match test.capture_average() {
+        Some(average) => {
+            ui.strong(format!(
+                "Average capture latency: {} ms",
+                average.as_millis()
+            ));
+        }
+        None => {
+            ui.label("No clicks were captured, check audio source");
+        }
+    }
+    match test.device_average() {
+        Some(average) => {
+            ui.strong(format!(
+                "Average device latency: {} ms",
+                average.as_millis()
+            ))
+            .on_hover_text("Includes your own reaction time");
+        }
+        None => {
+            ui.label("No pulses were felt");
+        }
+    }
+}
+
+/// Returns `true` if device delays should be set from measurements
+fn measured_latencies_widget(
+    ui: &mut Ui,
+    measured: &HashMap<DeviceKey, Duration>,
+    devices: &[(DeviceKey, String)],
+) -> bool {
+    if measured.is_empty() {
+        return false;
+    }
+    ui.separator();
+    ui.label("Measured devices:");
+    for (key, name) in devices {
+        if let Some(latency) = measured.get(key) {
+            ui.label(format!("{name}: {} ms", latency.as_millis()));
+        }
+    }
+    ui.add_enabled(measured.len() > 1, Button::new("Line up devices"))
+        .on_hover_text(
+            "Sets device delays, so faster devices wait for slowest one.\n\
+            Needs at least two measured devices",
+        )
+        .clicked()
+}
+
+fn band_meters_widget(ui: &mut Ui, powers: &[f32; BAND_COUNT]) {
+    for (band, power) in Band::SPLIT.iter().zip(powers) {
+        ui.add(ProgressBar::new(*power).desired_width(40.0))
+            .on_hover_text(format!("{}: {:.2}%", band.name(), power * 100.0));
+    }
+}
+
+fn notch_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    let mut use_notch = capture.use_notch.load();
+    ui.checkbox(&mut use_notch, "Notch filter").on_hover_text(
+        "Removes a narrow band around one frequency, \
+        like constant engine hum, keeping the rest",
+    );
+    capture.use_notch.store(use_notch);
+    if !use_notch {
+        return;
+    }
+    let mut notch_freq = capture.notch_freq.load();
+    ui.add(
+        Slider::new(&mut notch_freq, 20.0..=2000.0)
+            .logarithmic(true)
+            .text("Notch freq.")
+            .suffix(" Hz")
+            .integer(),
+    );
+    capture.notch_freq.store(notch_freq);
+    let mut notch_q = capture.notch_q.load();
+    ui.add(
+        Slider::new(&mut notch_q, 0.5..=30.0)
+            .logarithmic(true)
+            .text("Notch Q"),
+    )
+    .on_hover_text("Higher is narrower");
+    capture.notch_q.store(notch_q);
+}
+
+fn smoothing_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label("Smoothing: ");
+        ui.add(
+            Slider::new(&mut settings.smoothing_ms, 0.0..=500.0)
+                .suffix(" ms")
+                .integer(),
+        )
+    })
+    .inner
+    .on_hover_text(
+        "Averages level over time, removing jitter \
+        from both rises and falls.\n\
+        0 disables it",
+    );
+}
+
+fn bass_emphasis_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    let mut emphasis = capture.bass_emphasis.load();
+    ui.horizontal(|ui| {
+        ui.label("Bass emphasis: ");
+        ui.add(Slider::new(&mut emphasis, 0.0..=18.0).suffix(" dB"))
+    })
+    .inner
+    .on_hover_text(
+        "Weights low frequencies more, relative to 1 kHz, \
+        so kicks dominate while rest of the mix still contributes.\n\
+        0 dB is flat. Only applies to full range",
+    );
+    capture.bass_emphasis.store(emphasis);
+}
+
+fn pattern_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label("Pattern blend: ");
+        let mut blend_as_percent = settings.pattern_blend * 100.0;
+        ui.add(Slider::new(&mut blend_as_percent, 0.0..=100.0).suffix("%"))
+            .on_hover_text(
+                "Mixes a generated pattern into audio level.\n\
+                0% is audio only, 100% is pattern only",
+            );
+        settings.pattern_blend = blend_as_percent / 100.0;
+        if settings.pattern_blend <= 0.0 {
+            return;
+        }
+        egui::ComboBox::from_id_source("pattern_shape")
+            .selected_text(settings.pattern_shape.name())
+            .show_ui(ui, |ui| {
+                for shape in PatternShape::ALL {
+                    ui.selectable_value(
+                        &mut settings.pattern_shape,
+                        shape,
+                        shape.name(),
+                    );
+                }
+            });
+        ui.add(
+            Slider::new(&mut settings.pattern_rate, 0.1..=5.0)
+                .logarithmic(true)
+                .text("Rate")
+                .suffix(" Hz"),
+        );
+        ui.add(
+            Slider::new(&mut settings.pattern_depth, 0.0..=1.0).text("Depth"),
+        );
+    });
+}
+
+fn beat_pulse_widget(ui: &mut Ui, settings: &mut Settings, bpm: f32) {
+    ui.horizontal(|ui| {
+        ui.label("Beat pulse: ");
+        for variant in BeatPulse::ALL {
+            ui.selectable_value(
+                &mut settings.beat_pulse,
+                variant,
+                variant.name(),
+            );
+        }
+    })
+    .response
+    .on_hover_text(
+        "Pulses output in time with the beat, \
+        each pulse as strong as current level.\n\
+        Follows level as usual while tempo is unclear",
+    );
+    if bpm > 0.0 {
+        ui.label(format!("Tempo: {bpm:.0} BPM"));
+    } else {
+        ui.weak("Tempo: unclear");
+    }
+}
+
+fn power_band_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    let mut power_band = capture.power_band.load();
+    ui.horizontal(|ui| {
+        ui.label("Driven by: ");
+        egui::ComboBox::from_id_source("power_band")
+            .selected_text(power_band.name())
+            .show_ui(ui, |ui| {
+                for band in Band::ALL {
+                    ui.selectable_value(&mut power_band, band, band.name());
+                }
+            });
+    })
+    .response
+    .on_hover_text(
+        "Frequency band used for main volume.\n\
+        Full range uses filters above, like before spectrum analysis",
+    );
+    capture.power_band.store(power_band);
+}
+
+fn agc_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    let mut use_agc = capture.use_agc.load();
+    ui.checkbox(&mut use_agc, "Automatic gain control")
+        .on_hover_text(
+            "Scales volume, so that recent peaks reach target level.\n\
+            Main volume is still applied on top",
+        );
+    capture.use_agc.store(use_agc);
+    if !use_agc {
+        return;
+    }
+    let mut target = capture.agc_target.load();
+    ui.add(Slider::new(&mut target, 0.1..=1.0).text("Target level"));
+    capture.agc_target.store(target);
+    let mut adaptation_time = capture.agc_adaptation_time.load();
+    ui.add(
+        Slider::new(&mut adaptation_time, 5.0..=30.0)
+            .text("Adaptation time")
+            .suffix(" s"),
+    );
+    capture.agc_adaptation_time.store(adaptation_time);
+    let mut floor = capture.agc_floor.load();
+    ui.add(
+        Slider::new(&mut floor, 0.001..=0.2)
+            .logarithmic(true)
+            .text("Silence floor"),
+    )
+    .on_hover_text("Gain stays frozen while level is below this");
+    capture.agc_floor.store(floor);
+}
+
+fn compressor_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label("Dynamics: ");
+        for variant in DynamicsMode::ALL {
+            ui.selectable_value(
+                &mut settings.dynamics_mode,
+                variant,
+                variant.name(),
+            );
+        }
+    })
+    .response
+    .on_hover_text(
+        "Applied after main volume.\n\
+        Compress tames sudden loud sounds.\n\
+        Expand makes quiet passages more dynamic, \
+        without touching loud parts",
+    );
+    match settings.dynamics_mode {
+        DynamicsMode::Off => return,
+        DynamicsMode::Compress => {
+            ui.add(
+                Slider::new(&mut settings.compressor_ratio, 1.0..=20.0)
+                    .logarithmic(true)
+                    .text("Compressor ratio"),
+            )
+            .on_hover_text("1 turns compressor off");
+            if settings.compressor_ratio <= 1.0 {
+                return;
+            }
+            ui.add(
+                Slider::new(&mut settings.compressor_threshold, 0.05..=1.0)
+                    .text("Compressor threshold"),
+            )
+            .on_hover_text("Level above which it's compressed");
+        }
+        DynamicsMode::Expand => {
+            ui.add(
+                Slider::new(&mut settings.expander_ratio, 1.0..=4.0)
+                    .text("Expander ratio"),
+            )
+            .on_hover_text(
+                "How much differences below threshold are stretched.\n\
+                1 turns expander off",
+            );
+            if settings.expander_ratio <= 1.0 {
+                return;
+            }
+            ui.add(
+                Slider::new(&mut settings.expander_threshold, 0.01..=1.0)
+                    .logarithmic(true)
+                    .text("Expander threshold"),
+            )
+            .on_hover_text("Level below which it's expanded");
+        }
+    }
+    ui.add(
+        Slider::new(&mut settings.compressor_release_ms, 10.0..=2000.0)
+            .logarithmic(true)
+            .text("Release")
+            .suffix(" ms")
+            .integer(),
+    )
+    .on_hover_text(
+        "How quickly level recovers after a loud sound when compressing, \
+        or settles after it when expanding",
+    );
+}
+
+fn ducking_widget(ui: &mut Ui, settings: &mut Settings) {
+    let mut use_mic_ducking = settings.capture.use_mic_ducking.load();
+    ui.checkbox(&mut use_mic_ducking, "Duck while talking")
+        .on_hover_text(
+            "Listens to default voice chat mic, \
+            and lowers output while you're talking",
+        );
+    settings.capture.use_mic_ducking.store(use_mic_ducking);
+    if !use_mic_ducking {
+        return;
+    }
+    ui.add(
+        Slider::new(&mut settings.duck_threshold, 0.0..=0.5)
+            .text("Mic threshold"),
+    )
+    .on_hover_text(
+        "Mic level that counts as talking, see meter in main window",
+    );
+    let mut amount_as_percent = settings.duck_amount * 100.0;
+    ui.add(
+        Slider::new(&mut amount_as_percent, 0.0..=100.0)
+            .text("Duck amount")
+            .suffix("%"),
+    );
+    settings.duck_amount = amount_as_percent / 100.0;
+    ui.add(
+        Slider::new(&mut settings.duck_release_ms, 50.0..=5000.0)
+            .logarithmic(true)
+            .text("Duck release")
+            .suffix(" ms")
+            .integer(),
+    )
+    .on_hover_text("How slowly output comes back after you stop talking");
+}
+
+fn filter_meters_widget(ui: &mut Ui, unfiltered: f32, filtered: f32) {
+    ui.horizontal(|ui| {
+        ui.label("Unfiltered: ");
+        ui.add(ProgressBar::new(unfiltered).desired_width(100.0));
+        ui.label("Filtered: ");
+        ui.add(ProgressBar::new(filtered).desired_width(100.0));
+        if unfiltered > 0.0 && filtered > 0.0 {
+            let db = 20.0 * (filtered / unfiltered).log10();
+            ui.label(format!("{db:+.1} dB"));
+        }
+    })
+    .response
+    .on_hover_text(
+        "RMS level before and after filters, weighting and emphasis, \
+        without main volume.\n\
+        Difference shows how much filters take away",
+    );
+}
+
+fn mic_meter_widget(ui: &mut Ui, level: f32, threshold: f32, is_ducking: bool) {
+    ui.horizontal(|ui| {
+        ui.label("Mic: ");
+        if level > threshold {
+            ui.visuals_mut().selection.bg_fill = Color32::YELLOW;
+        }
+        ui.add(ProgressBar::new(level).desired_width(120.0));
+        if is_ducking {
+            ui.weak("ducking");
+        }
+    });
+}
+
+fn normalize_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.checkbox(&mut settings.use_normalize, "Normalize to recent max")
+        .on_hover_text(
+            "Divides level by loudest one heard recently, \
+            so output spans full range for quiet and loud content alike",
+        );
+    if !settings.use_normalize {
+        return;
+    }
+    ui.add(
+        Slider::new(&mut settings.normalize_window, 1.0..=60.0)
+            .text("Normalize window")
+            .suffix(" s"),
+    )
+    .on_hover_text("How long loudest level is kept, before slowly decaying");
+}
+
+fn drop_boost_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.checkbox(&mut settings.use_drop_boost, "Boost on drops")
+        .on_hover_text(
+            "Boosts output for a while, when music gets much louder \
+            for a few seconds, like when a chorus or a drop starts.\n\
+            Boosts are marked on session timeline",
+        );
+    if !settings.use_drop_boost {
+        return;
+    }
+    ui.add(
+        Slider::new(&mut settings.drop_sensitivity, 0.0..=1.0)
+            .text("Drop sensitivity"),
+    );
+    ui.add(Slider::new(&mut settings.drop_boost, 1.0..=3.0).text("Drop boost"));
+    ui.add(
+        Slider::new(&mut settings.drop_boost_duration, 1.0..=30.0)
+            .text("Drop boost duration")
+            .suffix(" s"),
+    );
+}
+
+fn pitch_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.add(
+        Slider::new(&mut settings.pitch_low_hz, 20.0..=2000.0)
+            .logarithmic(true)
+            .text("Pitch low")
+            .suffix(" Hz")
+            .integer(),
+    )
+    .on_hover_text(
+        "Devices following pitch get full output at this frequency, \
+        and below",
+    );
+    ui.add(
+        Slider::new(&mut settings.pitch_high_hz, 40.0..=4000.0)
+            .logarithmic(true)
+            .text("Pitch high")
+            .suffix(" Hz")
+            .integer(),
+    )
+    .on_hover_text(format!(
+        "Devices following pitch get {:.0}% of output at this frequency, \
+        and above",
+        MIN_PITCH_FACTOR * 100.0
+    ));
+    settings.pitch_high_hz = settings.pitch_high_hz.max(settings.pitch_low_hz);
+}
+
+fn jitter_widget(ui: &mut Ui, settings: &mut Settings) {
+    let mut amount_as_percent = settings.jitter_amount * 100.0;
+    ui.add(
+        Slider::new(&mut amount_as_percent, 0.0..=50.0)
+            .text("Humanize")
+            .prefix("\u{b1}")
+            .suffix("%"),
+    )
+    .on_hover_text(
+        "Randomly varies output, gliding between random levels.\n\
+        0% disables it",
+    );
+    settings.jitter_amount = amount_as_percent / 100.0;
+    if settings.jitter_amount <= 0.0 {
+        return;
+    }
+    ui.add(
+        Slider::new(&mut settings.jitter_rate_hz, 0.5..=10.0)
+            .text("Humanize rate")
+            .suffix(" Hz"),
+    )
+    .on_hover_text("How many times per second variation changes direction");
+}
+
+fn silence_stop_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.checkbox(&mut settings.use_silence_stop, "Auto-stop after silence")
+        .on_hover_text(
+            "Stops devices when audio stays silent, \
+            like when music stops or a game is paused.\n\
+            They resume when audio comes back.\n\
+            Inverted devices keep going, silence is their full output",
+        );
+    if !settings.use_silence_stop {
+        return;
+    }
+    ui.add(
+        Slider::new(&mut settings.silence_timeout, 1.0..=120.0)
+            .logarithmic(true)
+            .text("Silence timeout")
+            .suffix(" s"),
+    );
+}
+
+fn throttle_policy_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label("Slow devices: ");
+        for variant in ThrottlePolicy::ALL {
+            ui.selectable_value(
+                &mut settings.throttle_policy,
+                variant,
+                variant.name(),
+            );
+        }
+    })
+    .response
+    .on_hover_text(
+        "When a device takes longer to handle commands, \
+        than it takes to send them.\n\
+        Auto-throttle sends it commands less often, until it speeds up",
+    );
+}
+
+fn slider_preview_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label("While dragging sliders: ");
+        for variant in SliderPreview::ALL {
+            ui.selectable_value(
+                &mut settings.slider_preview,
+                variant,
+                variant.name(),
+            );
+        }
+    })
+    .response
+    .on_hover_text(
+        "Freeze keeps sending what was sent before dragging.\n\
+        Limit caps output at preview ceiling.\n\
+        Applies to volume, multiplier and maximum sliders",
+    );
+    if settings.slider_preview == SliderPreview::Limit {
+        ui.add(
+            Slider::new(&mut settings.preview_ceiling, 0.0..=1.0)
+                .text("Preview ceiling"),
+        );
+    }
+}
+
+/// What can be captured, listed when settings are opened or refreshed
+struct AudioSources {
+    devices: Vec<Endpoint>,
+    /// Executable names of apps playing audio
+    apps: Vec<String>,
+}
+
+impl AudioSources {
+    fn new() -> Self {
+        Self {
+            devices: wasapi::endpoints().unwrap_or_default(),
+            apps: wasapi::audio_apps().unwrap_or_default(),
+        }
+    }
+}
+
+/// Returns `true` if sources should be listed again
+fn audio_source_widget(
+    ui: &mut Ui,
+    sources: &AudioSources,
+    capture: &CaptureSettings,
+) -> bool {
+    let mut app = capture.capture_app.load();
+    let mut device_id = capture.audio_device.load();
+    let selected = sources.devices.iter().find(|x| x.id == device_id);
+    let selected_name = match selected {
+        _ if device_id.is_empty() => "Default device".to_owned(),
+        Some(endpoint) => endpoint_name(endpoint),
+        None => "Unavailable device".to_owned(),
+    };
+    let is_input = selected.is_some_and(|x| x.is_input);
+    let refresh = ui
+        .horizontal(|ui| {
+            ui.add_enabled_ui(app.is_empty(), |ui| {
+                ui.label("Audio device: ").on_hover_text(
+                    "Output device whose sound drives devices, \
+                    or an input like a mic or line-in",
+                );
+                egui::ComboBox::from_id_source("audio_device")
+                    .selected_text(selected_name)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut device_id,
+                            String::new(),
+                            "Default device",
+                        );
+                        for endpoint in &sources.devices {
+                            ui.selectable_value(
+                                &mut device_id,
+                                endpoint.id.clone(),
+                                endpoint_name(endpoint),
+                            );
+                        }
+                    });
+            });
+            ui.button("Refresh").clicked()
+        })
+        .inner;
+    ui.horizontal(|ui| {
+        ui.label("Capture app: ").on_hover_text(
+            "Only this app's sound drives devices, from any output device.\n\
+            Waits for app to start, if it isn't running",
+        );
+        let selected_text = if app.is_empty() {
+            "Whole device".to_owned()
+        } else {
+            app.clone()
+        };
+        egui::ComboBox::from_id_source("capture_app")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app, String::new(), "Whole device");
+                for name in &sources.apps {
+                    ui.selectable_value(&mut app, name.clone(), name);
+                }
+            });
+    });
+    if is_input && app.is_empty() {
+        let mut input_gain = capture.input_gain.load();
+        ui.add(
+            Slider::new(&mut input_gain, 1.0..=50.0)
+                .logarithmic(true)
+                .text("Input gain")
+                .suffix("x"),
+        )
+        .on_hover_text("Mics pick up music much quieter than loopback");
+        capture.input_gain.store(input_gain);
+    }
+    capture.audio_device.store(device_id);
+    capture.capture_app.store(app);
+    refresh
+}
+
+fn endpoint_name(endpoint: &Endpoint) -> String {
+    if endpoint.is_input {
+        format!("{} (input)", endpoint.name)
+    } else {
+        endpoint.name.clone()
+    }
+}
+
+fn filter_slope_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    let mut slope = capture.filter_slope.load();
+    ui.horizontal(|ui| {
+        ui.label("Low pass slope: ");
+        egui::ComboBox::from_id_source("filter_slope")
+            .selected_text(slope.name())
+            .show_ui(ui, |ui| {
+                for variant in FilterSlope::ALL {
+                    ui.selectable_value(&mut slope, variant, variant.name());
+                }
+            });
+    })
+    .response
+    .on_hover_text(
+        "Steeper slopes cut frequencies above cutoff more sharply.\n\
+        Legacy is the original gentle filter",
+    );
+    capture.filter_slope.store(slope);
+}
+
+fn downmix_widget(ui: &mut Ui, capture: &CaptureSettings, channels: u16) {
+    let mut downmix = capture.downmix.load();
+    ui.horizontal(|ui| {
+        ui.label(format!("Downmix ({channels} channels): "));
+        for variant in Downmix::ALL {
+            ui.selectable_value(&mut downmix, variant, variant.name());
+        }
+    })
+    .response
+    .on_hover_text(
+        "How channels are combined into main level.\n\
+        Surround sources often have mostly silent rear channels, \
+        which drag average down",
+    );
+    capture.downmix.store(downmix);
+    if downmix != Downmix::Custom {
+        return;
+    }
+    let mut weights = capture.downmix_weights.load(channels);
+    ui.collapsing("Channel weights", |ui| {
+        for (i, weight) in weights.iter_mut().enumerate() {
+            ui.add(Slider::new(weight, 0.0..=1.0).text(util::channel_name(i)));
+        }
+    });
+    capture.downmix_weights.store(channels, weights);
+}
+
+fn detection_mode_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    let mut mode = capture.detection_mode.load();
+    ui.horizontal(|ui| {
+        ui.label("Detection mode: ");
+        for variant in DetectionMode::ALL {
+            ui.selectable_value(&mut mode, variant, variant.name());
+        }
+    })
+    .response
+    .on_hover_text(
+        "RMS follows loudness.\n\
+        Peak follows loudest sample, so short spikes come through.\n\
+        Peak-hold keeps loudest peak over a few analysis windows.\n\
+        Onset pulses on hits like kicks and snares",
+    );
+    capture.detection_mode.store(mode);
+    if mode == DetectionMode::PeakHold {
+        let mut windows = capture.peak_hold_windows.load();
+        ui.add(
+            Slider::new(&mut windows, 1.0..=100.0)
+                .text("Peak-hold windows")
+                .integer(),
+        );
+        capture.peak_hold_windows.store(windows);
+    }
+    if mode == DetectionMode::Onset {
+        let mut sensitivity = capture.onset_sensitivity.load();
+        ui.add(
+            Slider::new(&mut sensitivity, 0.0..=1.0).text("Onset sensitivity"),
+        );
+        capture.onset_sensitivity.store(sensitivity);
+    }
+}
+
+/// Slider affecting device output. Marks dragging for preview safety,
+/// and pressing Escape while dragging restores value from before the drag.
+fn output_slider(
+    ui: &mut Ui,
+    value: &mut f32,
+    slider: impl FnOnce(&mut f32) -> Slider<'_>,
+    is_dragging: &Cell<bool>,
+) -> Response {
+    let before = *value;
+    let response = ui.add(slider(value));
+    let id = response.id;
+    if response.drag_started() {
+        // (value before drag, is cancelled)
+        ui.memory().data.insert_temp(id, (before, false));
+    }
+    let saved = ui.memory().data.get_temp::<(f32, bool)>(id);
+    let Some((original, mut is_cancelled)) = saved else {
+        return response;
+    };
+    if response.dragged() {
+        is_dragging.set(true);
+        if ui.input().key_pressed(Key::Escape) {
+            is_cancelled = true;
+            ui.memory().data.insert_temp(id, (original, true));
+        }
+    }
+    if is_cancelled {
+        *value = original;
+    }
+    if !response.dragged() {
+        ui.memory().data.remove::<(f32, bool)>(id);
+    }
+    response
+}
+
+fn filter_widget(ui: &mut Ui, capture: &CaptureSettings) {
+    if capture.use_band_pass.load() {
+        let mut band_low = capture.band_low.load();
+        let mut band_high = capture.band_high.load();
+        let r1 = ui.label("Band: ");
+        let r2 =
+            range_slider(ui, &mut band_low, &mut band_high, 20.0..=20_000.0);
+        let r3 = ui.label(format!("{band_low:.0} - {band_high:.0} Hz"));
+        r1.union(r2).union(r3).on_hover_text_at_pointer(
+            "Only frequencies within this range drive vibrations",
+        );
+        capture.band_low.store(band_low);
+        capture.band_high.store(band_high);
+        return;
+    }
+
+    let mut low_pass_freq = capture.low_pass_freq.load();
+    let r1 = ui.label("Low pass freq.: ");
+    let r2 = ui.add(
+        Slider::new(&mut low_pass_freq, 0.0..=20_000.0)
+            .logarithmic(true)
+            .integer(),
+    );
+    r1.union(r2).on_hover_text_at_pointer(
+        "Filters out frequencies above this one,\n\
+        leaving only lower frequencies.\n\
+        Defaults to max (20_000 Hz)",
+    );
+    capture.low_pass_freq.store(low_pass_freq);
+
+    let mut high_pass_freq = capture.high_pass_freq.load();
+    let r1 = ui.label("High pass freq.: ");
+    let r2 = ui.add(
+        Slider::new(&mut high_pass_freq, 0.0..=20_000.0)
+            .logarithmic(true)
+            .integer(),
+    );
+    r1.union(r2).on_hover_text_at_pointer(
+        "Filters out frequencies below this one,\n\
+        leaving only higher frequencies.\n\
+        Defaults to 0 (disabled)",
+    );
+    capture.high_pass_freq.store(high_pass_freq);
+}
+
+/// Double-ended slider with logarithmic scale
+fn range_slider(
+    ui: &mut Ui,
+    low: &mut f32,
+    high: &mut f32,
+    range: RangeInclusive<f32>,
+) -> Response {
+    let size = vec2(ui.spacing().slider_width, ui.spacing().interact_size.y);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::drag());
+    let log_range = range.start().ln()..=range.end().ln();
+    let to_x = |v: f32| {
+        let v = v.clamp(*range.start(), *range.end()).ln();
+        egui::remap_clamp(v, log_range.clone(), rect.x_range())
+    };
+    let from_x =
+        |x: f32| egui::remap_clamp(x, rect.x_range(), log_range.clone()).exp();
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        // remember which handle is being dragged, so they don't swap
+        let saved = ui.memory().data.get_temp::<bool>(response.id);
+        let is_low = match saved {
+            Some(is_low) if !response.drag_started() => is_low,
+            _ => {
+                let is_low = pos.x < (to_x(*low) + to_x(*high)) / 2.0;
+                ui.memory().data.insert_temp(response.id, is_low);
+                is_low
+            }
+        };
+        let value = from_x(pos.x);
+        if is_low {
+            *low = value.min(*high);
+        } else {
+            *high = value.max(*low);
+        }
+    }
+
+    let visuals = ui.style().interact(&response);
+    let painter = ui.painter();
+    let y = rect.center().y;
+    painter.line_segment(
+        [pos2(rect.left(), y), pos2(rect.right(), y)],
+        visuals.bg_stroke,
+    );
+    let (x1, x2) = (to_x(*low), to_x(*high));
+    painter.line_segment(
+        [pos2(x1, y), pos2(x2, y)],
+        Stroke::new(4.0, ui.visuals().selection.bg_fill),
+    );
+    for x in [x1, x2] {
+        painter.circle(
+            pos2(x, y),
+            rect.height() / 3.0,
+            visuals.bg_fill,
+            visuals.fg_stroke,
+        );
+    }
+    response
+}
+
+/// Returns `true` if scan should start
+fn bluetooth_prompt_widget(ctx: &egui::Context, is_open: &mut bool) -> bool {
+    let mut scan = false;
+    Window::new("Bluetooth turned on")
+        .open(is_open)
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, vec2(-10.0, -10.0))
+        .show(ctx, |ui| {
+            ui.label("Devices can be found now");
+            scan = ui.button("Scan now").clicked();
+        });
+    if scan {
+        *is_open = false;
+    }
+    scan
+}
+
+fn low_battery_widget(ctx: &egui::Context, alerts: &mut Vec<String>) {
+    if alerts.is_empty() {
+        return;
+    }
+    let mut is_open = true;
+    Window::new("Low battery")
+        .open(&mut is_open)
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, vec2(-10.0, -10.0))
+        .show(ctx, |ui| {
+            for name in alerts.iter() {
+                ui.colored_label(Color32::RED, name);
+            }
+            ui.label("Charge soon, or output might cut out");
+        });
+    if !is_open {
+        alerts.clear();
+    }
+}
+
+/// Bars from -60 dB to 0 dB, with draggable low pass cutoff marker
+fn spectrum_widget(
+    ui: &mut Ui,
+    bins: &[f32; SPECTRUM_BINS],
+    capture: &CaptureSettings,
+) {
+    let size = vec2(ui.available_width(), 80.0);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::drag());
+    let log_range = SPECTRUM_MIN_FREQ.ln()..=SPECTRUM_MAX_FREQ.ln();
+    let to_x = |freq: f32| {
+        let freq = freq.clamp(SPECTRUM_MIN_FREQ, SPECTRUM_MAX_FREQ);
+        egui::remap_clamp(freq.ln(), log_range.clone(), rect.x_range())
+    };
+    let from_x =
+        |x: f32| egui::remap_clamp(x, rect.x_range(), log_range.clone()).exp();
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let bar_width = rect.width() / SPECTRUM_BINS as f32;
+    let bar_color = ui.visuals().selection.bg_fill;
+    for (i, bin) in bins.iter().enumerate() {
+        let db = 20.0 * bin.log10();
+        let height = ((db + 60.0) / 60.0).clamp(0.0, 1.0) * rect.height();
+        let left = rect.left() + i as f32 * bar_width;
+        let bar = Rect::from_min_max(
+            pos2(left, rect.bottom() - height),
+            pos2(left + bar_width - 1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, bar_color);
+    }
+
+    // band pass upper edge plays the role of low pass cutoff
+    let cutoff = if capture.use_band_pass.load() {
+        &capture.band_high
+    } else {
+        &capture.low_pass_freq
+    };
+    if let Some(pos) = response.interact_pointer_pos() {
+        cutoff.store(from_x(pos.x).round());
+    }
+    let x = to_x(cutoff.load());
+    painter.vline(x, rect.y_range(), Stroke::new(2.0, Color32::RED));
+    response.on_hover_text(format!(
+        "Low pass cutoff: {:.0} Hz\nDrag to change it",
+        cutoff.load()
+    ));
+}
+
+fn capture_health_widget(ui: &mut Ui, health: &CaptureHealth) {
+    let (empty_reads, oversleeps) = health.recent(HEALTH_WINDOW);
+    let stats = &health.stats;
+    ui.label(format!(
+        "Empty reads in last minute: {empty_reads} ({} total)",
+        stats.empty_reads.load()
+    ))
+    .on_hover_text("Normal while nothing is playing");
+    ui.label(format!(
+        "Late polls in last minute: {oversleeps} ({} total)",
+        stats.oversleeps.load()
+    ))
+    .on_hover_text(
+        "Capture thread woke up much later than planned, \
+        so devices stopped reacting for a moment",
+    );
+}
+
+fn capture_hint_widget(ui: &mut Ui, show_hint: &mut bool) {
+    if !*show_hint {
+        return;
+    }
+    ui.horizontal_wrapped(|ui| {
+        ui.colored_label(
+            Color32::YELLOW,
+            "Audio capture keeps stalling. This is often caused by \
+            an app using the output device in exclusive mode, \
+            or by audio driver enhancements.",
+        );
+        if ui.small_button("Dismiss").clicked() {
+            *show_hint = false;
+        }
+    });
+}
+
+fn settings_window_widget(
+    ctx: &egui::Context,
+    show_settings: &mut bool,
+    settings: &mut Settings,
+    has_in_process_server: bool,
+    device_config_error: Option<&str>,
+    connected_devices: &[String],
+    audio_sources: &AudioSources,
+) -> SettingsWindowResponse {
+    let mut response = SettingsWindowResponse::default();
+    Window::new("Settings")
+        .open(show_settings)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.use_dark_mode, "Use dark mode");
+            response.start_calibration = ui
+                .button("Calibrate...")
+                .on_hover_text(
+                    "Measures noise floor and loud content, \
+                    then suggests main volume, multiplier and minimum",
+                )
+                .clicked();
+            response.refresh_audio_sources =
+                audio_source_widget(ui, audio_sources, &settings.capture);
+            let mut use_band_pass = settings.capture.use_band_pass.load();
+            ui.checkbox(&mut use_band_pass, "Use band pass filter")
+                .on_hover_text(
+                    "Pick a single frequency range, \
+                    instead of separate low and high pass cutoffs",
+                );
+            settings.capture.use_band_pass.store(use_band_pass);
+            let mut use_a_weighting = settings.capture.use_a_weighting.load();
+            ui.checkbox(&mut use_a_weighting, "Use A-weighting")
+                .on_hover_text(
+                    "Weights frequencies by how loud they sound, \
+                    so inaudible rumble doesn't drive devices",
+                );
+            settings.capture.use_a_weighting.store(use_a_weighting);
+            let mut use_side = settings.capture.use_side.load();
+            ui.checkbox(&mut use_side, "Reject center (vocals)")
+                .on_hover_text(
+                    "Analyzes difference between left and right channels, \
+                    removing center-panned sounds like dialog.\n\
+                    Only works on stereo sources, mono ones are unaffected",
+                );
+            settings.capture.use_side.store(use_side);
+            let mut pause_when_muted = settings.capture.pause_when_muted.load();
+            ui.checkbox(&mut pause_when_muted, "Pause when source is muted")
+                .on_hover_text(
+                    "Stops output while captured device or app \
+                    is muted in Windows.\n\
+                    Turn off to keep devices running with speakers muted",
+                );
+            settings.capture.pause_when_muted.store(pause_when_muted);
+            let mut peak_since_frame = settings.capture.peak_since_frame.load();
+            ui.checkbox(&mut peak_since_frame, "Keep peaks between frames")
+                .on_hover_text(
+                    "Output follows highest level since last frame, \
+                    instead of latest one.\n\
+                    Catches short hits at low frame rates",
+                );
+            settings.capture.peak_since_frame.store(peak_since_frame);
+            notch_widget(ui, &settings.capture);
+            filter_slope_widget(ui, &settings.capture);
+            let mut show_filter_meters =
+                settings.capture.show_filter_meters.load();
+            ui.checkbox(&mut show_filter_meters, "Show filter meters")
+                .on_hover_text(
+                    "Level meters before and after filters, \
+                    in main window. Doubles level analysis",
+                );
+            settings
+                .capture
+                .show_filter_meters
+                .store(show_filter_meters);
+            detection_mode_widget(ui, &settings.capture);
+            slider_preview_widget(ui, settings);
+            throttle_policy_widget(ui, settings);
+            agc_widget(ui, &settings.capture);
+            normalize_widget(ui, settings);
+            ducking_widget(ui, settings);
+            compressor_widget(ui, settings);
+            drop_boost_widget(ui, settings);
+            jitter_widget(ui, settings);
+            pitch_widget(ui, settings);
+            ui.add(
+                Slider::new(&mut settings.level_delay_ms, 0.0..=500.0)
+                    .text("Level delay")
+                    .suffix(" ms")
+                    .integer(),
+            )
+            .on_hover_text(
+                "Drives devices with levels from this long ago, \
+                to line up with audio that plays late, \
+                like on Bluetooth headphones or a TV.\n\
+                Can't be negative, devices can't react before sound is heard",
+            );
+            silence_stop_widget(ui, settings);
+            ui.add(
+                Slider::new(&mut settings.attack_ms, 0.0..=200.0)
+                    .text("Attack")
+                    .suffix(" ms")
+                    .integer(),
+            )
+            .on_hover_text(
+                "How long it takes to ramp up to sudden loud sounds.\n\
+                0 follows them immediately",
+            );
+            ui.add(
+                Slider::new(&mut settings.hold_ms, 0.0..=2000.0)
+                    .text("Hold")
+                    .suffix(" ms")
+                    .integer(),
+            )
+            .on_hover_text("How long peaks are held before decaying");
+            ui.add(
+                Slider::new(&mut settings.decay_ms, 0.0..=2000.0)
+                    .text("Decay")
+                    .suffix(" ms")
+                    .integer(),
+            )
+            .on_hover_text(
+                "How slowly level falls after hold.\n\
+                0 drops immediately",
+            );
+            let mut analysis_window_ms =
+                settings.capture.analysis_window_ms.load();
+            ui.add(
+                Slider::new(&mut analysis_window_ms, 5.0..=200.0)
+                    .text("Analysis window")
+                    .suffix(" ms")
+                    .integer(),
+            )
+            .on_hover_text(
+                "How much audio is analyzed at once.\n\
+                Longer is smoother, shorter is snappier",
+            );
+            settings
+                .capture
+                .analysis_window_ms
+                .store(analysis_window_ms);
+            ui.add(
+                Slider::new(&mut settings.display_rate, 1.0..=60.0)
+                    .text("Meter update rate")
+                    .suffix(" Hz"),
+            )
+            .on_hover_text(
+                "How often numbers and bars are refreshed.\n\
+                Doesn't affect how often devices are updated",
+            );
+            ui.checkbox(
+                &mut settings.start_scanning_on_startup,
+                "Start scanning on startup",
+            );
+            ui.checkbox(
+                &mut settings.stop_all_disconnects,
+                "Stop all also disconnects from servers",
+            );
+            ui.checkbox(
+                &mut settings.restore_enabled_on_reconnect,
+                "Restore enabled state on reconnect",
+            )
+            .on_hover_text(
+                "Devices that were enabled when they dropped out get enabled\n\
+                again, if they come back within 2 minutes",
+            );
+            ui.add(
+                Slider::new(&mut settings.fade_out_ms, 0.0..=2000.0)
+                    .text("Fade out when disabling")
+                    .suffix(" ms"),
+            )
+            .on_hover_text(
+                "Devices ramp down to zero before stopping.\n\
+                0 stops them right away",
+            );
+            ui.add_enabled(
+                settings.fade_out_ms > 0.0,
+                egui::Checkbox::new(
+                    &mut settings.stop_all_fades,
+                    "Stop all fades out too",
+                ),
+            )
+            .on_hover_text("Off by default, so stop all is always immediate");
+            stop_hotkey_widget(ui, &mut settings.stop_hotkey);
+            device_persistence_widget(ui, settings);
+            ui.separator();
+            ui.label("End session");
+            let end_session = &mut settings.end_session;
+            ui.add(
+                Slider::new(&mut end_session.duration, 1.0..=120.0)
+                    .text("Ramp down duration")
+                    .suffix(" s"),
+            );
+            ui.add(
+                Slider::new(&mut end_session.pulse_count, 0..=10)
+                    .text("Pulses at the end"),
+            );
+            ui.add(
+                Slider::new(&mut end_session.pulse_strength, 0.0..=1.0)
+                    .text("Pulse strength"),
+            );
+            ui.checkbox(
+                &mut end_session.exit_after,
+                "Exit after ending session",
+            );
+            ui.separator();
+            ui.label("Battery");
+            let polling = &settings.battery_polling;
+            let mut is_polling = polling.is_enabled.load();
+            ui.checkbox(&mut is_polling, "Check battery levels")
+                .on_hover_text(
+                    "Some devices stall when asked, turn off for them",
+                );
+            polling.is_enabled.store(is_polling);
+            ui.add_enabled_ui(is_polling, |ui| {
+                let mut interval = polling.interval.load();
+                ui.add(
+                    Slider::new(&mut interval, 1.0..=60.0)
+                        .text("Check every")
+                        .suffix(" s"),
+                );
+                polling.interval.store(interval);
+            });
+            let low_battery = &mut settings.low_battery;
+            ui.add(
+                Slider::new(&mut low_battery.threshold, 0.0..=0.5)
+                    .text("Warn below")
+                    .fixed_decimals(2),
+            );
+            ui.checkbox(&mut low_battery.notify, "Show warning window");
+            ui.checkbox(&mut low_battery.derate, "Limit output of low devices")
+                .on_hover_text(
+                    "Keeps full power bursts from cutting out the device",
+                );
+            ui.add_enabled_ui(low_battery.derate, |ui| {
+                ui.add(
+                    Slider::new(&mut low_battery.ceiling, 0.0..=1.0)
+                        .text("Output limit")
+                        .fixed_decimals(2),
+                );
+            });
+            ui.separator();
+            ui.label("Device config");
+            ui.add_enabled_ui(has_in_process_server, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("User device config file: ");
+                    ui.text_edit_singleline(
+                        &mut settings.user_device_config_path,
+                    );
+                });
+                ui.checkbox(
+                    &mut settings.watch_user_device_config,
+                    "Reload when file changes",
+                );
+                if ui.button("Reload device config").clicked() {
+                    response.reload_device_config = true;
+                }
+            })
+            .response
+            .on_disabled_hover_text("Only available with in-process server");
+            if let Some(error) = device_config_error {
+                ui.colored_label(Color32::RED, error);
+            }
+            ui.separator();
+            ui.label("Additional servers");
+            connection_settings_widget(ui, &mut settings.connections);
+            if ui.button("Apply server changes").clicked() {
+                response.apply_connections = true;
+            }
+            ui.separator();
+            ui.label("Device slots");
+            device_slots_widget(ui, settings, connected_devices);
+            ui.separator();
+            ui.label("Hidden devices");
+            hidden_devices_widget(ui, settings);
+            ui.separator();
+            ui.label("Device presets");
+            preset_settings_widget(ui, &mut settings.presets);
+            ui.separator();
+            ui.label("Device groups");
+            group_settings_widget(ui, &mut settings.groups, connected_devices);
+        });
+    response
+}
+
+fn device_persistence_widget(ui: &mut Ui, settings: &mut Settings) {
+    ui.checkbox(
+        &mut settings.remember_device_settings,
+        "Remember device settings",
+    )
+    .on_hover_text("Devices start with values from when they were last seen");
+    ui.add_enabled_ui(settings.remember_device_settings, |ui| {
+        ui.indent("device_persistence", |ui| {
+            let mask = &mut settings.device_persistence;
+            ui.horizontal_wrapped(|ui| {
+                ui.checkbox(&mut mask.multiplier, "Multiplier");
+                ui.checkbox(&mut mask.min, "Min");
+                ui.checkbox(&mut mask.max, "Max");
+                ui.checkbox(&mut mask.curve, "Curve");
+                ui.checkbox(&mut mask.routing, "Band and channel");
+                ui.checkbox(&mut mask.vibrators, "Vibrators");
+            });
+        });
+    });
+}
+
+fn hidden_devices_widget(ui: &mut Ui, settings: &mut Settings) {
+    if settings.hidden_devices.is_empty() {
+        ui.weak("None, right-click device name to hide it");
+        return;
+    }
+    let mut to_unhide = None;
+    for identifier in &settings.hidden_devices {
+        ui.horizontal(|ui| {
+            let alias = settings
+                .saved_devices
+                .get(identifier)
+                .and_then(|x| x.alias.as_ref());
+            ui.label(alias.unwrap_or(identifier));
+            if ui.button("Unhide").clicked() {
+                to_unhide = Some(identifier.clone());
+            }
+        });
+    }
+    if let Some(identifier) = to_unhide {
+        settings.hidden_devices.remove(&identifier);
+    }
+}
+
+fn device_slots_widget(
+    ui: &mut Ui,
+    settings: &mut Settings,
+    connected_devices: &[String],
+) {
+    let is_connected = |x: &String| connected_devices.contains(x);
+    let mut reassign = None;
+    let mut to_free = None;
+    egui::Grid::new("device_slots").show(ui, |ui| {
+        for (&slot, identifier) in &settings.device_slots {
+            let mut new_slot = slot;
+            ui.add(DragValue::new(&mut new_slot).prefix("#"))
+                .on_hover_text("Drag to move to another slot");
+            if new_slot != slot && new_slot > 0 {
+                reassign = Some((slot, new_slot));
+            }
+            let alias = settings
+                .saved_devices
+                .get(identifier)
+                .and_then(|x| x.alias.as_ref());
+            match alias {
+                Some(alias) => {
+                    ui.label(alias).on_hover_text(identifier);
+                }
+                None => {
+                    ui.label(identifier);
+                }
+            }
+            if is_connected(identifier) {
+                ui.label("Connected");
+            } else {
+                ui.weak("Not connected");
+            }
+            if ui.button("Free").clicked() {
+                to_free = Some(slot);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some((from, to)) = reassign {
+        settings.reassign_device_slot(from, to);
+    }
+    if let Some(slot) = to_free {
+        settings.device_slots.remove(&slot);
+    }
+    if ui.button("Free unused slots").clicked() {
+        settings.device_slots.retain(|_, x| is_connected(x));
+    }
+}
+
+fn preset_settings_widget(ui: &mut Ui, presets: &mut Vec<DevicePreset>) {
+    egui::Grid::new("device_presets").show(ui, |ui| {
+        ui.label("Name");
+        ui.label("Multiplier");
+        ui.label("Minimum");
+        ui.label("Maximum").on_hover_text(
+            "Minimum and maximum are relative to calibrated minimum",
+        );
+        ui.label("Curve");
+        ui.end_row();
+        for preset in presets.iter_mut() {
+            ui.add(TextEdit::singleline(&mut preset.name).desired_width(80.0));
+            ui.add(
+                DragValue::new(&mut preset.multiplier)
+                    .clamp_range(0.0..=20.0)
+                    .speed(0.01),
+            );
+            ui.add(
+                DragValue::new(&mut preset.min)
+                    .clamp_range(0.0..=1.0)
+                    .speed(0.01),
+            );
+            ui.add(
+                DragValue::new(&mut preset.max)
+                    .clamp_range(0.0..=1.0)
+                    .speed(0.01),
+            );
+            ui.add(
+                DragValue::new(&mut preset.gamma)
+                    .clamp_range(0.3..=3.0)
+                    .speed(0.01),
+            );
+            ui.end_row();
+        }
+    });
+    if ui.button("Reset presets").clicked() {
+        *presets = Settings::default().presets;
+    }
+}
+
+/// Shared controls of group, applied on top of each member's own
+fn group_header_widget(ui: &mut Ui, i: usize, group: &mut DeviceGroup) {
+    ui.push_id(("group_header", i), |ui| {
+        ui.horizontal_wrapped(|ui| {
+            let label = if group.is_enabled {
+                "Enabled"
+            } else {
+                "Enable"
+            };
+            if ui.selectable_label(group.is_enabled, label).clicked() {
+                group.is_enabled = !group.is_enabled;
+            }
+            ui.strong(&group.name);
+            ui.label("Multiplier: ");
+            ui.add(Slider::new(&mut group.multiplier, 0.0..=20.0));
+            ui.label("Minimum: ");
+            ui.add(Slider::new(&mut group.min, 0.0..=1.0));
+            ui.label("Maximum: ");
+            ui.add(Slider::new(&mut group.max, 0.0..=1.0));
+        });
+    });
+}
+
+fn group_settings_widget(
+    ui: &mut Ui,
+    groups: &mut Vec<DeviceGroup>,
+    connected_devices: &[String],
+) {
+    let mut to_remove = None;
+    for (i, group) in groups.iter_mut().enumerate() {
+        let connected = group.connected_count(connected_devices);
+        let total = group.members.len();
+        let title = format!("{} ({connected}/{total} connected)", group.name);
+        egui::CollapsingHeader::new(title)
+            .id_source(("group", i))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name: ");
+                    ui.text_edit_singleline(&mut group.name);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Multiplier: ");
+                    ui.add(
+                        DragValue::new(&mut group.multiplier)
+                            .clamp_range(0.0..=20.0)
+                            .speed(0.01),
+                    );
+                    ui.label("Minimum: ");
+                    ui.add(
+                        DragValue::new(&mut group.min)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.01),
+                    );
+                    ui.label("Maximum: ");
+                    ui.add(
+                        DragValue::new(&mut group.max)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.01),
+                    );
+                });
+                let mut to_forget = None;
+                for member in &group.members {
+                    ui.horizontal(|ui| {
+                        ui.label(member);
+                        if !connected_devices.contains(member) {
+                            ui.weak("Not connected");
+                        }
+                        if ui.small_button("Remove").clicked() {
+                            to_forget = Some(member.clone());
+                        }
+                    });
+                }
+                if let Some(member) = to_forget {
+                    group.members.retain(|x| *x != member);
+                }
+                if ui.button("Delete group").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+    }
+    if let Some(i) = to_remove {
+        groups.remove(i);
+    }
+    if ui.button("New group").clicked() {
+        groups.push(DeviceGroup::new(format!("Group {}", groups.len() + 1)));
+    }
+}
+
+fn stop_hotkey_widget(ui: &mut Ui, hotkey: &mut Option<Hotkey>) {
+    let id = ui.id().with("stop_hotkey");
+    let is_capturing = ui.memory().data.get_temp::<bool>(id).unwrap_or(false);
+    ui.horizontal(|ui| {
+        ui.label("Stop all hotkey: ");
+        if !is_capturing {
+            ui.label(hotkey.map_or("None".to_owned(), |x| x.name()));
+            if ui
+                .button("Change")
+                .on_hover_text("Works even when window isn't focused")
+                .clicked()
+            {
+                ui.memory().data.insert_temp(id, true);
+            }
+            if hotkey.is_some() && ui.button("Clear").clicked() {
+                *hotkey = None;
+            }
+            return;
+        }
+        ui.strong("Press keys...").on_hover_text(
+            "Needs Ctrl or Alt, with a letter or digit.\n\
+            Escape cancels",
+        );
+        let pressed = ui.input().events.iter().find_map(|x| match x {
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            } => Some((*key, *modifiers)),
+            _ => None,
+        });
+        let is_done = match pressed {
+            Some((Key::Escape, _)) => true,
+            Some((key, modifiers)) => {
+                let new = Hotkey::from_input(key, modifiers);
+                if new.is_some() {
+                    *hotkey = new;
+                }
+                new.is_some()
+            }
+            None => false,
+        };
+        if is_done {
+            ui.memory().data.remove::<bool>(id);
+        }
+    });
+}
+
+#[derive(Default)]
+struct SettingsWindowResponse {
+    refresh_audio_sources: bool,
+    start_calibration: bool,
+    reload_device_config: bool,
+    apply_connections: bool,
+}
+
+fn connection_settings_widget(
+    ui: &mut Ui,
+    connections: &mut Vec<ConnectionSettings>,
+) {
+    let mut to_remove = None;
+    egui::Grid::new("connection_settings").show(ui, |ui| {
+        for (i, connection) in connections.iter_mut().enumerate() {
+            ui.label("Name: ");
+            ui.text_edit_singleline(&mut connection.name);
+            ui.label("Address: ");
+            ui.text_edit_singleline(&mut connection.address);
+            if ui.button("Remove").clicked() {
+                to_remove = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = to_remove {
+        connections.remove(i);
+    }
+    if ui.button("Add server").clicked() {
+        connections.push(ConnectionSettings {
+            name: format!("Server {}", connections.len() + 1),
+            address: "ws://127.0.0.1:12345".into(),
+        });
+    }
+}
+
+fn connections_widget(
+    ui: &mut Ui,
+    connections: &mut ConnectionManager,
+    runtime: &Runtime,
+) {
+    ui.collapsing("Servers", |ui| {
+        for connection in &mut connections.connections {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{}: {}",
+                    connection.name,
+                    connection.status()
+                ));
+                let is_scanning = connection.scan.is_wanted;
+                let scan_button = SelectableLabel::new(
+                    connection.scan.state == ScanState::Scanning,
+                    connection.scan.state.label(),
+                );
+                let is_pending = connection.scan.state.is_pending();
+                if ui.add_enabled(!is_pending, scan_button).clicked() {
+                    connection.set_scanning(runtime, !is_scanning);
+                }
+                let connect_label = match &connection.state {
+                    ConnectionState::Idle => Some("Connect"),
+                    ConnectionState::Connecting(_) => None,
+                    ConnectionState::Connected(client, _)
+                        if client.connected() =>
+                    {
+                        None
+                    }
+                    _ => Some("Reconnect"),
+                };
+                if let Some(label) = connect_label {
+                    if ui.button(label).clicked() {
+                        connection.reconnect(runtime);
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Scrolling plot of output level, newest on the right
+fn level_graph_widget(
+    ui: &mut Ui,
+    levels: &LevelHistory,
+    show_input: bool,
+    hovered_min: Option<f32>,
+) {
+    let size = vec2(ui.available_width(), 80.0);
+    let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let now = Instant::now();
+    let duration = history::LEVEL_HISTORY_DURATION.as_secs_f32();
+    let to_pos = |t: Instant, level: f32| {
+        let age = (now - t).as_secs_f32() / duration;
+        pos2(
+            rect.right() - age * rect.width(),
+            rect.bottom() - level.clamp(0.0, 1.0) * rect.height(),
+        )
+    };
+    if let Some(min) = hovered_min {
+        let y = to_pos(now, min).y;
+        painter.hline(rect.x_range(), y, Stroke::new(1.0, Color32::YELLOW));
+    }
+    if show_input {
+        let points = levels.samples().map(|&(t, _, x)| to_pos(t, x));
+        let stroke = Stroke::new(1.0, Color32::GRAY);
+        painter.add(egui::Shape::line(points.collect(), stroke));
+    }
+    let points = levels.samples().map(|&(t, x, _)| to_pos(t, x));
+    let stroke = Stroke::new(1.5, ui.visuals().selection.bg_fill);
+    painter.add(egui::Shape::line(points.collect(), stroke));
+}
+
+fn timeline_widget(ui: &mut Ui, timeline: &IntensityTimeline) {
+    let size = vec2(ui.available_width(), 20.0);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+    let buckets = timeline.buckets();
+    if buckets.is_empty() {
+        return;
+    }
+    let bucket_width = rect.width() / buckets.len() as f32;
+    let painter = ui.painter();
+    for (i, bucket) in buckets.iter().enumerate() {
+        let min = pos2(rect.left() + i as f32 * bucket_width, rect.top());
+        let bucket_rect = Rect::from_min_size(min, vec2(bucket_width, 20.0));
+        let intensity = (bucket.mean() * 255.0) as u8;
+        let color = Color32::from_rgb(intensity, 0, 255 - intensity);
+        painter.rect_filled(bucket_rect, 0.0, color);
+    }
+    let duration = timeline.duration().as_secs_f32();
+    for marker in timeline.markers() {
+        let x = rect.left() + marker.as_secs_f32() / duration * rect.width();
+        painter.vline(x, rect.y_range(), Stroke::new(2.0, Color32::YELLOW));
+    }
+    if let Some(pos) = response.hover_pos() {
+        let i = ((pos.x - rect.left()) / bucket_width) as usize;
+        if let Some(bucket) = buckets.get(i) {
+            let timestamp = history::format_timestamp(timeline.bucket_start(i));
+            response.on_hover_text(format!(
+                "{timestamp}\nPeak: {:.2}%",
+                bucket.peak * 100.0
+            ));
+        }
+    }
+}
+
+/// Present until user decides what to do with saved settings
+struct SafeMode {
+    saved: Option<Settings>,
+    sections: Sections,
+    /// Accept or skip picked for remembered devices, by identifier
+    device_choices: HashMap<String, bool>,
+    show_repair: bool,
+}
+
+impl SafeMode {
+    fn new(saved: Option<Settings>) -> Self {
+        Self {
+            saved,
+            sections: Sections::default(),
+            device_choices: HashMap::new(),
+            show_repair: false,
+        }
+    }
+
+    /// Remembered devices from saved settings, compared against current
+    /// ones. Devices can connect meanwhile, so it's redone every frame.
+    fn device_imports(
+        &self,
+        settings: &Settings,
+        connected: &[DeviceSnapshot],
+    ) -> Vec<DeviceImport> {
+        let Some(saved) = &self.saved else {
+            return vec![];
+        };
+        let mut imports = DeviceImport::reconcile(
+            &saved.saved_devices,
+            &settings.saved_devices,
+            connected,
+        );
+        for import in &mut imports {
+            let choice = self.device_choices.get(&import.snapshot.identifier);
+            if let Some(&accept) = choice {
+                import.accept = accept;
+            }
+        }
+        imports
+    }
+}
+
+enum SafeModeAction {
+    Repair,
+    Reset,
+}
+
+fn safe_mode_widget(
+    ctx: &egui::Context,
+    safe_mode: &mut SafeMode,
+    device_imports: &mut [DeviceImport],
+) -> Option<SafeModeAction> {
+    let mut action = None;
+    egui::TopBottomPanel::top("safe_mode_banner").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                Color32::YELLOW,
+                "Safe mode: saved settings weren't loaded, \
+                and won't be overwritten until you repair or reset them",
+            );
+            if ui.button("Repair or reset...").clicked() {
+                safe_mode.show_repair = true;
+            }
+        });
+    });
+    Window::new("Repair settings")
+        .open(&mut safe_mode.show_repair)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if safe_mode.saved.is_some() {
+                ui.label("Choose which saved settings to bring back:");
+                let sections = &mut safe_mode.sections;
+                ui.checkbox(
+                    &mut sections.general,
+                    "General (theme, meter rate, scanning on startup)",
+                );
+                ui.checkbox(&mut sections.audio, "Volume and filters");
+                ui.checkbox(&mut sections.end_session, "End session");
+                ui.checkbox(&mut sections.device_config, "Device config");
+                ui.checkbox(&mut sections.servers, "Additional servers");
+                ui.checkbox(&mut sections.device_slots, "Device slots");
+                ui.checkbox(&mut sections.presets, "Device presets");
+                ui.checkbox(&mut sections.groups, "Device groups");
+                ui.checkbox(&mut sections.snapshots, "Snapshots");
+                if !device_imports.is_empty() {
+                    ui.separator();
+                    ui.label("Remembered device settings:");
+                    device_imports_widget(
+                        ui,
+                        device_imports,
+                        &mut safe_mode.device_choices,
+                    );
+                }
+                if ui.button("Import selected").clicked() {
+                    action = Some(SafeModeAction::Repair);
+                }
+            } else {
+                ui.label("No saved settings found");
+            }
+            ui.separator();
+            if ui.button("Reset saved settings to defaults").clicked() {
+                action = Some(SafeModeAction::Reset);
+            }
+        });
+    action
+}
+
+fn device_imports_widget(
+    ui: &mut Ui,
+    device_imports: &mut [DeviceImport],
+    choices: &mut HashMap<String, bool>,
+) {
+    egui::Grid::new("device_imports").show(ui, |ui| {
+        for import in device_imports {
+            let identifier = &import.snapshot.identifier;
+            if ui
+                .checkbox(&mut import.accept, identifier.as_str())
+                .changed()
+            {
+                choices.insert(identifier.clone(), import.accept);
+            }
+            ui.label(import.status.name());
+            let changes = match import.status {
+                ImportStatus::Unknown => "Applies once it connects".to_owned(),
+                _ if import.changes.is_empty() => "No changes".to_owned(),
+                _ => {
+                    let changes: Vec<_> = import
+                        .changes
+                        .iter()
+                        .map(|x| {
+                            format!(
+                                "{}: {} -> {}",
+                                x.name, x.current, x.imported
+                            )
+                        })
+                        .collect();
+                    changes.join("\n")
+                }
+            };
+            ui.label(changes);
+            ui.end_row();
+        }
+    });
+}
+
+struct VibratorProps {
+    /// Name of motor given by device, like "Clit vibrator", can be empty
+    descriptor: String,
+    /// Distinct speeds device supports, above 0
+    step_count: u32,
+    is_enabled: bool,
+    multiplier: f32,
+    gate: Gate,
+    max: f32,
+    /// Overrides device band, `None` follows it
+    band: Option<Band>,
+    /// Overrides device channel, `None` follows it
+    channel: Option<Channel>,
+}
+
+impl VibratorProps {
+    fn new(descriptor: String, step_count: u32) -> Self {
+        Self {
+            descriptor,
+            step_count,
+            is_enabled: true,
+            multiplier: 1.0,
+            gate: Gate::default(),
+            max: 1.0,
+            band: None,
+            channel: None,
+        }
+    }
+}
+
+/// Scalar actuator other than vibrator, at device speed
+struct ScalarProps {
+    /// Among scalar features of device
+    index: u32,
+    actuator: ActuatorType,
+    is_enabled: bool,
+    multiplier: f32,
+    gate: Gate,
+    max: f32,
+}
+
+impl ScalarProps {
+    fn new(index: u32, actuator: ActuatorType) -> Self {
+        Self {
+            index,
+            actuator,
+            is_enabled: true,
+            multiplier: 1.0,
+            gate: Gate::default(),
+            max: 1.0,
+        }
+    }
+
+    fn actuator_name(&self) -> String {
+        format!("{:?}", self.actuator)
+    }
+}
+
+/// Rotates at device speed, there's no rotation band
+struct RotatorProps {
+    is_enabled: bool,
+    multiplier: f32,
+    gate: Gate,
+    max: f32,
+    clockwise: bool,
+}
+
+impl Default for RotatorProps {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            multiplier: 1.0,
+            gate: Gate::default(),
+            max: 1.0,
+            clockwise: true,
+        }
+    }
+}
+
+/// Identifies device across sessions, for persisted per-device data.
+/// `occurrence` tells apart identical devices, first one is 0.
+fn device_identifier(device: &ButtplugClientDevice, occurrence: u32) -> String {
+    match occurrence {
+        0 => device.name().clone(),
+        _ => format!("{} ({})", device.name(), occurrence + 1),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SoloState {
+    Off,
+    Soloed,
+    /// Other device is soloed
+    Muted,
+}
+
+enum DeviceAction {
+    ToggleSolo,
+    /// Stop controlling device and remove its card
+    Hide,
+    /// Index into group list, `None` leaves all groups
+    SetGroup(Option<usize>),
+}
+
+fn disconnected_devices_widget(
+    ui: &mut Ui,
+    devices: &mut Vec<DisconnectedDevice>,
+) {
+    let mut to_dismiss = None;
+    for (i, device) in devices.iter().enumerate() {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.weak(&device.name);
+                ui.label("Disconnected").on_hover_text(
+                    "Settings come back when device is found again",
+                );
+                if ui.small_button("Dismiss").clicked() {
+                    to_dismiss = Some(i);
+                }
+            });
+        });
+    }
+    if let Some(i) = to_dismiss {
+        devices.remove(i);
+    }
+}
+
+/// Per-device values that don't live in `DeviceProps`
+struct DeviceInfo<'a> {
+    slot: u32,
+    connection: Option<&'a str>,
+    solo: SoloState,
+    groups: &'a [DeviceGroup],
+}
+
+/// Name label, or alias editor while renaming
+fn device_name_widget(
+    ui: &mut Ui,
+    device: &ButtplugClientDevice,
+    props: &mut DeviceProps,
+    info: &DeviceInfo,
+) -> Option<DeviceAction> {
+    let slot = info.slot;
+    let prefix = if cfg!(debug_assertions) {
+        format!("#{slot} ({})", device.index())
+    } else {
+        format!("#{slot}")
+    };
+    let edit_id = egui::Id::new(("device_alias", slot));
+    if let Some(text) = &mut props.alias_edit {
+        ui.label(prefix);
+        let edit = ui.add(
+            TextEdit::singleline(text)
+                .id(edit_id)
+                .hint_text(props.identifier.as_str())
+                .desired_width(160.0),
+        );
+        if edit.lost_focus() {
+            let is_cancelled = ui.input().key_pressed(Key::Escape);
+            let text = props.alias_edit.take().unwrap_or_default();
+            let text = text.trim();
+            if !is_cancelled {
+                props.alias = (!text.is_empty() && text != props.identifier)
+                    .then(|| text.to_owned());
+            }
+        }
+        return None;
+    }
+    let name = format!("{prefix} {}", props.display_name());
+    let mut name_label = ui
+        .add(Label::new(name).sense(Sense::click()))
+        .on_hover_text("Right-click to rename");
+    if props.alias.is_some() {
+        name_label =
+            name_label.on_hover_text(format!("Device: {}", props.identifier));
+    }
+    if let Some(connection) = info.connection {
+        name_label = name_label.on_hover_text(format!("Server: {connection}"));
+    }
+    let mut action = None;
+    if name_label.double_clicked() {
+        action = Some(DeviceAction::ToggleSolo);
+    }
+    name_label.context_menu(|ui| {
+        if ui.button("Rename").clicked() {
+            props.alias_edit = Some(props.alias.clone().unwrap_or_default());
+            ui.memory().request_focus(edit_id);
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(props.alias.is_some(), Button::new("Clear alias"))
+            .clicked()
+        {
+            props.alias = None;
+            ui.close_menu();
+        }
+        if ui
+            .button("Hide")
+            .on_hover_text("Never control this device. Unhide in settings")
+            .clicked()
+        {
+            action = Some(DeviceAction::Hide);
+            ui.close_menu();
+        }
+    });
+    action
+}
+
+fn device_widget(
+    ui: &mut Ui,
+    device: Arc<ButtplugClientDevice>,
+    props: &mut DeviceProps,
+    info: &DeviceInfo,
+    frame_state: &FrameState,
+    runtime: &Runtime,
+) -> Option<DeviceAction> {
+    let fade_ms = frame_state.fade_out_ms;
+    if props.is_fading_out() && props.fade_out_factor(fade_ms) <= 0.0 {
+        props.disable(runtime, &device, 0.0);
+    }
+    let fade = props.fade_out_factor(fade_ms);
+    let levels = props.process_levels(frame_state);
+    let sound_power = levels.device;
+    props.alternation.advance(props.alternate.rate);
+    let end_session = frame_state.end_session;
+    let slot = info.slot;
+    let group_index = info
+        .groups
+        .iter()
+        .position(|x| x.members.contains(&props.identifier));
+    let group = group_index.map(|i| &info.groups[i]);
+    let can_test = !props.is_testing() && props.has_actuators();
+    let mut action = None;
+    let group = ui.group(|ui| {
+        ui.horizontal(|ui| {
+            action = device_name_widget(ui, &device, props, info);
+            // nothing more gets sent, not even this frame
+            if let Some(DeviceAction::Hide) = action {
+                props.is_enabled = false;
+            }
+            let is_soloed = info.solo == SoloState::Soloed;
+            let solo_label = if is_soloed { "SOLO" } else { "S" };
+            if ui
+                .selectable_label(is_soloed, solo_label)
+                .on_hover_text(
+                    "Solo: mute all other devices.\n\
+                    Double-clicking name works too",
+                )
+                .clicked()
+            {
+                action = Some(DeviceAction::ToggleSolo);
+            }
+            if info.solo == SoloState::Muted {
+                ui.weak("Muted by solo");
+            }
+            let test_button = Button::new("Test");
+            if ui
+                .add_enabled(can_test, test_button)
+                .on_hover_text("Short pulse of all actuators, even if disabled")
+                .clicked()
+            {
+                props.start_test(runtime, device.clone(), None);
+            }
+        });
+
+        if let Some(bat) = props.battery_state.get_level() {
+            let text = format!("Battery: {}%", bat * 100.0);
+            if props.is_battery_low(&frame_state.low_battery) {
+                let limit = if frame_state.low_battery.derate {
+                    ", output limited"
+                } else {
+                    ""
+                };
+                ui.colored_label(Color32::RED, format!("{text} (low{limit})"));
+            } else {
+                ui.label(text);
+            }
+        }
+        ui.horizontal(|ui| {
+            presets_widget(ui, props, frame_state.presets);
+            ui.separator();
+            if !info.groups.is_empty() {
+                let new_index =
+                    device_group_widget(ui, group_index, info.groups, slot);
+                if new_index != group_index {
+                    action = Some(DeviceAction::SetGroup(new_index));
+                }
+                ui.separator();
+            }
+            let is_overridden = props.low_pass_override.is_some();
+            let is_mixed = props.band_weights.is_some();
+            ui.add_enabled_ui(!is_overridden && !is_mixed, |ui| {
+                device_band_widget(ui, &mut props.band, slot);
+            });
+            ui.add_enabled_ui(
+                !is_overridden && !is_mixed && props.band == Band::FullRange,
+                |ui| {
+                    channel_widget(
+                        ui,
+                        &mut props.channel,
+                        slot,
+                        frame_state.is_mono_band,
+                    );
+                },
+            );
+            device_low_pass_widget(ui, &mut props.low_pass_override);
+            ui.add(
+                Slider::new(&mut props.transient_blend, 0.0..=1.0)
+                    .text("Hits")
+                    .fixed_decimals(2),
+            )
+            .on_hover_text(
+                "0 follows sustained level, good for bass drops.\n\
+                1 follows only sudden rises, like drum hits",
+            );
+            ui.checkbox(&mut props.is_inverted, "Invert").on_hover_text(
+                "Quiet parts drive device stronger, loud ones weaker.\n\
+                Minimum and maximum apply after inverting.\n\
+                Silence is full output, so auto-stop skips it",
+            );
+            ui.checkbox(&mut props.use_pitch, "Pitch").on_hover_text(
+                "Low notes make output stronger, high notes weaker.\n\
+                Range is in settings",
+            );
+            ui.add(
+                Slider::new(&mut props.width_depth, 0.0..=1.0)
+                    .text("Width")
+                    .fixed_decimals(2),
+            )
+            .on_hover_text(
+                "Wide stereo parts, like big choruses, make output stronger.\n\
+                Mono sources are unaffected",
+            );
+            ui.add(
+                Slider::new(&mut props.delay_ms, 0.0..=500.0)
+                    .text("Delay")
+                    .suffix(" ms")
+                    .fixed_decimals(0),
+            )
+            .on_hover_text(
+                "Holds back this device, to line it up with slower ones",
+            );
+        });
+
+        ui.add_enabled_ui(props.low_pass_override.is_none(), |ui| {
+            band_weights_widget(ui, &mut props.band_weights);
+        });
+
+        if frame_state.refresh_display {
+            let (speed, cutoff) = props.calculate_visual_output(
+                sound_power,
+                group,
+                &frame_state.low_battery,
+            );
+            let speed = end_session.map_or(speed, |x| x.apply(speed));
+            props.displayed_output = (speed, cutoff);
+        }
+        let (speed, cutoff) = props.displayed_output;
+        let peak = if props.is_enabled {
+            let (output, _) = props.calculate_visual_output(
+                sound_power,
+                group,
+                &frame_state.low_battery,
+            );
+            props.peak_marker.process(output)
+        } else {
+            props.peak_marker.reset();
+            0.0
+        };
+        let group_power =
+            group.map_or(sound_power, |g| g.scale_input(sound_power));
+        let is_clipping =
+            props.is_enabled && props.curve().is_clipping(group_power);
+        props.clips.record(is_clipping);
+        if is_clipping {
+            frame_state.is_clipping.set(true);
+        }
+
+        ui.horizontal(|ui| {
+            let label = if props.is_fading_out() {
+                "Stopping"
+            } else if props.is_enabled {
+                "Enabled"
+            } else {
+                "Enable"
+            };
+            let enable_button = SelectableLabel::new(props.is_enabled, label);
+            ui.group(|ui| {
+                let button = ui.add_sized([60.0, 60.0], enable_button);
+                let button = if props.is_fading_out() {
+                    button.on_hover_text("Click to keep it running")
+                } else {
+                    button
+                };
+                if button.clicked() {
+                    if props.is_fading_out() {
+                        props.fade_out_start = None;
+                    } else if props.is_enabled {
+                        props.disable(runtime, &device, fade_ms);
+                    } else {
+                        props.is_enabled = true;
+                    }
+                }
+            });
+            ui.vertical(|ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{:.2}%", speed * 100.0));
+                    clip_light_widget(ui, &props.clips);
+                    if cutoff {
+                        ui.visuals_mut().selection.bg_fill = Color32::RED;
+                    }
                     if !props.is_enabled {
                         ui.visuals_mut().selection.bg_fill = Color32::GRAY;
                     }
-                    ui.add(ProgressBar::new(speed));
+                    let mut bar = ProgressBar::new(speed);
+                    if props.is_fading_out() {
+                        bar = bar.text("Stopping");
+                    } else if props.is_manual {
+                        bar = bar.text("Manual");
+                    }
+                    let bar = ui.add(bar);
+                    if props.is_enabled {
+                        peak_marker_widget(ui, bar.rect, peak);
+                    }
                 });
                 ui.horizontal_wrapped(|ui| {
+                    let is_dragging = &frame_state.is_dragging_output;
                     ui.label("Multiplier: ");
-                    ui.add(Slider::new(&mut props.multiplier, 0.0..=20.0));
-                    ui.label("Minimum (cut-off): ");
-                    ui.add(Slider::new(&mut props.min, 0.0..=1.0));
+                    output_slider(
+                        ui,
+                        &mut props.multiplier,
+                        |x| Slider::new(x, 0.0..=20.0),
+                        is_dragging,
+                    );
+                    gate_widget(ui, &mut props.gate);
                     ui.label("Maximum: ");
-                    ui.add(Slider::new(&mut props.max, 0.0..=1.0));
+                    output_slider(
+                        ui,
+                        &mut props.max,
+                        |x| Slider::new(x, 0.0..=1.0),
+                        is_dragging,
+                    );
+                    ui.label("Curve: ");
+                    let curve = output_slider(
+                        ui,
+                        &mut props.gamma,
+                        |x| Slider::new(x, 0.3..=3.0).logarithmic(true),
+                        is_dragging,
+                    )
+                    .on_hover_text(
+                        "Below 1 boosts quiet parts, above 1 compresses them.\n\
+                        Double-click to reset",
+                    );
+                    if curve.double_clicked() {
+                        props.gamma = 1.0;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut props.is_manual, "Manual").on_hover_text(
+                        "Set output by hand instead of following audio.\n\
+                        Multiplier, minimum and maximum still apply",
+                    );
+                    ui.add_enabled(
+                        props.is_manual,
+                        Slider::new(&mut props.manual_level, 0.0..=1.0)
+                            .fixed_decimals(2),
+                    );
                 });
                 ui.collapsing("Vibrators", |ui| {
+                    if props.vibrators.len() > 1 {
+                        alternate_widget(ui, &mut props.alternate);
+                    }
                     ui.group(|ui| {
+                        let mut to_test = None;
                         for (i, vibe) in props.vibrators.iter_mut().enumerate()
                         {
-                            vibrator_widget(ui, i, vibe);
+                            let test = vibrator_widget(
+                                ui,
+                                i,
+                                vibe,
+                                slot,
+                                frame_state,
+                                can_test,
+                            );
+                            if test {
+                                to_test = Some(i);
+                            }
+                        }
+                        if to_test.is_some() {
+                            props.start_test(runtime, device.clone(), to_test);
                         }
                     });
                 });
-                if props.is_enabled {
-                    let speed = props.calculate_output(sound_power);
-                    let speed_cmd = VibrateCommand::SpeedVec(
-                        props
-                            .vibrators
-                            .iter()
-                            .map(|v| {
-                                if v.is_enabled {
-                                    (speed * v.multiplier)
-                                        .clamp(0.0, v.max)
-                                        .min_cutoff(v.min)
-                                        as f64
-                                } else {
-                                    0.0
-                                }
-                            })
-                            .collect(),
+                if let Some(stroke) = &mut props.stroke {
+                    ui.collapsing("Strokes", |ui| {
+                        stroke_widget(ui, stroke);
+                    });
+                }
+                if !props.scalars.is_empty() {
+                    ui.collapsing("Other actuators", |ui| {
+                        ui.group(|ui| {
+                            for scalar in &mut props.scalars {
+                                scalar_widget(ui, scalar, frame_state);
+                            }
+                        });
+                    });
+                }
+                if !props.rotators.is_empty() {
+                    ui.collapsing("Rotators", |ui| {
+                        ui.group(|ui| {
+                            for (i, rotator) in
+                                props.rotators.iter_mut().enumerate()
+                            {
+                                rotator_widget(ui, i, rotator, frame_state);
+                            }
+                        });
+                    });
+                }
+                let is_muted = info.solo == SoloState::Muted;
+                let is_frozen = frame_state.is_output_frozen();
+                let is_paused = frame_state.is_silence_paused;
+                let mut stroke_level = 0.0;
+                // test pulse has device for itself
+                let is_testing = props.test.is_some();
+                // manual output doesn't depend on audio being there,
+                // and silence is full output when inverted
+                let is_paused =
+                    is_paused && !props.is_manual && !props.is_inverted;
+                if props.is_enabled
+                    && !is_testing
+                    && !is_paused
+                    && (is_muted || !is_frozen)
+                {
+                    // battery ceiling comes after vibrator multipliers too
+                    let limit = if is_muted {
+                        0.0
+                    } else {
+                        frame_state.output_limit().unwrap_or(1.0)
+                    }
+                    .min(props.battery_ceiling(&frame_state.low_battery));
+                    let speed = props.calculate_output(
+                        sound_power,
+                        group,
+                        &frame_state.low_battery,
+                    );
+                    stroke_level = end_session
+                        .map_or(speed, |x| x.apply(speed))
+                        .min(limit)
+                        * fade;
+                    let count = props.vibrators.len();
+                    let speeds: Vec<_> = levels
+                        .vibrators
+                        .iter()
+                        .enumerate()
+                        .map(|(i, level)| {
+                            let speed = match level {
+                                Some(_) if props.is_manual => speed,
+                                Some(level) => props.calculate_vibrator_output(
+                                    *level,
+                                    group,
+                                    &frame_state.low_battery,
+                                ),
+                                None => speed,
+                            };
+                            let depth = props.alternate.depth;
+                            speed * props.alternation.factor(i, count, depth)
+                        })
+                        .collect();
+                    let vibe_speeds: Vec<f64> = props
+                        .vibrators
+                        .iter_mut()
+                        .zip(speeds)
+                        .map(|(v, speed)| {
+                            if v.is_enabled {
+                                let speed = v.gate.process(
+                                    (speed * v.multiplier).clamp(0.0, v.max),
+                                );
+                                let speed = end_session.map_or(speed, |x| {
+                                    x.apply(speed).min(v.max)
+                                });
+                                (speed.min(limit) * fade) as f64
+                            } else {
+                                0.0
+                            }
+                        })
+                        .collect();
+                    let rotate_speeds: Vec<(f64, bool)> = props
+                        .rotators
+                        .iter_mut()
+                        .map(|r| {
+                            if r.is_enabled {
+                                let speed = r.gate.process(
+                                    (speed * r.multiplier).clamp(0.0, r.max),
+                                );
+                                let speed = end_session.map_or(speed, |x| {
+                                    x.apply(speed).min(r.max)
+                                });
+                                ((speed.min(limit) * fade) as f64, r.clockwise)
+                            } else {
+                                (0.0, r.clockwise)
+                            }
+                        })
+                        .collect();
+                    let scalar_values: Vec<_> = props
+                        .scalars
+                        .iter_mut()
+                        .map(|s| {
+                            let speed = if s.is_enabled {
+                                let speed = s.gate.process(
+                                    (speed * s.multiplier).clamp(0.0, s.max),
+                                );
+                                let speed = end_session.map_or(speed, |x| {
+                                    x.apply(speed).min(s.max)
+                                });
+                                (speed.min(limit) * fade) as f64
+                            } else {
+                                0.0
+                            };
+                            (s.index, (speed, s.actuator))
+                        })
+                        .collect();
+                    // half a step of change can already reach next step
+                    let vibe_resolutions = props
+                        .vibrators
+                        .iter()
+                        .map(|v| 0.5 / v.step_count.max(1) as f64);
+                    let levels: Vec<_> = vibe_speeds
+                        .iter()
+                        .copied()
+                        .zip(vibe_resolutions)
+                        // direction as sign, so reversing counts as change
+                        .chain(rotate_speeds.iter().map(|&(x, clockwise)| {
+                            (if clockwise { x } else { -x }, SEND_EPSILON)
+                        }))
+                        .chain(
+                            scalar_values
+                                .iter()
+                                .map(|x| (x.1 .0, SEND_EPSILON)),
+                        )
+                        .collect();
+                    let is_changed =
+                        is_worth_sending(props.last_sent.as_deref(), &levels);
+                    let is_auto =
+                        frame_state.throttle_policy == ThrottlePolicy::Auto;
+                    let timer = if is_changed {
+                        props.throttle.attempt(is_auto)
+                    } else {
+                        None
+                    };
+                    if let Some(timer) = timer {
+                        props.last_sent =
+                            Some(levels.iter().map(|x| x.0).collect());
+                        let speed_cmd = VibrateCommand::SpeedVec(vibe_speeds);
+                        let rotate_cmd =
+                            RotateCommand::RotateVec(rotate_speeds);
+                        let scalar_cmd = ScalarCommand::ScalarMap(
+                            scalar_values.into_iter().collect(),
+                        );
+                        let has_rotators = !props.rotators.is_empty();
+                        let has_scalars = !props.scalars.is_empty();
+                        // devices without vibrators reject empty commands
+                        let has_other = has_rotators
+                            || has_scalars
+                            || props.stroke.is_some();
+                        let is_vibrate_less =
+                            has_other && props.vibrators.is_empty();
+                        let command = (!is_vibrate_less)
+                            .then(|| device.vibrate(&speed_cmd));
+                        let rotate =
+                            has_rotators.then(|| device.rotate(&rotate_cmd));
+                        let scalar =
+                            has_scalars.then(|| device.scalar(&scalar_cmd));
+                        runtime.spawn(async move {
+                            let mut result = Ok(());
+                            if let Some(command) = command {
+                                result = command.await;
+                            }
+                            if let Some(rotate) = rotate {
+                                result = result.and(rotate.await);
+                            }
+                            if let Some(scalar) = scalar {
+                                result = result.and(scalar.await);
+                            }
+                            timer.finish();
+                            result
+                        });
+                    }
+                } else {
+                    // device might have been stopped meanwhile
+                    props.last_sent = None;
+                }
+                // strokes are timed by their own task, not throttle
+                if let Some(stroke) = &mut props.stroke {
+                    stroke.update(
+                        runtime,
+                        &device,
+                        props.is_enabled,
+                        stroke_level,
                     );
-                    runtime.spawn(device.vibrate(&speed_cmd));
                 }
+                throttle_widget(
+                    ui,
+                    &props.throttle,
+                    frame_state.throttle_policy,
+                );
+            })
+        })
+    });
+    if ui.rect_contains_pointer(group.response.rect) {
+        frame_state.hovered_min.set(Some(props.gate.open));
+    }
+    action
+}
+
+/// Whether any of (level, resolution) pairs differs enough from `last` sent
+/// levels. Reaching 0 always counts, so devices don't get stuck running.
+fn is_worth_sending(last: Option<&[f64]>, levels: &[(f64, f64)]) -> bool {
+    let Some(last) = last else {
+        return true;
+    };
+    last.len() != levels.len()
+        || last
+            .iter()
+            .zip(levels)
+            .any(|(&last, &(level, resolution))| {
+                (level - last).abs() >= resolution
+                    || (level == 0.0 && last != 0.0)
             })
+}
+
+/// Devices with cutoffs rounding to same Hz share their power
+fn cutoff_key(cutoff: f32) -> u32 {
+    cutoff.round().max(1.0) as u32
+}
+
+/// Thin line over progress bar, at `peak` share of its width
+fn peak_marker_widget(ui: &mut Ui, bar: Rect, peak: f32) {
+    if peak <= 0.0 {
+        return;
+    }
+    let x = bar.left() + bar.width() * peak.clamp(0.0, 1.0);
+    let stroke = Stroke::new(2.0, ui.visuals().strong_text_color());
+    ui.painter().vline(x, bar.y_range(), stroke);
+}
+
+/// Lit when output was clamped to maximum within last second
+fn clip_light_widget(ui: &mut Ui, clips: &ClipHistory) {
+    let size = vec2(10.0, 10.0);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+    let color = if clips.is_lit() {
+        Color32::RED
+    } else {
+        ui.visuals().widgets.inactive.bg_fill
+    };
+    ui.painter()
+        .circle_filled(rect.center(), size.x / 2.0, color);
+    response.on_hover_text(format!(
+        "Clip: output hit its maximum\n\
+        Clipped {:.0}% of last minute",
+        clips.clipped_share() * 100.0
+    ));
+}
+
+fn throttle_widget(ui: &mut Ui, throttle: &Throttle, policy: ThrottlePolicy) {
+    let (Some(latency), Some(interval)) =
+        (throttle.overload(), throttle.interval())
+    else {
+        return;
+    };
+    match policy {
+        ThrottlePolicy::Auto => {
+            ui.weak(format!("Auto-throttled to {} ms", latency.as_millis()))
+                .on_hover_text(format!(
+                    "Device takes longer to handle commands, \
+                    than the {} ms between updates",
+                    interval.as_millis()
+                ));
+        }
+        ThrottlePolicy::Suggest => {
+            ui.colored_label(
+                Color32::YELLOW,
+                format!(
+                    "Device takes {} ms per command, but gets one every {} ms, \
+                    so they pile up. Consider turning on auto-throttle.",
+                    latency.as_millis(),
+                    interval.as_millis()
+                ),
+            );
+        }
+    }
+}
+
+/// Returns new group index
+fn device_group_widget(
+    ui: &mut Ui,
+    index: Option<usize>,
+    groups: &[DeviceGroup],
+    slot: u32,
+) -> Option<usize> {
+    let mut index = index;
+    let name = |x: Option<usize>| {
+        x.and_then(|i| groups.get(i))
+            .map_or("None", |x| x.name.as_str())
+    };
+    ui.label("Group: ");
+    egui::ComboBox::from_id_source(("group", slot))
+        .selected_text(name(index))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut index, None, name(None));
+            for i in 0..groups.len() {
+                ui.selectable_value(&mut index, Some(i), name(Some(i)));
+            }
+        });
+    index
+}
+
+fn presets_widget(
+    ui: &mut Ui,
+    props: &mut DeviceProps,
+    presets: &[DevicePreset],
+) {
+    ui.label("Preset: ");
+    let mut is_custom = true;
+    for preset in presets {
+        let is_matching = props.matches_preset(preset);
+        is_custom &= !is_matching;
+        if ui.selectable_label(is_matching, &preset.name).clicked() {
+            props.apply_preset(preset);
+        }
+    }
+    if is_custom {
+        ui.weak("Custom");
+    }
+}
+
+fn device_band_widget(ui: &mut Ui, band: &mut Band, slot: u32) {
+    ui.label("Band: ");
+    egui::ComboBox::from_id_source(("band", slot))
+        .selected_text(band.name())
+        .show_ui(ui, |ui| {
+            for x in Band::ALL {
+                ui.selectable_value(band, x, x.name());
+            }
+        })
+        .response
+        .on_hover_text("Full range follows main volume");
+}
+
+fn vibrator_band_widget(
+    ui: &mut Ui,
+    band: &mut Option<Band>,
+    id: (u32, usize),
+) {
+    let name = |x: Option<Band>| x.map_or("Device default", |x| x.name());
+    ui.label("Band: ");
+    egui::ComboBox::from_id_source(("vibe_band", id))
+        .selected_text(name(*band))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(band, None, name(None));
+            for x in Band::ALL {
+                ui.selectable_value(band, Some(x), x.name());
+            }
+        })
+        .response
+        .on_hover_text(
+            "Lets motors of one device follow different frequencies, \
+            like bass on one and highs on another.\n\
+            Device minimum only applies to device default",
+        );
+}
+
+fn vibrator_channel_widget(
+    ui: &mut Ui,
+    channel: &mut Option<Channel>,
+    id: (u32, usize),
+    is_mono_band: bool,
+) {
+    let name = |x: Option<Channel>| x.map_or("Device default", |x| x.name());
+    ui.label("Source: ");
+    let response = ui
+        .add_enabled_ui(!is_mono_band, |ui| {
+            egui::ComboBox::from_id_source(("vibe_channel", id))
+                .selected_text(name(*channel))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(channel, None, name(None));
+                    for x in Channel::ALL {
+                        ui.selectable_value(channel, Some(x), x.name());
+                    }
+                })
+        })
+        .response;
+    if is_mono_band {
+        response.on_disabled_hover_text(MONO_BAND_HINT);
+    } else {
+        response.on_hover_text(
+            "Lets motors follow different stereo channels, \
+            like left on one and right on another.\n\
+            Mono audio drives both the same. Only used with full range band",
+        );
+    }
+}
+
+fn channel_widget(
+    ui: &mut Ui,
+    channel: &mut Channel,
+    slot: u32,
+    is_mono_band: bool,
+) {
+    ui.label("Source: ");
+    let response = ui
+        .add_enabled_ui(!is_mono_band, |ui| {
+            egui::ComboBox::from_id_source(("channel", slot))
+                .selected_text(channel.name())
+                .show_ui(ui, |ui| {
+                    for x in Channel::ALL {
+                        ui.selectable_value(channel, x, x.name());
+                    }
+                })
         })
+        .response;
+    if is_mono_band {
+        response.on_disabled_hover_text(MONO_BAND_HINT);
+    } else {
+        response.on_hover_text("Audio channel driving this device");
+    }
+}
+
+fn device_low_pass_widget(ui: &mut Ui, low_pass_override: &mut Option<f32>) {
+    let mut is_overridden = low_pass_override.is_some();
+    ui.checkbox(&mut is_overridden, "Own low pass")
+        .on_hover_text(
+            "Follows only frequencies below its own cutoff, \
+            instead of global filters, band and channel",
+        );
+    if !is_overridden {
+        *low_pass_override = None;
+        return;
+    }
+    let cutoff = low_pass_override.get_or_insert(DEVICE_LOW_PASS);
+    ui.add(
+        Slider::new(cutoff, 20.0..=20_000.0)
+            .logarithmic(true)
+            .integer()
+            .suffix(" Hz"),
+    );
+}
+
+fn band_weights_widget(ui: &mut Ui, band_weights: &mut Option<BandWeights>) {
+    ui.horizontal(|ui| {
+        let mut is_mixed = band_weights.is_some();
+        ui.checkbox(&mut is_mixed, "Mix bands").on_hover_text(
+            "Follows bass, mids and highs at once, instead of one band.\n\
+            Only ratios of weights matter, equal ones follow whole spectrum",
+        );
+        if !is_mixed {
+            *band_weights = None;
+            return;
+        }
+        let weights = band_weights.get_or_insert_with(BandWeights::default);
+        for (weight, name) in [
+            (&mut weights.bass, "Bass"),
+            (&mut weights.mids, "Mids"),
+            (&mut weights.highs, "Highs"),
+        ] {
+            ui.add(Slider::new(weight, 0.0..=1.0).text(name).fixed_decimals(2));
+        }
+    });
+}
+
+fn gate_widget(ui: &mut Ui, gate: &mut Gate) {
+    // close threshold follows open one, until set apart
+    let is_linked = gate.close == gate.open;
+    ui.label("Minimum (gate open): ");
+    ui.add(Slider::new(&mut gate.open, 0.0..=1.0));
+    if is_linked {
+        gate.close = gate.open;
+    }
+    ui.label("Gate close: ");
+    ui.add(Slider::new(&mut gate.close, 0.0..=gate.open))
+        .on_hover_text(
+            "Once open, output is cut off only after falling below this",
+        );
+    ui.label("Hold: ");
+    ui.add(Slider::new(&mut gate.hold, 0.0..=2.0).suffix(" s"))
+        .on_hover_text("How long level must stay below close threshold");
+    ui.label("Knee: ");
+    ui.add(Slider::new(&mut gate.knee, 0.0..=0.25))
+        .on_hover_text("Fades output in around minimum, instead of jumping");
+}
+
+fn scalar_widget(
+    ui: &mut Ui,
+    scalar: &mut ScalarProps,
+    frame_state: &FrameState,
+) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label(format!("{} {}: ", scalar.actuator_name(), scalar.index));
+        let label = if scalar.is_enabled {
+            "Enabled"
+        } else {
+            "Enable"
+        };
+        if ui.selectable_label(scalar.is_enabled, label).clicked() {
+            scalar.is_enabled = !scalar.is_enabled;
+        }
+
+        let is_dragging = &frame_state.is_dragging_output;
+        ui.label("Multiplier: ");
+        output_slider(
+            ui,
+            &mut scalar.multiplier,
+            |x| Slider::new(x, 0.0..=5.0),
+            is_dragging,
+        );
+        gate_widget(ui, &mut scalar.gate);
+        ui.label("Maximum: ");
+        output_slider(
+            ui,
+            &mut scalar.max,
+            |x| Slider::new(x, 0.0..=1.0),
+            is_dragging,
+        );
+
+        if ui.button("Reset").clicked() {
+            *scalar = ScalarProps::new(scalar.index, scalar.actuator);
+        }
+    });
+}
+
+fn stroke_widget(ui: &mut Ui, stroke: &mut StrokeState) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Fastest stroke: ");
+        ui.add(
+            Slider::new(&mut stroke.min_duration, 100.0..=2000.0)
+                .integer()
+                .suffix(" ms"),
+        )
+        .on_hover_text("Time of one stroke at full level");
+        ui.label("Slowest stroke: ");
+        ui.add(
+            Slider::new(&mut stroke.max_duration, 100.0..=5000.0)
+                .integer()
+                .suffix(" ms"),
+        )
+        .on_hover_text("Time of one stroke at lowest level");
+        ui.checkbox(&mut stroke.use_depth, "Depth follows level")
+            .on_hover_text("Quiet parts make strokes shorter, not only slower");
+    });
+}
+
+fn rotator_widget(
+    ui: &mut Ui,
+    index: usize,
+    rotator: &mut RotatorProps,
+    frame_state: &FrameState,
+) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label(format!("Rotator {index}: "));
+        let label = if rotator.is_enabled {
+            "Enabled"
+        } else {
+            "Enable"
+        };
+        if ui.selectable_label(rotator.is_enabled, label).clicked() {
+            rotator.is_enabled = !rotator.is_enabled;
+        }
+
+        let is_dragging = &frame_state.is_dragging_output;
+        ui.label("Multiplier: ");
+        output_slider(
+            ui,
+            &mut rotator.multiplier,
+            |x| Slider::new(x, 0.0..=5.0),
+            is_dragging,
+        );
+        gate_widget(ui, &mut rotator.gate);
+        ui.label("Maximum: ");
+        output_slider(
+            ui,
+            &mut rotator.max,
+            |x| Slider::new(x, 0.0..=1.0),
+            is_dragging,
+        );
+        ui.checkbox(&mut rotator.clockwise, "Clockwise");
+
+        if ui.button("Reset").clicked() {
+            *rotator = RotatorProps::default();
+        }
+    });
+}
+
+fn alternate_widget(ui: &mut Ui, alternate: &mut AlternateSnapshot) {
+    ui.horizontal(|ui| {
+        ui.add(
+            Slider::new(&mut alternate.depth, 0.0..=1.0)
+                .text("Alternate")
+                .fixed_decimals(2),
+        )
+        .on_hover_text(
+            "Motors take turns, in a wave going around them.\n\
+            0 keeps them in unison",
+        );
+        ui.add_enabled(
+            alternate.depth > 0.0,
+            Slider::new(&mut alternate.rate, 0.05..=5.0)
+                .logarithmic(true)
+                .text("Rate")
+                .suffix(" Hz"),
+        );
     });
 }
 
-fn vibrator_widget(ui: &mut Ui, index: usize, vibe: &mut VibratorProps) {
+/// Returns `true` if test was requested
+fn vibrator_widget(
+    ui: &mut Ui,
+    index: usize,
+    vibe: &mut VibratorProps,
+    slot: u32,
+    frame_state: &FrameState,
+    can_test: bool,
+) -> bool {
+    let mut test = false;
     ui.horizontal_wrapped(|ui| {
-        ui.label(format!("Vibe {index}: "));
+        let descriptor = vibe.descriptor.trim();
+        // some devices report placeholder instead of leaving it empty
+        let name = if descriptor.is_empty() || descriptor == "N/A" {
+            format!("Vibe {index}: ")
+        } else {
+            format!("Vibe {index} - {descriptor}: ")
+        };
+        ui.label(name);
+        ui.weak(format!("Vibrate, {} steps", vibe.step_count))
+            .on_hover_text("Speeds sent to device are rounded to these steps");
         let label = if vibe.is_enabled { "Enabled" } else { "Enable" };
         if ui.selectable_label(vibe.is_enabled, label).clicked() {
             vibe.is_enabled = !vibe.is_enabled;
         }
 
+        let is_dragging = &frame_state.is_dragging_output;
         ui.label("Multiplier: ");
-        ui.add(Slider::new(&mut vibe.multiplier, 0.0..=5.0));
-        ui.label("Minimum (cut-off): ");
-        ui.add(Slider::new(&mut vibe.min, 0.0..=1.0));
+        output_slider(
+            ui,
+            &mut vibe.multiplier,
+            |x| Slider::new(x, 0.0..=5.0),
+            is_dragging,
+        );
+        gate_widget(ui, &mut vibe.gate);
         ui.label("Maximum: ");
-        ui.add(Slider::new(&mut vibe.max, 0.0..=1.0));
+        output_slider(
+            ui,
+            &mut vibe.max,
+            |x| Slider::new(x, 0.0..=1.0),
+            is_dragging,
+        );
+        vibrator_band_widget(ui, &mut vibe.band, (slot, index));
+        vibrator_channel_widget(
+            ui,
+            &mut vibe.channel,
+            (slot, index),
+            frame_state.is_mono_band,
+        );
 
         if ui.button("Reset").clicked() {
-            *vibe = VibratorProps::default();
+            let descriptor = std::mem::take(&mut vibe.descriptor);
+            *vibe = VibratorProps::new(descriptor, vibe.step_count);
         }
+        test = ui
+            .add_enabled(can_test, Button::new("Test"))
+            .on_hover_text("Short pulse of only this vibrator")
+            .clicked();
     });
+    test
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grouped_device_stays_under_max_and_ceiling() {
+        let curve = DeviceCurve {
+            gamma: 1.0,
+            multiplier: 2.0,
+            max: 0.6,
+        };
+        let mut group = DeviceGroup::new("Loud".to_owned());
+        group.multiplier = 4.0;
+        for input in [0.1, 0.3, 0.5, 1.0] {
+            let output =
+                grouped_output(input, Some(&group), |x| curve.apply(x), 1.0);
+            assert!(output <= curve.max, "{input} -> {output}");
+            let output =
+                grouped_output(input, Some(&group), |x| curve.apply(x), 0.4);
+            assert!(output <= 0.4, "{input} -> {output}");
+        }
+        // group multiplier still drives quiet input harder
+        let alone = grouped_output(0.05, None, |x| curve.apply(x), 1.0);
+        let grouped =
+            grouped_output(0.05, Some(&group), |x| curve.apply(x), 1.0);
+        assert!(grouped > alone);
+    }
+
+    #[test]
+    fn group_limits_come_after_device_curve() {
+        let curve = DeviceCurve {
+            gamma: 1.0,
+            multiplier: 1.0,
+            max: 1.0,
+        };
+        let mut group = DeviceGroup::new("Quiet".to_owned());
+        group.min = 0.2;
+        group.max = 0.5;
+        let output =
+            |x| grouped_output(x, Some(&group), |x| curve.apply(x), 1.0);
+        assert_eq!(output(0.1), 0.0);
+        assert_eq!(output(0.3), 0.3);
+        assert_eq!(output(0.9), 0.5);
+        group.is_enabled = false;
+        let output = grouped_output(0.9, Some(&group), |x| curve.apply(x), 1.0);
+        assert_eq!(output, 0.0);
+    }
+
+    fn inverted_output(level: f32, max: f32, gate: &mut Gate) -> f32 {
+        let curve = DeviceCurve {
+            gamma: 1.0,
+            multiplier: 1.0,
+            max,
+        };
+        gated_output(invert_level(level), curve, gate, None, 1.0)
+    }
+
+    #[test]
+    fn inverted_silence_is_full_output() {
+        let mut gate = Gate::default();
+        assert_eq!(inverted_output(0.0, 1.0, &mut gate), 1.0);
+        assert_eq!(inverted_output(1.0, 1.0, &mut gate), 0.0);
+    }
+
+    #[test]
+    fn gate_cuts_inverted_level() {
+        let mut gate = Gate {
+            open: 0.3,
+            close: 0.3,
+            ..Default::default()
+        };
+        // loud input is weak once inverted, so gate keeps it off
+        assert_eq!(inverted_output(0.9, 1.0, &mut gate), 0.0);
+        let output = inverted_output(0.2, 1.0, &mut gate);
+        assert!((output - 0.8).abs() < 1e-6, "{output}");
+    }
+
+    #[test]
+    fn max_clamps_inverted_level() {
+        let mut gate = Gate::default();
+        assert_eq!(inverted_output(0.0, 0.5, &mut gate), 0.5);
+        let output = inverted_output(0.8, 0.5, &mut gate);
+        assert!((output - 0.2).abs() < 1e-6, "{output}");
+    }
 }
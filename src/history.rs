@@ -0,0 +1,219 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const TIMELINE_BUCKET_DURATION: Duration = Duration::from_secs(5);
+// an hour of 5 second buckets, after that buckets get merged
+const TIMELINE_MAX_BUCKETS: usize = 720;
+// clip light stays on this long after clipping
+const CLIP_LIGHT_DURATION: Duration = Duration::from_secs(1);
+// seconds covered by clipped share
+const CLIP_HISTORY_SECS: u64 = 60;
+pub const LEVEL_HISTORY_DURATION: Duration = Duration::from_secs(30);
+// keeps level history bounded regardless of repaint rate
+const LEVEL_SAMPLE_INTERVAL: Duration = Duration::from_millis(16);
+
+#[derive(Clone, Copy, Default)]
+pub struct Bucket {
+    pub peak: f32,
+    sum: f32,
+    count: u32,
+}
+
+impl Bucket {
+    fn add(&mut self, level: f32) {
+        self.peak = self.peak.max(level);
+        self.sum += level;
+        self.count += 1;
+    }
+
+    fn merge(self, other: &Self) -> Self {
+        Self {
+            peak: self.peak.max(other.peak),
+            sum: self.sum + other.sum,
+            count: self.count + other.count,
+        }
+    }
+
+    pub fn mean(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f32
+        }
+    }
+}
+
+/// Output intensity over whole session, aggregated into buckets.
+/// Memory is bounded by doubling bucket duration once there are too many.
+pub struct IntensityTimeline {
+    start: Instant,
+    bucket_duration: Duration,
+    buckets: Vec<Bucket>,
+    /// Times of notable events, like boosts firing
+    markers: Vec<Duration>,
+}
+
+impl IntensityTimeline {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            bucket_duration: TIMELINE_BUCKET_DURATION,
+            buckets: vec![],
+            markers: vec![],
+        }
+    }
+
+    pub fn record(&mut self, level: f32) {
+        let elapsed = self.start.elapsed();
+        let mut index = self.bucket_index(elapsed);
+        while index >= TIMELINE_MAX_BUCKETS {
+            self.compact();
+            index = self.bucket_index(elapsed);
+        }
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, Bucket::default());
+        }
+        self.buckets[index].add(level);
+    }
+
+    pub fn add_marker(&mut self) {
+        self.markers.push(self.start.elapsed());
+    }
+
+    fn bucket_index(&self, elapsed: Duration) -> usize {
+        (elapsed.as_secs_f64() / self.bucket_duration.as_secs_f64()) as usize
+    }
+
+    fn compact(&mut self) {
+        self.buckets = self
+            .buckets
+            .chunks(2)
+            .map(|x| x.iter().fold(Bucket::default(), Bucket::merge))
+            .collect();
+        self.bucket_duration *= 2;
+    }
+
+    pub fn buckets(&self) -> &[Bucket] {
+        &self.buckets
+    }
+
+    pub fn bucket_start(&self, index: usize) -> Duration {
+        self.bucket_duration * index as u32
+    }
+
+    pub fn markers(&self) -> &[Duration] {
+        &self.markers
+    }
+
+    /// Time covered by all buckets
+    pub fn duration(&self) -> Duration {
+        self.bucket_start(self.buckets.len())
+    }
+}
+
+/// How often output was clamped to its maximum, over last minute
+pub struct ClipHistory {
+    start: Instant,
+    /// (second since start, clipped frames, all frames), oldest first
+    seconds: VecDeque<(u64, u32, u32)>,
+    last_clip: Option<Instant>,
+}
+
+impl ClipHistory {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            seconds: VecDeque::new(),
+            last_clip: None,
+        }
+    }
+
+    /// Call once per frame
+    pub fn record(&mut self, is_clipped: bool) {
+        let now = Instant::now();
+        if is_clipped {
+            self.last_clip = Some(now);
+        }
+        let second = (now - self.start).as_secs();
+        match self.seconds.back_mut() {
+            Some((s, clipped, total)) if *s == second => {
+                *clipped += is_clipped as u32;
+                *total += 1;
+            }
+            _ => self.seconds.push_back((second, is_clipped as u32, 1)),
+        }
+        while self
+            .seconds
+            .front()
+            .is_some_and(|(s, ..)| second - s >= CLIP_HISTORY_SECS)
+        {
+            self.seconds.pop_front();
+        }
+    }
+
+    /// Clipped within last second
+    pub fn is_lit(&self) -> bool {
+        self.last_clip
+            .is_some_and(|t| t.elapsed() < CLIP_LIGHT_DURATION)
+    }
+
+    /// 0.0 to 1.0, share of frames clipped over last minute
+    pub fn clipped_share(&self) -> f32 {
+        let (clipped, total) = self
+            .seconds
+            .iter()
+            .fold((0, 0), |(c, t), (_, clipped, total)| {
+                (c + clipped, t + total)
+            });
+        if total == 0 {
+            0.0
+        } else {
+            clipped as f32 / total as f32
+        }
+    }
+}
+
+/// Recent output and input levels, for scrolling graph
+pub struct LevelHistory {
+    /// (time, output, input), oldest first
+    samples: VecDeque<(Instant, f32, f32)>,
+}
+
+impl LevelHistory {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, output: f32, input: f32) {
+        let now = Instant::now();
+        let is_recent = self
+            .samples
+            .back()
+            .is_some_and(|(t, ..)| now - *t < LEVEL_SAMPLE_INTERVAL);
+        if is_recent {
+            return;
+        }
+        self.samples.push_back((now, output, input));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, ..)| now - *t > LEVEL_HISTORY_DURATION)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = &(Instant, f32, f32)> {
+        self.samples.iter()
+    }
+}
+
+/// Formats as `h:mm:ss`
+pub fn format_timestamp(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
@@ -0,0 +1,230 @@
+use std::sync::mpsc;
+
+use eframe::egui::{self, Key, Modifiers};
+use serde::{Deserialize, Serialize};
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, WPARAM},
+    System::Threading::GetCurrentThreadId,
+    UI::{
+        Input::KeyboardAndMouse::{
+            RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT,
+            MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT,
+        },
+        WindowsAndMessaging::{
+            GetMessageW, PeekMessageW, PostThreadMessageW, MSG, PM_NOREMOVE,
+            WM_HOTKEY, WM_QUIT, WM_USER,
+        },
+    },
+};
+
+use crate::util::{SharedBool, SharedEnum, Variants};
+
+const HOTKEY_ID: i32 = 1;
+
+// virtual key codes of letters and digits match their ASCII
+const KEYS: [(Key, u8); 36] = [
+    (Key::A, b'A'),
+    (Key::B, b'B'),
+    (Key::C, b'C'),
+    (Key::D, b'D'),
+    (Key::E, b'E'),
+    (Key::F, b'F'),
+    (Key::G, b'G'),
+    (Key::H, b'H'),
+    (Key::I, b'I'),
+    (Key::J, b'J'),
+    (Key::K, b'K'),
+    (Key::L, b'L'),
+    (Key::M, b'M'),
+    (Key::N, b'N'),
+    (Key::O, b'O'),
+    (Key::P, b'P'),
+    (Key::Q, b'Q'),
+    (Key::R, b'R'),
+    (Key::S, b'S'),
+    (Key::T, b'T'),
+    (Key::U, b'U'),
+    (Key::V, b'V'),
+    (Key::W, b'W'),
+    (Key::X, b'X'),
+    (Key::Y, b'Y'),
+    (Key::Z, b'Z'),
+    (Key::Num0, b'0'),
+    (Key::Num1, b'1'),
+    (Key::Num2, b'2'),
+    (Key::Num3, b'3'),
+    (Key::Num4, b'4'),
+    (Key::Num5, b'5'),
+    (Key::Num6, b'6'),
+    (Key::Num7, b'7'),
+    (Key::Num8, b'8'),
+    (Key::Num9, b'9'),
+];
+
+/// System-wide key combination
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Virtual key code, only letters and digits
+    pub key: u8,
+}
+
+impl Hotkey {
+    pub const CTRL_ALT_S: Self = Self {
+        ctrl: true,
+        alt: true,
+        shift: false,
+        key: b'S',
+    };
+
+    /// `None` for keys that can't be bound, or without ctrl or alt,
+    /// so plain typing isn't taken from other apps
+    pub fn from_input(key: Key, modifiers: Modifiers) -> Option<Self> {
+        if !modifiers.ctrl && !modifiers.alt {
+            return None;
+        }
+        let (_, key) = KEYS.iter().find(|(x, _)| *x == key)?;
+        Some(Self {
+            ctrl: modifiers.ctrl,
+            alt: modifiers.alt,
+            shift: modifiers.shift,
+            key: *key,
+        })
+    }
+
+    pub fn name(self) -> String {
+        let mut name = String::new();
+        if self.ctrl {
+            name += "Ctrl+";
+        }
+        if self.alt {
+            name += "Alt+";
+        }
+        if self.shift {
+            name += "Shift+";
+        }
+        name.push(char::from(self.key));
+        name
+    }
+
+    fn modifiers(self) -> HOT_KEY_MODIFIERS {
+        let mut modifiers = MOD_NOREPEAT;
+        if self.ctrl {
+            modifiers = modifiers | MOD_CONTROL;
+        }
+        if self.alt {
+            modifiers = modifiers | MOD_ALT;
+        }
+        if self.shift {
+            modifiers = modifiers | MOD_SHIFT;
+        }
+        modifiers
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyStatus {
+    /// Not registered yet, or no hotkey set
+    Unknown,
+    Registered,
+    /// Most likely taken by another app
+    Failed,
+}
+
+impl Variants for HotkeyStatus {
+    const VARIANTS: &'static [Self] =
+        &[Self::Unknown, Self::Registered, Self::Failed];
+}
+
+/// Listens for hotkey in background thread, even while window is
+/// minimized or unfocused
+pub struct HotkeyListener {
+    hotkey: Option<Hotkey>,
+    /// Hotkey belongs to this thread, `None` when it isn't running
+    thread_id: Option<u32>,
+    is_pressed: SharedBool,
+    status: SharedEnum<HotkeyStatus>,
+}
+
+impl HotkeyListener {
+    pub fn new(ctx: egui::Context, hotkey: Option<Hotkey>) -> Self {
+        let is_pressed = SharedBool::new(false);
+        let status = SharedEnum::new(HotkeyStatus::Unknown);
+        let thread_id = hotkey.and_then(|hotkey| {
+            let (tx, rx) = mpsc::channel();
+            let is_pressed = is_pressed.clone();
+            let status = status.clone();
+            std::thread::spawn(move || {
+                listen_thread(hotkey, ctx, tx, is_pressed, status)
+            });
+            rx.recv().ok()
+        });
+        Self {
+            hotkey,
+            thread_id,
+            is_pressed,
+            status,
+        }
+    }
+
+    pub fn hotkey(&self) -> Option<Hotkey> {
+        self.hotkey
+    }
+
+    pub fn status(&self) -> HotkeyStatus {
+        self.status.load()
+    }
+
+    /// Returns `true` once for each press
+    pub fn take_pressed(&self) -> bool {
+        let is_pressed = self.is_pressed.load();
+        if is_pressed {
+            self.is_pressed.store(false);
+        }
+        is_pressed
+    }
+}
+
+impl Drop for HotkeyListener {
+    fn drop(&mut self) {
+        // hotkey can only be unregistered by its own thread
+        if let Some(thread_id) = self.thread_id {
+            let _ = unsafe {
+                PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0))
+            };
+        }
+    }
+}
+
+fn listen_thread(
+    hotkey: Hotkey,
+    ctx: egui::Context,
+    thread_id: mpsc::Sender<u32>,
+    is_pressed: SharedBool,
+    status: SharedEnum<HotkeyStatus>,
+) {
+    let mut msg = MSG::default();
+    unsafe {
+        // creates message queue, so quit can be posted before first wait
+        PeekMessageW(&mut msg, HWND(0), WM_USER, WM_USER, PM_NOREMOVE);
+        let _ = thread_id.send(GetCurrentThreadId());
+        let modifiers = hotkey.modifiers();
+        let key = u32::from(hotkey.key);
+        if !RegisterHotKey(HWND(0), HOTKEY_ID, modifiers, key).as_bool() {
+            status.store(HotkeyStatus::Failed);
+            return;
+        }
+        status.store(HotkeyStatus::Registered);
+        // 0 on quit, -1 on error
+        while GetMessageW(&mut msg, HWND(0), 0, 0).0 > 0 {
+            if msg.message == WM_HOTKEY {
+                is_pressed.store(true);
+                // wakes window up, in case it idles while minimized
+                ctx.request_repaint();
+            }
+        }
+        UnregisterHotKey(HWND(0), HOTKEY_ID);
+    }
+}
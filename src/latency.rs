@@ -0,0 +1,227 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::HMODULE,
+        Media::Audio::{PlaySoundW, SND_ASYNC, SND_MEMORY, SND_NODEFAULT},
+    },
+};
+
+use crate::connection::DeviceKey;
+
+pub const TRIAL_COUNT: usize = 5;
+/// Strong enough to notice, without full power jolts
+pub const PULSE_LEVEL: f64 = 0.5;
+const PULSE_DURATION: Duration = Duration::from_millis(200);
+// trial counts as missed if click isn't captured by then
+const DETECT_TIMEOUT: Duration = Duration::from_secs(1);
+// trial counts as missed if there's no tap by then
+const TAP_TIMEOUT: Duration = Duration::from_secs(3);
+// wait between trials is randomized, so taps can't be anticipated
+const MIN_WAIT: Duration = Duration::from_millis(1500);
+const MAX_EXTRA_WAIT_MS: u32 = 2000;
+// captured level has to rise this much above level from before click
+const ONSET_RISE: f32 = 0.1;
+const CLICK_SAMPLE_RATE: u32 = 48000;
+const CLICK_FREQ: f32 = 1000.0;
+const CLICK_DURATION: Duration = Duration::from_millis(30);
+
+pub enum TestCommand {
+    PlayClick,
+    StartPulse,
+    StopPulse,
+}
+
+enum TrialState {
+    Waiting {
+        until: Instant,
+    },
+    /// Click was played, waiting for it to show up in capture
+    Listening {
+        played: Instant,
+        baseline: f32,
+    },
+    Pulsing {
+        sent: Instant,
+        capture: Duration,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct Trial {
+    /// From playing click to capture noticing it, `None` if it didn't
+    pub capture: Option<Duration>,
+    /// From sending pulse to user feeling it, `None` if missed
+    pub device: Option<Duration>,
+}
+
+/// Plays clicks, and once capture picks them up, pulses device,
+/// so capture and device latency are measured separately
+pub struct LatencyTest {
+    pub device: DeviceKey,
+    state: TrialState,
+    is_pulse_on: bool,
+    pub trials: Vec<Trial>,
+}
+
+impl LatencyTest {
+    pub fn new(device: DeviceKey) -> Self {
+        Self {
+            device,
+            state: next_wait(),
+            is_pulse_on: false,
+            trials: vec![],
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.trials.len() >= TRIAL_COUNT
+    }
+
+    /// Advances test with latest captured `level`,
+    /// returning what should be done next, if anything
+    pub fn update(&mut self, level: f32) -> Option<TestCommand> {
+        if self.is_finished() {
+            return self.stop_pulse();
+        }
+        match self.state {
+            TrialState::Waiting { until } if Instant::now() >= until => {
+                self.state = TrialState::Listening {
+                    played: Instant::now(),
+                    baseline: level,
+                };
+                Some(TestCommand::PlayClick)
+            }
+            TrialState::Waiting { .. } => self.stop_pulse(),
+            TrialState::Listening { played, baseline } => {
+                if level >= baseline + ONSET_RISE {
+                    self.state = TrialState::Pulsing {
+                        sent: Instant::now(),
+                        capture: played.elapsed(),
+                    };
+                    self.is_pulse_on = true;
+                    Some(TestCommand::StartPulse)
+                } else {
+                    if played.elapsed() >= DETECT_TIMEOUT {
+                        self.trials.push(Trial {
+                            capture: None,
+                            device: None,
+                        });
+                        self.state = next_wait();
+                    }
+                    None
+                }
+            }
+            TrialState::Pulsing { sent, capture } => {
+                if sent.elapsed() >= TAP_TIMEOUT {
+                    self.trials.push(Trial {
+                        capture: Some(capture),
+                        device: None,
+                    });
+                    self.state = next_wait();
+                }
+                if sent.elapsed() >= PULSE_DURATION {
+                    self.stop_pulse()
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn stop_pulse(&mut self) -> Option<TestCommand> {
+        if self.is_pulse_on {
+            self.is_pulse_on = false;
+            Some(TestCommand::StopPulse)
+        } else {
+            None
+        }
+    }
+
+    /// User felt the pulse. Taps outside of pulses are ignored.
+    pub fn tap(&mut self) {
+        if let TrialState::Pulsing { sent, capture } = self.state {
+            self.trials.push(Trial {
+                capture: Some(capture),
+                device: Some(sent.elapsed()),
+            });
+            self.state = next_wait();
+        }
+    }
+
+    /// Average over trials where click was captured
+    pub fn capture_average(&self) -> Option<Duration> {
+        average(self.trials.iter().filter_map(|x| x.capture))
+    }
+
+    /// Average over trials that weren't missed
+    pub fn device_average(&self) -> Option<Duration> {
+        average(self.trials.iter().filter_map(|x| x.device))
+    }
+}
+
+fn average(durations: impl Iterator<Item = Duration>) -> Option<Duration> {
+    let hits: Vec<_> = durations.collect();
+    if hits.is_empty() {
+        return None;
+    }
+    Some(hits.iter().sum::<Duration>() / hits.len() as u32)
+}
+
+fn next_wait() -> TrialState {
+    // good enough randomness, only needs to be unpredictable to a human
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let extra = Duration::from_millis((nanos % MAX_EXTRA_WAIT_MS) as u64);
+    TrialState::Waiting {
+        until: Instant::now() + MIN_WAIT + extra,
+    }
+}
+
+/// Plays short tone through default output device, without waiting for it
+pub fn play_click() {
+    // has to outlive asynchronous playback
+    static CLICK: OnceLock<Vec<u8>> = OnceLock::new();
+    let click = CLICK.get_or_init(click_wav);
+    unsafe {
+        PlaySoundW(
+            PCWSTR(click.as_ptr().cast()),
+            HMODULE(0),
+            SND_MEMORY | SND_ASYNC | SND_NODEFAULT,
+        );
+    }
+}
+
+/// Mono 16-bit WAV file, with a sine burst fading out
+fn click_wav() -> Vec<u8> {
+    let sample_count =
+        (CLICK_SAMPLE_RATE as f32 * CLICK_DURATION.as_secs_f32()) as u32;
+    let data_len = sample_count * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&CLICK_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(CLICK_SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for i in 0..sample_count {
+        let t = i as f32 / CLICK_SAMPLE_RATE as f32;
+        let fade = 1.0 - i as f32 / sample_count as f32;
+        let x = (std::f32::consts::TAU * CLICK_FREQ * t).sin() * fade * 0.8;
+        wav.extend_from_slice(&((x * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    wav
+}
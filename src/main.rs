@@ -1,9 +1,18 @@
 // Stops console from showing, but also stops stdout and stderr
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod auto_setup;
+mod capture;
+mod connection;
 mod gui;
+mod history;
+mod hotkey;
+mod latency;
+mod radio;
 mod settings;
+mod throttle;
 mod util;
+mod wasapi;
 
 use clap::Parser;
 use gui::Gui;
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use windows::{
+    Devices::Radios::{Radio, RadioKind, RadioState},
+    Win32::System::WinRT::{RoInitialize, RO_INIT_MULTITHREADED},
+};
+
+use crate::util::{SharedEnum, Variants};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RadioStatus {
+    /// Not checked yet
+    Unknown,
+    /// No Bluetooth adapter, or it couldn't be queried
+    Missing,
+    Off,
+    On,
+}
+
+impl Variants for RadioStatus {
+    const VARIANTS: &'static [Self] =
+        &[Self::Unknown, Self::Missing, Self::Off, Self::On];
+}
+
+/// Checks Bluetooth radio state in background thread
+pub struct BluetoothWatch {
+    status: SharedEnum<RadioStatus>,
+    last_status: RadioStatus,
+}
+
+impl BluetoothWatch {
+    pub fn new() -> Self {
+        let status = SharedEnum::new(RadioStatus::Unknown);
+        {
+            let status = status.clone();
+            std::thread::spawn(move || watch_thread(status));
+        }
+        Self {
+            status,
+            last_status: RadioStatus::Unknown,
+        }
+    }
+
+    pub fn status(&self) -> RadioStatus {
+        self.last_status
+    }
+
+    /// Returns `true` if radio was just turned on
+    pub fn poll(&mut self) -> bool {
+        let status = self.status.load();
+        let was_off = self.last_status == RadioStatus::Off;
+        self.last_status = status;
+        was_off && status == RadioStatus::On
+    }
+}
+
+fn watch_thread(status: SharedEnum<RadioStatus>) {
+    // already initialized is fine too
+    let _ = unsafe { RoInitialize(RO_INIT_MULTITHREADED) };
+    loop {
+        status.store(bluetooth_status().unwrap_or(RadioStatus::Missing));
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn bluetooth_status() -> windows::core::Result<RadioStatus> {
+    let radios = Radio::GetRadiosAsync()?.get()?;
+    for radio in radios {
+        if radio.Kind()? == RadioKind::Bluetooth {
+            return Ok(match radio.State()? {
+                RadioState::On => RadioStatus::On,
+                RadioState::Unknown => RadioStatus::Missing,
+                _ => RadioStatus::Off,
+            });
+        }
+    }
+    Ok(RadioStatus::Missing)
+}
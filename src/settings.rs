@@ -1,66 +1,2065 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use eframe::{get_value, set_value, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hotkey::Hotkey,
+    util::{
+        drop_boost::DropBoostParams,
+        dynamics::{CompressorParams, DuckParams, DynamicsMode},
+        envelope::EnvelopeParams,
+        pattern::{PatternParams, PatternShape},
+        spectrum::{Band, BandWeights},
+        tempo::BeatPulse,
+        Channel, DetectionMode, Downmix, FilterSlope, Gate, SharedBool,
+        SharedEnum, SharedF32, SharedString, SharedWeights,
+    },
+};
 
-use crate::util::SharedF32;
+// older snapshots are dropped
+const MAX_SNAPSHOTS: usize = 10;
+// output factor at high end of pitch range, so high notes still register
+pub const MIN_PITCH_FACTOR: f32 = 0.25;
 
 // TODO: Add derive macro
 pub struct Settings {
     pub main_volume: f32,
-    pub low_pass_freq: SharedF32,
+    /// Levels drive devices this much later, to line up with delayed audio
+    pub level_delay_ms: f32,
+    /// Dominant frequencies mapped to full and weakest output of devices
+    /// following pitch
+    pub pitch_low_hz: f32,
+    pub pitch_high_hz: f32,
+    /// Time constant of moving average over sound power, 0 disables it
+    pub smoothing_ms: f32,
+    /// Ramp up time of sound power, 0 follows it immediately
+    pub attack_ms: f32,
+    /// How long peaks are held
+    pub hold_ms: f32,
+    /// Decay time after hold, 0 drops immediately
+    pub decay_ms: f32,
+    /// Compressor or expander on main level, ratio of 1 bypasses it
+    pub dynamics_mode: DynamicsMode,
+    pub compressor_threshold: f32,
+    pub compressor_ratio: f32,
+    /// Shared by compressor and expander
+    pub compressor_release_ms: f32,
+    pub expander_threshold: f32,
+    pub expander_ratio: f32,
+    /// Boost after sustained rise in loudness
+    pub use_drop_boost: bool,
+    pub drop_sensitivity: f32,
+    pub drop_boost: f32,
+    /// Seconds
+    pub drop_boost_duration: f32,
+    /// Largest random change of level, relative, 0 disables it
+    pub jitter_amount: f32,
+    /// New random targets per second
+    pub jitter_rate_hz: f32,
+    /// Share of generated pattern in level, 0 is audio only
+    pub pattern_blend: f32,
+    pub pattern_shape: PatternShape,
+    /// In Hz
+    pub pattern_rate: f32,
+    pub pattern_depth: f32,
+    /// Pulses level in time with detected beat
+    pub beat_pulse: BeatPulse,
+    /// Mic level above which output is ducked
+    pub duck_threshold: f32,
+    /// Share of output taken away while ducked
+    pub duck_amount: f32,
+    pub duck_release_ms: f32,
+    /// Divide level by recent maximum
+    pub use_normalize: bool,
+    /// Seconds, how long maximum is kept before decaying
+    pub normalize_window: f32,
+    /// Stop devices while audio stays silent
+    pub use_silence_stop: bool,
+    /// Seconds of silence before stopping
+    pub silence_timeout: f32,
+    pub capture: CaptureSettings,
     pub use_dark_mode: bool,
+    pub display_rate: f32,
     pub start_scanning_on_startup: bool,
+    /// Stop all button also closes server connections
+    pub stop_all_disconnects: bool,
+    /// Devices that drop out while enabled get enabled again,
+    /// if they come back soon
+    pub restore_enabled_on_reconnect: bool,
+    /// Fade out before disabling a device, 0 stops it right away
+    pub fade_out_ms: f32,
+    /// Stop all fades out too, instead of stopping right away
+    pub stop_all_fades: bool,
+    /// Works like stop all button, even while window is unfocused
+    pub stop_hotkey: Option<Hotkey>,
+    pub slider_preview: SliderPreview,
+    /// Output limit while dragging, in `SliderPreview::Limit` mode
+    pub preview_ceiling: f32,
+    pub throttle_policy: ThrottlePolicy,
+    /// Restore values of devices seen before, when they connect
+    pub remember_device_settings: bool,
+    /// Which values are remembered
+    pub device_persistence: DevicePersistence,
+    pub end_session: EndSessionSettings,
+    pub low_battery: LowBatterySettings,
+    pub battery_polling: BatteryPolling,
+    pub user_device_config_path: String,
+    pub watch_user_device_config: bool,
+    /// Additional servers, connected to alongside the default one
+    pub connections: Vec<ConnectionSettings>,
+    /// Stable slot numbers, from slot to device identifier
+    pub device_slots: BTreeMap<u32, String>,
+    /// Device identifiers that are neither shown nor controlled
+    pub hidden_devices: BTreeSet<String>,
+    /// Quick presets shown on each device
+    pub presets: Vec<DevicePreset>,
+    pub groups: Vec<DeviceGroup>,
+    /// Oldest first
+    pub snapshots: Vec<Snapshot>,
+    /// Remembered device values, by device identifier
+    pub saved_devices: BTreeMap<String, DeviceSnapshot>,
+}
+
+/// Settings shared with capture thread
+#[derive(Clone)]
+pub struct CaptureSettings {
+    /// Id of output device to capture, empty for default device
+    pub audio_device: SharedString,
+    /// Executable name of app to capture instead of device, empty for none
+    pub capture_app: SharedString,
+    /// Applied to samples from input devices
+    pub input_gain: SharedF32,
+    pub low_pass_freq: SharedF32,
+    /// 0 disables the filter
+    pub high_pass_freq: SharedF32,
+    /// Use `band_low..=band_high` range instead of low and high pass cutoffs
+    pub use_band_pass: SharedBool,
+    pub band_low: SharedF32,
+    pub band_high: SharedF32,
+    /// Perceptual weighting, so inaudible rumble doesn't dominate
+    pub use_a_weighting: SharedBool,
+    /// Analyze difference of stereo channels, removing center-panned
+    /// sounds like vocals. No effect on mono sources.
+    pub use_side: SharedBool,
+    /// Lower output while talking into default voice chat mic
+    pub use_mic_ducking: SharedBool,
+    /// Force levels to zero while captured device or app is muted
+    pub pause_when_muted: SharedBool,
+    /// Output follows highest level since last frame, instead of latest
+    pub peak_since_frame: SharedBool,
+    /// Removes narrow band around `notch_freq`, like constant hum
+    pub use_notch: SharedBool,
+    pub notch_freq: SharedF32,
+    /// Higher is narrower
+    pub notch_q: SharedF32,
+    /// Boost of low frequencies relative to 1 kHz, in dB, 0 is flat
+    pub bass_emphasis: SharedF32,
+    /// Steepness of low pass, also applies to band pass upper edge
+    pub filter_slope: SharedEnum<FilterSlope>,
+    /// Show levels before and after filters, costs extra analysis
+    pub show_filter_meters: SharedBool,
+    /// Band driving main volume
+    pub power_band: SharedEnum<Band>,
+    pub detection_mode: SharedEnum<DetectionMode>,
+    /// Used for sources with more than 2 channels
+    pub downmix: SharedEnum<Downmix>,
+    /// Used in custom downmix
+    pub downmix_weights: SharedWeights,
+    /// 0..=1, used in onset mode
+    pub onset_sensitivity: SharedF32,
+    pub analysis_window_ms: SharedF32,
+    /// Number of analysis windows, used in peak-hold mode
+    pub peak_hold_windows: SharedF32,
+    pub use_agc: SharedBool,
+    pub agc_target: SharedF32,
+    /// In seconds
+    pub agc_adaptation_time: SharedF32,
+    /// Gain is frozen below this level
+    pub agc_floor: SharedF32,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            audio_device: SharedString::default(),
+            capture_app: SharedString::default(),
+            input_gain: SharedF32::new(defaults::INPUT_GAIN),
+            low_pass_freq: SharedF32::new(defaults::LOW_PASS_FREQ),
+            high_pass_freq: SharedF32::new(defaults::HIGH_PASS_FREQ),
+            use_band_pass: SharedBool::new(defaults::USE_BAND_PASS),
+            band_low: SharedF32::new(defaults::BAND_LOW),
+            band_high: SharedF32::new(defaults::BAND_HIGH),
+            use_a_weighting: SharedBool::new(defaults::USE_A_WEIGHTING),
+            use_side: SharedBool::new(defaults::USE_SIDE),
+            use_mic_ducking: SharedBool::new(defaults::USE_MIC_DUCKING),
+            pause_when_muted: SharedBool::new(defaults::PAUSE_WHEN_MUTED),
+            peak_since_frame: SharedBool::new(defaults::PEAK_SINCE_FRAME),
+            use_notch: SharedBool::new(defaults::USE_NOTCH),
+            notch_freq: SharedF32::new(defaults::NOTCH_FREQ),
+            notch_q: SharedF32::new(defaults::NOTCH_Q),
+            bass_emphasis: SharedF32::new(defaults::BASS_EMPHASIS),
+            filter_slope: SharedEnum::new(defaults::FILTER_SLOPE),
+            show_filter_meters: SharedBool::new(defaults::SHOW_FILTER_METERS),
+            power_band: SharedEnum::new(defaults::POWER_BAND),
+            detection_mode: SharedEnum::new(defaults::DETECTION_MODE),
+            downmix: SharedEnum::new(defaults::DOWNMIX),
+            downmix_weights: SharedWeights::default(),
+            onset_sensitivity: SharedF32::new(defaults::ONSET_SENSITIVITY),
+            analysis_window_ms: SharedF32::new(defaults::ANALYSIS_WINDOW_MS),
+            peak_hold_windows: SharedF32::new(defaults::PEAK_HOLD_WINDOWS),
+            use_agc: SharedBool::new(defaults::USE_AGC),
+            agc_target: SharedF32::new(defaults::AGC_TARGET),
+            agc_adaptation_time: SharedF32::new(defaults::AGC_ADAPTATION_TIME),
+            agc_floor: SharedF32::new(defaults::AGC_FLOOR),
+        }
+    }
+}
+
+impl CaptureSettings {
+    fn store_from(&self, other: &Self) {
+        self.audio_device.store(other.audio_device.load());
+        self.capture_app.store(other.capture_app.load());
+        self.input_gain.store(other.input_gain.load());
+        self.low_pass_freq.store(other.low_pass_freq.load());
+        self.high_pass_freq.store(other.high_pass_freq.load());
+        self.use_band_pass.store(other.use_band_pass.load());
+        self.band_low.store(other.band_low.load());
+        self.band_high.store(other.band_high.load());
+        self.use_a_weighting.store(other.use_a_weighting.load());
+        self.use_side.store(other.use_side.load());
+        self.use_mic_ducking.store(other.use_mic_ducking.load());
+        self.pause_when_muted.store(other.pause_when_muted.load());
+        self.peak_since_frame.store(other.peak_since_frame.load());
+        self.use_notch.store(other.use_notch.load());
+        self.notch_freq.store(other.notch_freq.load());
+        self.notch_q.store(other.notch_q.load());
+        self.bass_emphasis.store(other.bass_emphasis.load());
+        self.filter_slope.store(other.filter_slope.load());
+        self.show_filter_meters
+            .store(other.show_filter_meters.load());
+        self.power_band.store(other.power_band.load());
+        self.detection_mode.store(other.detection_mode.load());
+        self.downmix.store(other.downmix.load());
+        self.downmix_weights
+            .store_all(other.downmix_weights.load_all());
+        self.onset_sensitivity.store(other.onset_sensitivity.load());
+        self.analysis_window_ms
+            .store(other.analysis_window_ms.load());
+        self.peak_hold_windows.store(other.peak_hold_windows.load());
+        self.use_agc.store(other.use_agc.load());
+        self.agc_target.store(other.agc_target.load());
+        self.agc_adaptation_time
+            .store(other.agc_adaptation_time.load());
+        self.agc_floor.store(other.agc_floor.load());
+    }
+}
+
+/// What happens to output while dragging sliders that affect it
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SliderPreview {
+    /// Output follows slider
+    Off,
+    /// Keep sending what was sent before dragging
+    Freeze,
+    /// Clamp output to preview ceiling
+    Limit,
+}
+
+impl SliderPreview {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Freeze, Self::Limit];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Freeze => "Freeze",
+            Self::Limit => "Limit",
+        }
+    }
+}
+
+/// What happens when device takes longer to handle commands,
+/// than it takes to send them
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThrottlePolicy {
+    /// Send commands to that device less often, until it speeds up
+    Auto,
+    /// Only suggest sending less often
+    Suggest,
+}
+
+impl ThrottlePolicy {
+    pub const ALL: [Self; 2] = [Self::Auto, Self::Suggest];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "Auto-throttle",
+            Self::Suggest => "Suggest only",
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionSettings {
+    pub name: String,
+    pub address: String,
+}
+
+/// Combination of device sliders, applied with a single click.
+/// Values are relative to device calibration, if it has one.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct DevicePreset {
+    pub name: String,
+    /// Scales calibrated multiplier
+    pub multiplier: f32,
+    /// 0 is calibrated minimum, 1 is full output
+    pub min: f32,
+    /// 0 is calibrated minimum, 1 is full output
+    pub max: f32,
+    /// Missing in presets from before curve was included
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+}
+
+fn default_gamma() -> f32 {
+    1.0
+}
+
+impl DevicePreset {
+    fn new(
+        name: &str,
+        multiplier: f32,
+        min: f32,
+        max: f32,
+        gamma: f32,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            multiplier,
+            min,
+            max,
+            gamma,
+        }
+    }
+
+    /// Absolute slider values for device with `calibration`
+    pub fn scaled(&self, calibration: Option<DeviceCalibration>) -> Self {
+        let calibration = calibration.unwrap_or_default();
+        let floor = calibration.min.clamp(0.0, 1.0);
+        let to_range = |x: f32| floor + x.clamp(0.0, 1.0) * (1.0 - floor);
+        Self {
+            name: self.name.clone(),
+            multiplier: self.multiplier * calibration.multiplier,
+            min: to_range(self.min),
+            max: to_range(self.max),
+            gamma: self.gamma,
+        }
+    }
+}
+
+/// Values found by level calibration, presets are scaled from them
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DeviceCalibration {
+    pub multiplier: f32,
+    pub min: f32,
+}
+
+impl Default for DeviceCalibration {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            min: 0.0,
+        }
+    }
+}
+
+/// Named set of devices with shared controls. Members are device identifiers,
+/// ones that aren't connected are kept until removed
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceGroup {
+    pub name: String,
+    /// Disabled group keeps its members at 0
+    pub is_enabled: bool,
+    pub multiplier: f32,
+    pub min: f32,
+    pub max: f32,
+    pub members: Vec<String>,
+}
+
+impl Default for DeviceGroup {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
+}
+
+impl DeviceGroup {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            is_enabled: true,
+            multiplier: 1.0,
+            min: 0.0,
+            max: 1.0,
+            members: vec![],
+        }
+    }
+
+    /// Group multiplier on level going into member, so member curve and
+    /// max still hold
+    pub fn scale_input(&self, level: f32) -> f32 {
+        if self.is_enabled {
+            level * self.multiplier
+        } else {
+            0.0
+        }
+    }
+
+    /// Group min and max on member output
+    pub fn limit(&self, output: f32) -> f32 {
+        if !self.is_enabled {
+            return 0.0;
+        }
+        let output = output.min(self.max);
+        if output < self.min {
+            0.0
+        } else {
+            output
+        }
+    }
+
+    /// Moves device to group at `index`, or out of all groups for `None`
+    pub fn assign(groups: &mut [Self], identifier: &str, index: Option<usize>) {
+        for group in groups.iter_mut() {
+            group.members.retain(|x| x != identifier);
+        }
+        if let Some(group) = index.and_then(|i| groups.get_mut(i)) {
+            group.members.push(identifier.to_owned());
+        }
+    }
+
+    pub fn connected_count(&self, connected_devices: &[String]) -> usize {
+        self.members
+            .iter()
+            .filter(|x| connected_devices.contains(x))
+            .count()
+    }
+}
+
+/// Device values kept by "Remember device settings"
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevicePersistence {
+    pub multiplier: bool,
+    pub min: bool,
+    pub max: bool,
+    /// Response curve exponent
+    pub curve: bool,
+    /// Band, band weights, channel, low pass override, pitch mode,
+    /// transient blend, inversion, width depth, output delay and manual mode
+    pub routing: bool,
+    /// Vibrator, alternation, rotator, stroke and other actuator values
+    pub vibrators: bool,
+}
+
+impl Default for DevicePersistence {
+    fn default() -> Self {
+        Self {
+            multiplier: true,
+            min: true,
+            max: true,
+            curve: true,
+            routing: true,
+            vibrators: true,
+        }
+    }
+}
+
+impl DevicePersistence {
+    /// Copies values included in mask from `from` into `to`
+    pub fn apply(&self, from: &DeviceSnapshot, to: &mut DeviceSnapshot) {
+        if self.multiplier {
+            to.multiplier = from.multiplier;
+            to.calibration = from.calibration;
+        }
+        if self.min {
+            to.gate.open = from.gate.open;
+            to.gate.close = from.gate.close;
+            to.gate.hold = from.gate.hold;
+            to.gate.knee = from.gate.knee;
+        }
+        if self.max {
+            to.max = from.max;
+        }
+        if self.curve {
+            to.gamma = from.gamma;
+        }
+        if self.routing {
+            to.band = from.band;
+            to.channel = from.channel;
+            to.low_pass_override = from.low_pass_override;
+            to.band_weights = from.band_weights;
+            to.use_pitch = from.use_pitch;
+            to.transient_blend = from.transient_blend;
+            to.is_inverted = from.is_inverted;
+            to.width_depth = from.width_depth;
+            to.delay_ms = from.delay_ms;
+            to.is_manual = from.is_manual;
+            to.manual_level = from.manual_level;
+        }
+        if self.vibrators {
+            to.vibrators = from.vibrators.clone();
+            to.rotators = from.rotators.clone();
+            to.stroke = from.stroke;
+            to.alternate = from.alternate;
+            to.scalars = from.scalars.clone();
+        }
+    }
+}
+
+/// Settings and device values at one moment, taken with one click
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Seconds since Unix epoch
+    pub taken_at: u64,
+    pub main_volume: f32,
+    /// Settings, in same format as in app storage
+    values: BTreeMap<String, String>,
+    pub devices: Vec<DeviceSnapshot>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub identifier: String,
+    /// Shown instead of device name, `None` shows the name
+    #[serde(default)]
+    pub alias: Option<String>,
+    pub is_enabled: bool,
+    pub band: Band,
+    pub channel: Channel,
+    pub gamma: f32,
+    pub multiplier: f32,
+    pub gate: Gate,
+    pub max: f32,
+    /// Missing in snapshots from before pitch mode
+    #[serde(default)]
+    pub use_pitch: bool,
+    /// 0 follows sustained level, 1 only sudden rises
+    #[serde(default)]
+    pub transient_blend: f32,
+    /// Quiet parts drive device stronger, loud ones weaker
+    #[serde(default)]
+    pub is_inverted: bool,
+    /// How much stereo width boosts output, 0 ignores it
+    #[serde(default)]
+    pub width_depth: f32,
+    /// Lines up device with slower ones, 0 disables it
+    #[serde(default)]
+    pub delay_ms: f32,
+    /// Output follows `manual_level` instead of audio
+    #[serde(default)]
+    pub is_manual: bool,
+    #[serde(default)]
+    pub manual_level: f32,
+    /// Low pass cutoff in Hz, `None` follows global filters
+    #[serde(default)]
+    pub low_pass_override: Option<f32>,
+    /// `None` follows single band
+    #[serde(default)]
+    pub band_weights: Option<BandWeights>,
+    pub vibrators: Vec<VibratorSnapshot>,
+    /// Missing in snapshots from before rotation support
+    #[serde(default)]
+    pub rotators: Vec<RotatorSnapshot>,
+    /// Only for linear devices
+    #[serde(default)]
+    pub stroke: Option<StrokeSnapshot>,
+    #[serde(default)]
+    pub alternate: AlternateSnapshot,
+    /// Scalar actuators other than vibrators
+    #[serde(default)]
+    pub scalars: Vec<ScalarSnapshot>,
+    /// Missing until level calibration was applied
+    #[serde(default)]
+    pub calibration: Option<DeviceCalibration>,
+}
+
+impl DeviceSnapshot {
+    /// Values differing in `other`, as shown before importing it
+    pub fn changes(&self, other: &Self) -> Vec<ValueChange> {
+        let mut changes = vec![];
+        let mut compare = |name, current: String, imported: String| {
+            if current != imported {
+                changes.push(ValueChange {
+                    name,
+                    current,
+                    imported,
+                });
+            }
+        };
+        let percent = |x: f32| format!("{:.0}%", x * 100.0);
+        let motors = |x: &Self| {
+            let multipliers: Vec<_> = x
+                .vibrators
+                .iter()
+                .map(|x| format!("{:.2}", x.multiplier))
+                .collect();
+            multipliers.join(", ")
+        };
+        compare(
+            "Multiplier",
+            format!("{:.2}", self.multiplier),
+            format!("{:.2}", other.multiplier),
+        );
+        compare("Min", percent(self.gate.open), percent(other.gate.open));
+        compare("Max", percent(self.max), percent(other.max));
+        compare(
+            "Curve",
+            format!("{:.2}", self.gamma),
+            format!("{:.2}", other.gamma),
+        );
+        compare("Band", self.band.name().into(), other.band.name().into());
+        compare(
+            "Channel",
+            self.channel.name().into(),
+            other.channel.name().into(),
+        );
+        compare(
+            "Inverted",
+            self.is_inverted.to_string(),
+            other.is_inverted.to_string(),
+        );
+        compare(
+            "Delay",
+            format!("{:.0} ms", self.delay_ms),
+            format!("{:.0} ms", other.delay_ms),
+        );
+        compare("Motor multipliers", motors(self), motors(other));
+        changes
+    }
+}
+
+/// How device from imported settings relates to this session
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportStatus {
+    Connected,
+    /// Remembered, but not connected now
+    Offline,
+    /// Never seen here, values wait until it connects
+    Unknown,
+}
+
+impl ImportStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Connected => "Connected",
+            Self::Offline => "Offline",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ValueChange {
+    pub name: &'static str,
+    pub current: String,
+    pub imported: String,
+}
+
+/// Remembered device from imported settings, written only if accepted
+pub struct DeviceImport {
+    pub snapshot: DeviceSnapshot,
+    pub status: ImportStatus,
+    /// Always empty for unknown devices
+    pub changes: Vec<ValueChange>,
+    pub accept: bool,
+}
+
+impl DeviceImport {
+    /// Compares `imported` devices with `connected` and `saved` ones.
+    /// Known devices are accepted by default, unknown ones skipped.
+    pub fn reconcile(
+        imported: &BTreeMap<String, DeviceSnapshot>,
+        saved: &BTreeMap<String, DeviceSnapshot>,
+        connected: &[DeviceSnapshot],
+    ) -> Vec<Self> {
+        imported
+            .values()
+            .map(|snapshot| {
+                let live = connected
+                    .iter()
+                    .find(|x| x.identifier == snapshot.identifier);
+                let (status, current) =
+                    match (live, saved.get(&snapshot.identifier)) {
+                        (Some(x), _) => (ImportStatus::Connected, Some(x)),
+                        (None, Some(x)) => (ImportStatus::Offline, Some(x)),
+                        (None, None) => (ImportStatus::Unknown, None),
+                    };
+                Self {
+                    snapshot: snapshot.clone(),
+                    status,
+                    changes: current
+                        .map_or_else(Vec::new, |x| x.changes(snapshot)),
+                    accept: current.is_some(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VibratorSnapshot {
+    pub is_enabled: bool,
+    pub multiplier: f32,
+    pub gate: Gate,
+    pub max: f32,
+    /// `None` follows device band
+    #[serde(default)]
+    pub band: Option<Band>,
+    /// `None` follows device channel
+    #[serde(default)]
+    pub channel: Option<Channel>,
+}
+
+/// Matched to device features by both index and actuator type
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScalarSnapshot {
+    /// Among scalar features of device
+    pub index: u32,
+    /// Actuator type name, like "Constrict"
+    pub actuator: String,
+    pub is_enabled: bool,
+    pub multiplier: f32,
+    pub gate: Gate,
+    pub max: f32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct StrokeSnapshot {
+    /// Milliseconds per stroke at full level
+    pub min_duration: f32,
+    /// Milliseconds per stroke at lowest level
+    pub max_duration: f32,
+    /// Quiet parts make strokes shorter, not only slower
+    pub use_depth: bool,
+}
+
+/// Motors of device taking turns
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct AlternateSnapshot {
+    /// In Hz
+    pub rate: f32,
+    /// 0 keeps motors in unison
+    pub depth: f32,
+}
+
+impl Default for AlternateSnapshot {
+    fn default() -> Self {
+        Self {
+            rate: 0.5,
+            depth: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RotatorSnapshot {
+    pub is_enabled: bool,
+    pub multiplier: f32,
+    pub gate: Gate,
+    pub max: f32,
+    pub clockwise: bool,
+}
+
+/// Settings storage in memory, for snapshots
+#[derive(Default)]
+struct ValueStorage(BTreeMap<String, String>);
+
+impl Storage for ValueStorage {
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.0.get(key).cloned()
+    }
+
+    fn set_string(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_owned(), value);
+    }
+
+    fn flush(&mut self) {}
+}
+
+pub struct EndSessionSettings {
+    pub duration: f32,
+    pub pulse_count: u32,
+    pub pulse_strength: f32,
+    pub exit_after: bool,
+}
+
+impl Default for EndSessionSettings {
+    fn default() -> Self {
+        Self {
+            duration: defaults::END_SESSION_DURATION,
+            pulse_count: defaults::END_SESSION_PULSE_COUNT,
+            pulse_strength: defaults::END_SESSION_PULSE_STRENGTH,
+            exit_after: defaults::END_SESSION_EXIT_AFTER,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct LowBatterySettings {
+    /// Battery level from 0 to 1, below which device counts as low
+    pub threshold: f32,
+    /// Pop up a warning when device gets low
+    pub notify: bool,
+    /// Limit output of low devices to `ceiling`
+    pub derate: bool,
+    pub ceiling: f32,
+}
+
+impl Default for LowBatterySettings {
+    fn default() -> Self {
+        Self {
+            threshold: defaults::LOW_BATTERY_THRESHOLD,
+            notify: defaults::LOW_BATTERY_NOTIFY,
+            derate: defaults::LOW_BATTERY_DERATE,
+            ceiling: defaults::LOW_BATTERY_CEILING,
+        }
+    }
+}
+
+/// Settings shared with battery tasks of devices
+#[derive(Clone)]
+pub struct BatteryPolling {
+    /// Some devices stall when asked for battery level
+    pub is_enabled: SharedBool,
+    /// Seconds between checks
+    pub interval: SharedF32,
+}
+
+impl Default for BatteryPolling {
+    fn default() -> Self {
+        Self {
+            is_enabled: SharedBool::new(defaults::POLL_BATTERY),
+            interval: SharedF32::new(defaults::BATTERY_INTERVAL),
+        }
+    }
+}
+
+impl BatteryPolling {
+    fn store_from(&self, other: &Self) {
+        self.is_enabled.store(other.is_enabled.load());
+        self.interval.store(other.interval.load());
+    }
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             main_volume: defaults::MAIN_VOLUME,
-            low_pass_freq: SharedF32::new(defaults::LOW_PASS_FREQ),
+            level_delay_ms: defaults::LEVEL_DELAY_MS,
+            pitch_low_hz: defaults::PITCH_LOW_HZ,
+            pitch_high_hz: defaults::PITCH_HIGH_HZ,
+            smoothing_ms: defaults::SMOOTHING_MS,
+            attack_ms: defaults::ATTACK_MS,
+            hold_ms: defaults::HOLD_MS,
+            decay_ms: defaults::DECAY_MS,
+            dynamics_mode: defaults::DYNAMICS_MODE,
+            compressor_threshold: defaults::COMPRESSOR_THRESHOLD,
+            compressor_ratio: defaults::COMPRESSOR_RATIO,
+            compressor_release_ms: defaults::COMPRESSOR_RELEASE_MS,
+            expander_threshold: defaults::EXPANDER_THRESHOLD,
+            expander_ratio: defaults::EXPANDER_RATIO,
+            use_drop_boost: defaults::USE_DROP_BOOST,
+            drop_sensitivity: defaults::DROP_SENSITIVITY,
+            drop_boost: defaults::DROP_BOOST,
+            drop_boost_duration: defaults::DROP_BOOST_DURATION,
+            jitter_amount: defaults::JITTER_AMOUNT,
+            jitter_rate_hz: defaults::JITTER_RATE_HZ,
+            pattern_blend: defaults::PATTERN_BLEND,
+            pattern_shape: defaults::PATTERN_SHAPE,
+            pattern_rate: defaults::PATTERN_RATE,
+            pattern_depth: defaults::PATTERN_DEPTH,
+            beat_pulse: defaults::BEAT_PULSE,
+            duck_threshold: defaults::DUCK_THRESHOLD,
+            duck_amount: defaults::DUCK_AMOUNT,
+            duck_release_ms: defaults::DUCK_RELEASE_MS,
+            use_normalize: defaults::USE_NORMALIZE,
+            normalize_window: defaults::NORMALIZE_WINDOW,
+            use_silence_stop: defaults::USE_SILENCE_STOP,
+            silence_timeout: defaults::SILENCE_TIMEOUT,
+            capture: Default::default(),
             use_dark_mode: defaults::DARK_MODE,
+            display_rate: defaults::DISPLAY_RATE,
             start_scanning_on_startup: defaults::START_SCANNING_ON_STARTUP,
+            stop_all_disconnects: defaults::STOP_ALL_DISCONNECTS,
+            restore_enabled_on_reconnect:
+                defaults::RESTORE_ENABLED_ON_RECONNECT,
+            fade_out_ms: defaults::FADE_OUT_MS,
+            stop_all_fades: defaults::STOP_ALL_FADES,
+            stop_hotkey: defaults::STOP_HOTKEY,
+            slider_preview: defaults::SLIDER_PREVIEW,
+            throttle_policy: defaults::THROTTLE_POLICY,
+            remember_device_settings: defaults::REMEMBER_DEVICE_SETTINGS,
+            device_persistence: DevicePersistence::default(),
+            preview_ceiling: defaults::PREVIEW_CEILING,
+            end_session: Default::default(),
+            low_battery: Default::default(),
+            battery_polling: Default::default(),
+            user_device_config_path: String::new(),
+            watch_user_device_config: defaults::WATCH_USER_DEVICE_CONFIG,
+            connections: vec![],
+            device_slots: BTreeMap::new(),
+            hidden_devices: BTreeSet::new(),
+            presets: defaults::presets(),
+            groups: vec![],
+            snapshots: vec![],
+            saved_devices: BTreeMap::new(),
+        }
+    }
+}
+
+/// Groups of settings that can be imported separately
+#[derive(Clone, Copy)]
+pub struct Sections {
+    pub general: bool,
+    pub audio: bool,
+    pub end_session: bool,
+    pub device_config: bool,
+    pub servers: bool,
+    pub device_slots: bool,
+    pub presets: bool,
+    pub groups: bool,
+    pub snapshots: bool,
+}
+
+impl Default for Sections {
+    fn default() -> Self {
+        Self {
+            general: true,
+            audio: true,
+            end_session: true,
+            device_config: true,
+            servers: true,
+            device_slots: true,
+            presets: true,
+            groups: true,
+            snapshots: true,
         }
     }
 }
 
 mod names {
     pub const MAIN_VOLUME: &str = "main_volume";
+    pub const LEVEL_DELAY_MS: &str = "level_delay_ms";
+    pub const PITCH_LOW_HZ: &str = "pitch_low_hz";
+    pub const PITCH_HIGH_HZ: &str = "pitch_high_hz";
+    pub const SMOOTHING_MS: &str = "smoothing_ms";
+    pub const ATTACK_MS: &str = "attack_ms";
+    pub const HOLD_MS: &str = "hold_ms";
+    pub const DECAY_MS: &str = "decay_ms";
+    pub const DYNAMICS_MODE: &str = "dynamics_mode";
+    pub const COMPRESSOR_THRESHOLD: &str = "compressor_threshold";
+    pub const COMPRESSOR_RATIO: &str = "compressor_ratio";
+    pub const COMPRESSOR_RELEASE_MS: &str = "compressor_release_ms";
+    pub const EXPANDER_THRESHOLD: &str = "expander_threshold";
+    pub const EXPANDER_RATIO: &str = "expander_ratio";
+    pub const USE_DROP_BOOST: &str = "use_drop_boost";
+    pub const DROP_SENSITIVITY: &str = "drop_sensitivity";
+    pub const DROP_BOOST: &str = "drop_boost";
+    pub const DROP_BOOST_DURATION: &str = "drop_boost_duration";
+    pub const JITTER_AMOUNT: &str = "jitter_amount";
+    pub const JITTER_RATE_HZ: &str = "jitter_rate_hz";
+    pub const PATTERN_BLEND: &str = "pattern_blend";
+    pub const PATTERN_SHAPE: &str = "pattern_shape";
+    pub const PATTERN_RATE: &str = "pattern_rate";
+    pub const PATTERN_DEPTH: &str = "pattern_depth";
+    pub const BEAT_PULSE: &str = "beat_pulse";
+    pub const USE_NORMALIZE: &str = "use_normalize";
+    pub const NORMALIZE_WINDOW: &str = "normalize_window";
+    pub const USE_SILENCE_STOP: &str = "use_silence_stop";
+    pub const SILENCE_TIMEOUT: &str = "silence_timeout";
+    pub const AUDIO_DEVICE: &str = "audio_device";
+    pub const CAPTURE_APP: &str = "capture_app";
+    pub const INPUT_GAIN: &str = "input_gain";
     pub const LOW_PASS_FREQ: &str = "low_pass_freq";
+    pub const HIGH_PASS_FREQ: &str = "high_pass_freq";
+    pub const USE_BAND_PASS: &str = "use_band_pass";
+    pub const BAND_LOW: &str = "band_low";
+    pub const BAND_HIGH: &str = "band_high";
+    pub const USE_A_WEIGHTING: &str = "use_a_weighting";
+    pub const USE_SIDE: &str = "use_side";
+    pub const USE_MIC_DUCKING: &str = "use_mic_ducking";
+    pub const PAUSE_WHEN_MUTED: &str = "pause_when_muted";
+    pub const PEAK_SINCE_FRAME: &str = "peak_since_frame";
+    pub const DUCK_THRESHOLD: &str = "duck_threshold";
+    pub const DUCK_AMOUNT: &str = "duck_amount";
+    pub const DUCK_RELEASE_MS: &str = "duck_release_ms";
+    pub const USE_NOTCH: &str = "use_notch";
+    pub const NOTCH_FREQ: &str = "notch_freq";
+    pub const NOTCH_Q: &str = "notch_q";
+    pub const BASS_EMPHASIS: &str = "bass_emphasis";
+    pub const FILTER_SLOPE: &str = "filter_slope";
+    pub const SHOW_FILTER_METERS: &str = "show_filter_meters";
+    pub const POWER_BAND: &str = "power_band";
+    pub const DETECTION_MODE: &str = "detection_mode";
+    pub const DOWNMIX: &str = "downmix";
+    pub const DOWNMIX_WEIGHTS: &str = "downmix_weights";
+    pub const ONSET_SENSITIVITY: &str = "onset_sensitivity";
+    pub const ANALYSIS_WINDOW_MS: &str = "analysis_window_ms";
+    pub const PEAK_HOLD_WINDOWS: &str = "peak_hold_windows";
+    pub const USE_AGC: &str = "use_agc";
+    pub const AGC_TARGET: &str = "agc_target";
+    pub const AGC_ADAPTATION_TIME: &str = "agc_adaptation_time";
+    pub const AGC_FLOOR: &str = "agc_floor";
     pub const DARK_MODE: &str = "dark_mode";
+    pub const DISPLAY_RATE: &str = "display_rate";
     pub const START_SCANNING_ON_STARTUP: &str = "start_scanning_on_startup";
+    pub const STOP_ALL_DISCONNECTS: &str = "stop_all_disconnects";
+    pub const RESTORE_ENABLED_ON_RECONNECT: &str =
+        "restore_enabled_on_reconnect";
+    pub const FADE_OUT_MS: &str = "fade_out_ms";
+    pub const STOP_ALL_FADES: &str = "stop_all_fades";
+    pub const STOP_HOTKEY: &str = "stop_hotkey";
+    pub const SLIDER_PREVIEW: &str = "slider_preview";
+    pub const THROTTLE_POLICY: &str = "throttle_policy";
+    pub const REMEMBER_DEVICE_SETTINGS: &str = "remember_device_settings";
+    pub const DEVICE_PERSISTENCE: &str = "device_persistence";
+    pub const PREVIEW_CEILING: &str = "preview_ceiling";
+    pub const END_SESSION_DURATION: &str = "end_session_duration";
+    pub const END_SESSION_PULSE_COUNT: &str = "end_session_pulse_count";
+    pub const END_SESSION_PULSE_STRENGTH: &str = "end_session_pulse_strength";
+    pub const END_SESSION_EXIT_AFTER: &str = "end_session_exit_after";
+    pub const LOW_BATTERY_THRESHOLD: &str = "low_battery_threshold";
+    pub const LOW_BATTERY_NOTIFY: &str = "low_battery_notify";
+    pub const LOW_BATTERY_DERATE: &str = "low_battery_derate";
+    pub const LOW_BATTERY_CEILING: &str = "low_battery_ceiling";
+    pub const POLL_BATTERY: &str = "poll_battery";
+    pub const BATTERY_INTERVAL: &str = "battery_interval";
+    pub const USER_DEVICE_CONFIG_PATH: &str = "user_device_config_path";
+    pub const WATCH_USER_DEVICE_CONFIG: &str = "watch_user_device_config";
+    pub const CONNECTIONS: &str = "connections";
+    pub const DEVICE_SLOTS: &str = "device_slots";
+    pub const HIDDEN_DEVICES: &str = "hidden_devices";
+    pub const PRESETS: &str = "presets";
+    pub const GROUPS: &str = "groups";
+    pub const SNAPSHOTS: &str = "snapshots";
+    pub const SAVED_DEVICES: &str = "saved_devices";
 }
 mod defaults {
+    use super::{DevicePreset, Hotkey, SliderPreview, ThrottlePolicy};
+    use crate::util::{
+        dynamics::DynamicsMode, pattern::PatternShape, spectrum::Band,
+        tempo::BeatPulse, DetectionMode, Downmix, FilterSlope,
+    };
+
     pub const MAIN_VOLUME: f32 = 1.0;
+    pub const LEVEL_DELAY_MS: f32 = 0.0;
+    pub const PITCH_LOW_HZ: f32 = 80.0;
+    pub const PITCH_HIGH_HZ: f32 = 1_000.0;
+    pub const SMOOTHING_MS: f32 = 0.0;
+    pub const ATTACK_MS: f32 = 0.0;
+    pub const HOLD_MS: f32 = 0.0;
+    pub const DECAY_MS: f32 = 0.0;
+    // compressor was only mode before, and its ratio of 1 still bypasses
+    pub const DYNAMICS_MODE: DynamicsMode = DynamicsMode::Compress;
+    pub const COMPRESSOR_THRESHOLD: f32 = 0.8;
+    pub const COMPRESSOR_RATIO: f32 = 1.0;
+    pub const COMPRESSOR_RELEASE_MS: f32 = 200.0;
+    pub const EXPANDER_THRESHOLD: f32 = 0.3;
+    pub const EXPANDER_RATIO: f32 = 2.0;
+    pub const USE_DROP_BOOST: bool = false;
+    pub const DROP_SENSITIVITY: f32 = 0.5;
+    pub const DROP_BOOST: f32 = 1.5;
+    pub const DROP_BOOST_DURATION: f32 = 8.0;
+    pub const JITTER_AMOUNT: f32 = 0.0;
+    pub const JITTER_RATE_HZ: f32 = 3.0;
+    pub const PATTERN_BLEND: f32 = 0.0;
+    pub const PATTERN_SHAPE: PatternShape = PatternShape::Sine;
+    pub const PATTERN_RATE: f32 = 1.0;
+    pub const PATTERN_DEPTH: f32 = 1.0;
+    pub const BEAT_PULSE: BeatPulse = BeatPulse::Off;
+    pub const USE_NORMALIZE: bool = false;
+    pub const NORMALIZE_WINDOW: f32 = 10.0;
+    pub const USE_SILENCE_STOP: bool = false;
+    pub const SILENCE_TIMEOUT: f32 = 10.0;
+    pub const INPUT_GAIN: f32 = 4.0;
     pub const LOW_PASS_FREQ: f32 = 20_000.0;
+    pub const HIGH_PASS_FREQ: f32 = 0.0;
+    pub const USE_BAND_PASS: bool = false;
+    pub const BAND_LOW: f32 = 20.0;
+    pub const BAND_HIGH: f32 = 250.0;
+    pub const USE_A_WEIGHTING: bool = false;
+    pub const USE_SIDE: bool = false;
+    pub const USE_MIC_DUCKING: bool = false;
+    pub const PAUSE_WHEN_MUTED: bool = true;
+    pub const PEAK_SINCE_FRAME: bool = false;
+    pub const DUCK_THRESHOLD: f32 = 0.05;
+    pub const DUCK_AMOUNT: f32 = 0.7;
+    pub const DUCK_RELEASE_MS: f32 = 800.0;
+    pub const USE_NOTCH: bool = false;
+    pub const NOTCH_FREQ: f32 = 120.0;
+    pub const NOTCH_Q: f32 = 5.0;
+    pub const BASS_EMPHASIS: f32 = 0.0;
+    pub const FILTER_SLOPE: FilterSlope = FilterSlope::Legacy;
+    pub const SHOW_FILTER_METERS: bool = false;
+    pub const POWER_BAND: Band = Band::FullRange;
+    pub const DETECTION_MODE: DetectionMode = DetectionMode::Rms;
+    pub const DOWNMIX: Downmix = Downmix::Average;
+    pub const ONSET_SENSITIVITY: f32 = 0.5;
+    pub const ANALYSIS_WINDOW_MS: f32 = 20.0;
+    pub const PEAK_HOLD_WINDOWS: f32 = 10.0;
+    pub const USE_AGC: bool = false;
+    pub const AGC_TARGET: f32 = 0.9;
+    pub const AGC_ADAPTATION_TIME: f32 = 10.0;
+    pub const AGC_FLOOR: f32 = 0.01;
     pub const DARK_MODE: bool = true;
+    pub const DISPLAY_RATE: f32 = 15.0;
     pub const START_SCANNING_ON_STARTUP: bool = false;
+    pub const STOP_ALL_DISCONNECTS: bool = false;
+    pub const RESTORE_ENABLED_ON_RECONNECT: bool = false;
+    pub const FADE_OUT_MS: f32 = 0.0;
+    pub const STOP_ALL_FADES: bool = false;
+    pub const STOP_HOTKEY: Option<Hotkey> = Some(Hotkey::CTRL_ALT_S);
+    pub const SLIDER_PREVIEW: SliderPreview = SliderPreview::Off;
+    pub const THROTTLE_POLICY: ThrottlePolicy = ThrottlePolicy::Auto;
+    pub const REMEMBER_DEVICE_SETTINGS: bool = false;
+    pub const PREVIEW_CEILING: f32 = 0.3;
+    pub const END_SESSION_DURATION: f32 = 10.0;
+    pub const END_SESSION_PULSE_COUNT: u32 = 3;
+    pub const END_SESSION_PULSE_STRENGTH: f32 = 0.2;
+    pub const END_SESSION_EXIT_AFTER: bool = false;
+    pub const LOW_BATTERY_THRESHOLD: f32 = 0.15;
+    pub const LOW_BATTERY_NOTIFY: bool = true;
+    pub const LOW_BATTERY_DERATE: bool = false;
+    pub const LOW_BATTERY_CEILING: f32 = 0.5;
+    pub const POLL_BATTERY: bool = true;
+    pub const BATTERY_INTERVAL: f32 = 5.0;
+    pub const WATCH_USER_DEVICE_CONFIG: bool = false;
+
+    pub fn presets() -> Vec<DevicePreset> {
+        vec![
+            DevicePreset::new("Gentle", 0.5, 0.1, 0.5, 1.5),
+            DevicePreset::new("Normal", 1.0, 0.0, 1.0, 1.0),
+            DevicePreset::new("Intense", 2.0, 0.0, 1.0, 0.7),
+        ]
+    }
 }
 
 impl Settings {
     pub fn load(storage: &dyn Storage) -> Self {
         let main_volume = get_value(storage, names::MAIN_VOLUME)
             .unwrap_or(defaults::MAIN_VOLUME);
+        let level_delay_ms = get_value(storage, names::LEVEL_DELAY_MS)
+            .unwrap_or(defaults::LEVEL_DELAY_MS);
+        let pitch_low_hz = get_value(storage, names::PITCH_LOW_HZ)
+            .unwrap_or(defaults::PITCH_LOW_HZ);
+        let pitch_high_hz = get_value(storage, names::PITCH_HIGH_HZ)
+            .unwrap_or(defaults::PITCH_HIGH_HZ);
+        let smoothing_ms = get_value(storage, names::SMOOTHING_MS)
+            .unwrap_or(defaults::SMOOTHING_MS);
+        let attack_ms =
+            get_value(storage, names::ATTACK_MS).unwrap_or(defaults::ATTACK_MS);
+        let hold_ms =
+            get_value(storage, names::HOLD_MS).unwrap_or(defaults::HOLD_MS);
+        let decay_ms =
+            get_value(storage, names::DECAY_MS).unwrap_or(defaults::DECAY_MS);
+        let compressor_threshold =
+            get_value(storage, names::COMPRESSOR_THRESHOLD)
+                .unwrap_or(defaults::COMPRESSOR_THRESHOLD);
+        let compressor_ratio = get_value(storage, names::COMPRESSOR_RATIO)
+            .unwrap_or(defaults::COMPRESSOR_RATIO);
+        let compressor_release_ms =
+            get_value(storage, names::COMPRESSOR_RELEASE_MS)
+                .unwrap_or(defaults::COMPRESSOR_RELEASE_MS);
+        let dynamics_mode = get_value(storage, names::DYNAMICS_MODE)
+            .unwrap_or(defaults::DYNAMICS_MODE);
+        let expander_threshold = get_value(storage, names::EXPANDER_THRESHOLD)
+            .unwrap_or(defaults::EXPANDER_THRESHOLD);
+        let expander_ratio = get_value(storage, names::EXPANDER_RATIO)
+            .unwrap_or(defaults::EXPANDER_RATIO);
+        let use_drop_boost = get_value(storage, names::USE_DROP_BOOST)
+            .unwrap_or(defaults::USE_DROP_BOOST);
+        let drop_sensitivity = get_value(storage, names::DROP_SENSITIVITY)
+            .unwrap_or(defaults::DROP_SENSITIVITY);
+        let drop_boost = get_value(storage, names::DROP_BOOST)
+            .unwrap_or(defaults::DROP_BOOST);
+        let drop_boost_duration =
+            get_value(storage, names::DROP_BOOST_DURATION)
+                .unwrap_or(defaults::DROP_BOOST_DURATION);
+        let jitter_amount = get_value(storage, names::JITTER_AMOUNT)
+            .unwrap_or(defaults::JITTER_AMOUNT);
+        let jitter_rate_hz = get_value(storage, names::JITTER_RATE_HZ)
+            .unwrap_or(defaults::JITTER_RATE_HZ);
+        let pattern_blend = get_value(storage, names::PATTERN_BLEND)
+            .unwrap_or(defaults::PATTERN_BLEND);
+        let pattern_shape = get_value(storage, names::PATTERN_SHAPE)
+            .unwrap_or(defaults::PATTERN_SHAPE);
+        let pattern_rate = get_value(storage, names::PATTERN_RATE)
+            .unwrap_or(defaults::PATTERN_RATE);
+        let beat_pulse = get_value(storage, names::BEAT_PULSE)
+            .unwrap_or(defaults::BEAT_PULSE);
+        let pattern_depth = get_value(storage, names::PATTERN_DEPTH)
+            .unwrap_or(defaults::PATTERN_DEPTH);
+        let duck_threshold = get_value(storage, names::DUCK_THRESHOLD)
+            .unwrap_or(defaults::DUCK_THRESHOLD);
+        let duck_amount = get_value(storage, names::DUCK_AMOUNT)
+            .unwrap_or(defaults::DUCK_AMOUNT);
+        let duck_release_ms = get_value(storage, names::DUCK_RELEASE_MS)
+            .unwrap_or(defaults::DUCK_RELEASE_MS);
+        let use_normalize = get_value(storage, names::USE_NORMALIZE)
+            .unwrap_or(defaults::USE_NORMALIZE);
+        let normalize_window = get_value(storage, names::NORMALIZE_WINDOW)
+            .unwrap_or(defaults::NORMALIZE_WINDOW);
+        let use_silence_stop = get_value(storage, names::USE_SILENCE_STOP)
+            .unwrap_or(defaults::USE_SILENCE_STOP);
+        let silence_timeout = get_value(storage, names::SILENCE_TIMEOUT)
+            .unwrap_or(defaults::SILENCE_TIMEOUT);
+        let audio_device =
+            get_value(storage, names::AUDIO_DEVICE).unwrap_or_default();
+        let capture_app =
+            get_value(storage, names::CAPTURE_APP).unwrap_or_default();
+        let input_gain = get_value(storage, names::INPUT_GAIN)
+            .unwrap_or(defaults::INPUT_GAIN);
         let low_pass_freq = get_value(storage, names::LOW_PASS_FREQ)
             .unwrap_or(defaults::LOW_PASS_FREQ);
+        let high_pass_freq = get_value(storage, names::HIGH_PASS_FREQ)
+            .unwrap_or(defaults::HIGH_PASS_FREQ);
+        let use_band_pass = get_value(storage, names::USE_BAND_PASS)
+            .unwrap_or(defaults::USE_BAND_PASS);
+        let band_low =
+            get_value(storage, names::BAND_LOW).unwrap_or(defaults::BAND_LOW);
+        let band_high =
+            get_value(storage, names::BAND_HIGH).unwrap_or(defaults::BAND_HIGH);
+        let use_a_weighting = get_value(storage, names::USE_A_WEIGHTING)
+            .unwrap_or(defaults::USE_A_WEIGHTING);
+        let use_side =
+            get_value(storage, names::USE_SIDE).unwrap_or(defaults::USE_SIDE);
+        let use_mic_ducking = get_value(storage, names::USE_MIC_DUCKING)
+            .unwrap_or(defaults::USE_MIC_DUCKING);
+        let pause_when_muted = get_value(storage, names::PAUSE_WHEN_MUTED)
+            .unwrap_or(defaults::PAUSE_WHEN_MUTED);
+        let peak_since_frame = get_value(storage, names::PEAK_SINCE_FRAME)
+            .unwrap_or(defaults::PEAK_SINCE_FRAME);
+        let use_notch =
+            get_value(storage, names::USE_NOTCH).unwrap_or(defaults::USE_NOTCH);
+        let notch_freq = get_value(storage, names::NOTCH_FREQ)
+            .unwrap_or(defaults::NOTCH_FREQ);
+        let notch_q =
+            get_value(storage, names::NOTCH_Q).unwrap_or(defaults::NOTCH_Q);
+        let bass_emphasis = get_value(storage, names::BASS_EMPHASIS)
+            .unwrap_or(defaults::BASS_EMPHASIS);
+        let filter_slope = get_value(storage, names::FILTER_SLOPE)
+            .unwrap_or(defaults::FILTER_SLOPE);
+        let show_filter_meters = get_value(storage, names::SHOW_FILTER_METERS)
+            .unwrap_or(defaults::SHOW_FILTER_METERS);
+        let power_band = get_value(storage, names::POWER_BAND)
+            .unwrap_or(defaults::POWER_BAND);
+        let detection_mode = get_value(storage, names::DETECTION_MODE)
+            .unwrap_or(defaults::DETECTION_MODE);
+        let downmix =
+            get_value(storage, names::DOWNMIX).unwrap_or(defaults::DOWNMIX);
+        let downmix_weights =
+            get_value(storage, names::DOWNMIX_WEIGHTS).unwrap_or_default();
+        let onset_sensitivity = get_value(storage, names::ONSET_SENSITIVITY)
+            .unwrap_or(defaults::ONSET_SENSITIVITY);
+        let analysis_window_ms = get_value(storage, names::ANALYSIS_WINDOW_MS)
+            .unwrap_or(defaults::ANALYSIS_WINDOW_MS);
+        let peak_hold_windows = get_value(storage, names::PEAK_HOLD_WINDOWS)
+            .unwrap_or(defaults::PEAK_HOLD_WINDOWS);
+        let use_agc =
+            get_value(storage, names::USE_AGC).unwrap_or(defaults::USE_AGC);
+        let agc_target = get_value(storage, names::AGC_TARGET)
+            .unwrap_or(defaults::AGC_TARGET);
+        let agc_adaptation_time =
+            get_value(storage, names::AGC_ADAPTATION_TIME)
+                .unwrap_or(defaults::AGC_ADAPTATION_TIME);
+        let agc_floor =
+            get_value(storage, names::AGC_FLOOR).unwrap_or(defaults::AGC_FLOOR);
+        let capture = CaptureSettings {
+            audio_device: SharedString::new(audio_device),
+            capture_app: SharedString::new(capture_app),
+            input_gain: SharedF32::new(input_gain),
+            low_pass_freq: SharedF32::new(low_pass_freq),
+            high_pass_freq: SharedF32::new(high_pass_freq),
+            use_band_pass: SharedBool::new(use_band_pass),
+            band_low: SharedF32::new(band_low),
+            band_high: SharedF32::new(band_high),
+            use_a_weighting: SharedBool::new(use_a_weighting),
+            use_side: SharedBool::new(use_side),
+            use_mic_ducking: SharedBool::new(use_mic_ducking),
+            pause_when_muted: SharedBool::new(pause_when_muted),
+            peak_since_frame: SharedBool::new(peak_since_frame),
+            use_notch: SharedBool::new(use_notch),
+            notch_freq: SharedF32::new(notch_freq),
+            notch_q: SharedF32::new(notch_q),
+            bass_emphasis: SharedF32::new(bass_emphasis),
+            filter_slope: SharedEnum::new(filter_slope),
+            show_filter_meters: SharedBool::new(show_filter_meters),
+            power_band: SharedEnum::new(power_band),
+            detection_mode: SharedEnum::new(detection_mode),
+            downmix: SharedEnum::new(downmix),
+            downmix_weights: SharedWeights::new(downmix_weights),
+            onset_sensitivity: SharedF32::new(onset_sensitivity),
+            analysis_window_ms: SharedF32::new(analysis_window_ms),
+            peak_hold_windows: SharedF32::new(peak_hold_windows),
+            use_agc: SharedBool::new(use_agc),
+            agc_target: SharedF32::new(agc_target),
+            agc_adaptation_time: SharedF32::new(agc_adaptation_time),
+            agc_floor: SharedF32::new(agc_floor),
+        };
         let use_dark_mode =
             get_value(storage, names::DARK_MODE).unwrap_or(defaults::DARK_MODE);
+        let display_rate = get_value(storage, names::DISPLAY_RATE)
+            .unwrap_or(defaults::DISPLAY_RATE);
         let start_scanning_on_startup =
             get_value(storage, names::START_SCANNING_ON_STARTUP)
                 .unwrap_or(defaults::START_SCANNING_ON_STARTUP);
+        let stop_all_disconnects =
+            get_value(storage, names::STOP_ALL_DISCONNECTS)
+                .unwrap_or(defaults::STOP_ALL_DISCONNECTS);
+        let restore_enabled_on_reconnect =
+            get_value(storage, names::RESTORE_ENABLED_ON_RECONNECT)
+                .unwrap_or(defaults::RESTORE_ENABLED_ON_RECONNECT);
+        let fade_out_ms = get_value(storage, names::FADE_OUT_MS)
+            .unwrap_or(defaults::FADE_OUT_MS);
+        let stop_all_fades = get_value(storage, names::STOP_ALL_FADES)
+            .unwrap_or(defaults::STOP_ALL_FADES);
+        let stop_hotkey = get_value(storage, names::STOP_HOTKEY)
+            .unwrap_or(defaults::STOP_HOTKEY);
+        let slider_preview = get_value(storage, names::SLIDER_PREVIEW)
+            .unwrap_or(defaults::SLIDER_PREVIEW);
+        let preview_ceiling = get_value(storage, names::PREVIEW_CEILING)
+            .unwrap_or(defaults::PREVIEW_CEILING);
+        let throttle_policy = get_value(storage, names::THROTTLE_POLICY)
+            .unwrap_or(defaults::THROTTLE_POLICY);
+        let remember_device_settings =
+            get_value(storage, names::REMEMBER_DEVICE_SETTINGS)
+                .unwrap_or(defaults::REMEMBER_DEVICE_SETTINGS);
+        let device_persistence =
+            get_value(storage, names::DEVICE_PERSISTENCE).unwrap_or_default();
+        let end_session = EndSessionSettings {
+            duration: get_value(storage, names::END_SESSION_DURATION)
+                .unwrap_or(defaults::END_SESSION_DURATION),
+            pulse_count: get_value(storage, names::END_SESSION_PULSE_COUNT)
+                .unwrap_or(defaults::END_SESSION_PULSE_COUNT),
+            pulse_strength: get_value(
+                storage,
+                names::END_SESSION_PULSE_STRENGTH,
+            )
+            .unwrap_or(defaults::END_SESSION_PULSE_STRENGTH),
+            exit_after: get_value(storage, names::END_SESSION_EXIT_AFTER)
+                .unwrap_or(defaults::END_SESSION_EXIT_AFTER),
+        };
+        let low_battery = LowBatterySettings {
+            threshold: get_value(storage, names::LOW_BATTERY_THRESHOLD)
+                .unwrap_or(defaults::LOW_BATTERY_THRESHOLD),
+            notify: get_value(storage, names::LOW_BATTERY_NOTIFY)
+                .unwrap_or(defaults::LOW_BATTERY_NOTIFY),
+            derate: get_value(storage, names::LOW_BATTERY_DERATE)
+                .unwrap_or(defaults::LOW_BATTERY_DERATE),
+            ceiling: get_value(storage, names::LOW_BATTERY_CEILING)
+                .unwrap_or(defaults::LOW_BATTERY_CEILING),
+        };
+        let battery_polling = BatteryPolling {
+            is_enabled: SharedBool::new(
+                get_value(storage, names::POLL_BATTERY)
+                    .unwrap_or(defaults::POLL_BATTERY),
+            ),
+            interval: SharedF32::new(
+                get_value(storage, names::BATTERY_INTERVAL)
+                    .unwrap_or(defaults::BATTERY_INTERVAL),
+            ),
+        };
+        let user_device_config_path =
+            get_value(storage, names::USER_DEVICE_CONFIG_PATH)
+                .unwrap_or_default();
+        let watch_user_device_config =
+            get_value(storage, names::WATCH_USER_DEVICE_CONFIG)
+                .unwrap_or(defaults::WATCH_USER_DEVICE_CONFIG);
+        let connections =
+            get_value(storage, names::CONNECTIONS).unwrap_or_default();
+        let device_slots =
+            get_value(storage, names::DEVICE_SLOTS).unwrap_or_default();
+        let hidden_devices =
+            get_value(storage, names::HIDDEN_DEVICES).unwrap_or_default();
+        let presets = get_value(storage, names::PRESETS)
+            .unwrap_or_else(defaults::presets);
+        let groups = get_value(storage, names::GROUPS).unwrap_or_default();
+        let snapshots =
+            get_value(storage, names::SNAPSHOTS).unwrap_or_default();
+        let saved_devices =
+            get_value(storage, names::SAVED_DEVICES).unwrap_or_default();
         Self {
             main_volume,
-            low_pass_freq: SharedF32::new(low_pass_freq),
+            level_delay_ms,
+            pitch_low_hz,
+            pitch_high_hz,
+            smoothing_ms,
+            attack_ms,
+            hold_ms,
+            decay_ms,
+            dynamics_mode,
+            compressor_threshold,
+            compressor_ratio,
+            compressor_release_ms,
+            expander_threshold,
+            expander_ratio,
+            use_drop_boost,
+            drop_sensitivity,
+            drop_boost,
+            drop_boost_duration,
+            jitter_amount,
+            jitter_rate_hz,
+            pattern_blend,
+            pattern_shape,
+            pattern_rate,
+            pattern_depth,
+            beat_pulse,
+            duck_threshold,
+            duck_amount,
+            duck_release_ms,
+            use_normalize,
+            normalize_window,
+            use_silence_stop,
+            silence_timeout,
+            capture,
             use_dark_mode,
+            display_rate,
             start_scanning_on_startup,
+            stop_all_disconnects,
+            restore_enabled_on_reconnect,
+            fade_out_ms,
+            stop_all_fades,
+            stop_hotkey,
+            slider_preview,
+            preview_ceiling,
+            throttle_policy,
+            remember_device_settings,
+            device_persistence,
+            end_session,
+            low_battery,
+            battery_polling,
+            user_device_config_path,
+            watch_user_device_config,
+            connections,
+            device_slots,
+            hidden_devices,
+            presets,
+            groups,
+            snapshots,
+            saved_devices,
         }
     }
 
     pub fn save(&self, storage: &mut dyn Storage) {
         set_value(storage, names::MAIN_VOLUME, &self.main_volume);
-        set_value(storage, names::LOW_PASS_FREQ, &self.low_pass_freq.load());
+        set_value(storage, names::LEVEL_DELAY_MS, &self.level_delay_ms);
+        set_value(storage, names::PITCH_LOW_HZ, &self.pitch_low_hz);
+        set_value(storage, names::PITCH_HIGH_HZ, &self.pitch_high_hz);
+        set_value(storage, names::SMOOTHING_MS, &self.smoothing_ms);
+        set_value(storage, names::ATTACK_MS, &self.attack_ms);
+        set_value(storage, names::HOLD_MS, &self.hold_ms);
+        set_value(storage, names::DECAY_MS, &self.decay_ms);
+        set_value(
+            storage,
+            names::COMPRESSOR_THRESHOLD,
+            &self.compressor_threshold,
+        );
+        set_value(storage, names::COMPRESSOR_RATIO, &self.compressor_ratio);
+        set_value(
+            storage,
+            names::COMPRESSOR_RELEASE_MS,
+            &self.compressor_release_ms,
+        );
+        set_value(storage, names::DYNAMICS_MODE, &self.dynamics_mode);
+        set_value(storage, names::EXPANDER_THRESHOLD, &self.expander_threshold);
+        set_value(storage, names::EXPANDER_RATIO, &self.expander_ratio);
+        set_value(storage, names::USE_DROP_BOOST, &self.use_drop_boost);
+        set_value(storage, names::DROP_SENSITIVITY, &self.drop_sensitivity);
+        set_value(storage, names::DROP_BOOST, &self.drop_boost);
+        set_value(
+            storage,
+            names::DROP_BOOST_DURATION,
+            &self.drop_boost_duration,
+        );
+        set_value(storage, names::JITTER_AMOUNT, &self.jitter_amount);
+        set_value(storage, names::JITTER_RATE_HZ, &self.jitter_rate_hz);
+        set_value(storage, names::PATTERN_BLEND, &self.pattern_blend);
+        set_value(storage, names::PATTERN_SHAPE, &self.pattern_shape);
+        set_value(storage, names::PATTERN_RATE, &self.pattern_rate);
+        set_value(storage, names::PATTERN_DEPTH, &self.pattern_depth);
+        set_value(storage, names::BEAT_PULSE, &self.beat_pulse);
+        set_value(storage, names::DUCK_THRESHOLD, &self.duck_threshold);
+        set_value(storage, names::DUCK_AMOUNT, &self.duck_amount);
+        set_value(storage, names::DUCK_RELEASE_MS, &self.duck_release_ms);
+        set_value(storage, names::USE_NORMALIZE, &self.use_normalize);
+        set_value(storage, names::NORMALIZE_WINDOW, &self.normalize_window);
+        set_value(storage, names::USE_SILENCE_STOP, &self.use_silence_stop);
+        set_value(storage, names::SILENCE_TIMEOUT, &self.silence_timeout);
+        let capture = &self.capture;
+        set_value(storage, names::AUDIO_DEVICE, &capture.audio_device.load());
+        set_value(storage, names::CAPTURE_APP, &capture.capture_app.load());
+        set_value(storage, names::INPUT_GAIN, &capture.input_gain.load());
+        set_value(storage, names::LOW_PASS_FREQ, &capture.low_pass_freq.load());
+        set_value(
+            storage,
+            names::HIGH_PASS_FREQ,
+            &capture.high_pass_freq.load(),
+        );
+        set_value(storage, names::USE_BAND_PASS, &capture.use_band_pass.load());
+        set_value(storage, names::BAND_LOW, &capture.band_low.load());
+        set_value(storage, names::BAND_HIGH, &capture.band_high.load());
+        set_value(
+            storage,
+            names::USE_A_WEIGHTING,
+            &capture.use_a_weighting.load(),
+        );
+        set_value(storage, names::USE_SIDE, &capture.use_side.load());
+        set_value(
+            storage,
+            names::USE_MIC_DUCKING,
+            &capture.use_mic_ducking.load(),
+        );
+        set_value(
+            storage,
+            names::PAUSE_WHEN_MUTED,
+            &capture.pause_when_muted.load(),
+        );
+        set_value(
+            storage,
+            names::PEAK_SINCE_FRAME,
+            &capture.peak_since_frame.load(),
+        );
+        set_value(storage, names::USE_NOTCH, &capture.use_notch.load());
+        set_value(storage, names::NOTCH_FREQ, &capture.notch_freq.load());
+        set_value(storage, names::NOTCH_Q, &capture.notch_q.load());
+        set_value(storage, names::BASS_EMPHASIS, &capture.bass_emphasis.load());
+        set_value(storage, names::FILTER_SLOPE, &capture.filter_slope.load());
+        set_value(
+            storage,
+            names::SHOW_FILTER_METERS,
+            &capture.show_filter_meters.load(),
+        );
+        set_value(storage, names::POWER_BAND, &capture.power_band.load());
+        set_value(
+            storage,
+            names::DETECTION_MODE,
+            &capture.detection_mode.load(),
+        );
+        set_value(storage, names::DOWNMIX, &capture.downmix.load());
+        set_value(
+            storage,
+            names::DOWNMIX_WEIGHTS,
+            &capture.downmix_weights.load_all(),
+        );
+        set_value(
+            storage,
+            names::ONSET_SENSITIVITY,
+            &capture.onset_sensitivity.load(),
+        );
+        set_value(
+            storage,
+            names::ANALYSIS_WINDOW_MS,
+            &capture.analysis_window_ms.load(),
+        );
+        set_value(
+            storage,
+            names::PEAK_HOLD_WINDOWS,
+            &capture.peak_hold_windows.load(),
+        );
+        set_value(storage, names::USE_AGC, &capture.use_agc.load());
+        set_value(storage, names::AGC_TARGET, &capture.agc_target.load());
+        set_value(
+            storage,
+            names::AGC_ADAPTATION_TIME,
+            &capture.agc_adaptation_time.load(),
+        );
+        set_value(storage, names::AGC_FLOOR, &capture.agc_floor.load());
         set_value(storage, names::DARK_MODE, &self.use_dark_mode);
+        set_value(storage, names::DISPLAY_RATE, &self.display_rate);
         set_value(
             storage,
             names::START_SCANNING_ON_STARTUP,
             &self.start_scanning_on_startup,
         );
+        set_value(
+            storage,
+            names::STOP_ALL_DISCONNECTS,
+            &self.stop_all_disconnects,
+        );
+        set_value(
+            storage,
+            names::RESTORE_ENABLED_ON_RECONNECT,
+            &self.restore_enabled_on_reconnect,
+        );
+        set_value(storage, names::FADE_OUT_MS, &self.fade_out_ms);
+        set_value(storage, names::STOP_ALL_FADES, &self.stop_all_fades);
+        set_value(storage, names::STOP_HOTKEY, &self.stop_hotkey);
+        set_value(storage, names::SLIDER_PREVIEW, &self.slider_preview);
+        set_value(storage, names::PREVIEW_CEILING, &self.preview_ceiling);
+        set_value(storage, names::THROTTLE_POLICY, &self.throttle_policy);
+        set_value(
+            storage,
+            names::REMEMBER_DEVICE_SETTINGS,
+            &self.remember_device_settings,
+        );
+        set_value(storage, names::DEVICE_PERSISTENCE, &self.device_persistence);
+        set_value(
+            storage,
+            names::END_SESSION_DURATION,
+            &self.end_session.duration,
+        );
+        set_value(
+            storage,
+            names::END_SESSION_PULSE_COUNT,
+            &self.end_session.pulse_count,
+        );
+        set_value(
+            storage,
+            names::END_SESSION_PULSE_STRENGTH,
+            &self.end_session.pulse_strength,
+        );
+        set_value(
+            storage,
+            names::END_SESSION_EXIT_AFTER,
+            &self.end_session.exit_after,
+        );
+        set_value(
+            storage,
+            names::LOW_BATTERY_THRESHOLD,
+            &self.low_battery.threshold,
+        );
+        set_value(storage, names::LOW_BATTERY_NOTIFY, &self.low_battery.notify);
+        set_value(storage, names::LOW_BATTERY_DERATE, &self.low_battery.derate);
+        set_value(
+            storage,
+            names::LOW_BATTERY_CEILING,
+            &self.low_battery.ceiling,
+        );
+        set_value(
+            storage,
+            names::POLL_BATTERY,
+            &self.battery_polling.is_enabled.load(),
+        );
+        set_value(
+            storage,
+            names::BATTERY_INTERVAL,
+            &self.battery_polling.interval.load(),
+        );
+        set_value(
+            storage,
+            names::USER_DEVICE_CONFIG_PATH,
+            &self.user_device_config_path,
+        );
+        set_value(
+            storage,
+            names::WATCH_USER_DEVICE_CONFIG,
+            &self.watch_user_device_config,
+        );
+        set_value(storage, names::CONNECTIONS, &self.connections);
+        set_value(storage, names::DEVICE_SLOTS, &self.device_slots);
+        set_value(storage, names::HIDDEN_DEVICES, &self.hidden_devices);
+        set_value(storage, names::PRESETS, &self.presets);
+        set_value(storage, names::GROUPS, &self.groups);
+        set_value(storage, names::SNAPSHOTS, &self.snapshots);
+        set_value(storage, names::SAVED_DEVICES, &self.saved_devices);
+    }
+
+    /// Copies chosen sections from `other`. Shared values are stored into
+    /// instead of replaced, as they might be used by other threads already.
+    /// Remembered devices go through [`Self::import_devices`] instead.
+    pub fn import_sections(&mut self, other: Settings, sections: Sections) {
+        if sections.general {
+            self.use_dark_mode = other.use_dark_mode;
+            self.display_rate = other.display_rate;
+            self.start_scanning_on_startup = other.start_scanning_on_startup;
+            self.stop_all_disconnects = other.stop_all_disconnects;
+            self.restore_enabled_on_reconnect =
+                other.restore_enabled_on_reconnect;
+            self.fade_out_ms = other.fade_out_ms;
+            self.stop_all_fades = other.stop_all_fades;
+            self.stop_hotkey = other.stop_hotkey;
+            self.slider_preview = other.slider_preview;
+            self.preview_ceiling = other.preview_ceiling;
+            self.throttle_policy = other.throttle_policy;
+            self.remember_device_settings = other.remember_device_settings;
+            self.device_persistence = other.device_persistence;
+        }
+        if sections.audio {
+            self.main_volume = other.main_volume;
+            self.level_delay_ms = other.level_delay_ms;
+            self.pitch_low_hz = other.pitch_low_hz;
+            self.pitch_high_hz = other.pitch_high_hz;
+            self.smoothing_ms = other.smoothing_ms;
+            self.attack_ms = other.attack_ms;
+            self.hold_ms = other.hold_ms;
+            self.decay_ms = other.decay_ms;
+            self.compressor_threshold = other.compressor_threshold;
+            self.compressor_ratio = other.compressor_ratio;
+            self.compressor_release_ms = other.compressor_release_ms;
+            self.dynamics_mode = other.dynamics_mode;
+            self.expander_threshold = other.expander_threshold;
+            self.expander_ratio = other.expander_ratio;
+            self.use_drop_boost = other.use_drop_boost;
+            self.drop_sensitivity = other.drop_sensitivity;
+            self.drop_boost = other.drop_boost;
+            self.drop_boost_duration = other.drop_boost_duration;
+            self.jitter_amount = other.jitter_amount;
+            self.jitter_rate_hz = other.jitter_rate_hz;
+            self.pattern_blend = other.pattern_blend;
+            self.pattern_shape = other.pattern_shape;
+            self.pattern_rate = other.pattern_rate;
+            self.pattern_depth = other.pattern_depth;
+            self.beat_pulse = other.beat_pulse;
+            self.duck_threshold = other.duck_threshold;
+            self.duck_amount = other.duck_amount;
+            self.duck_release_ms = other.duck_release_ms;
+            self.use_normalize = other.use_normalize;
+            self.normalize_window = other.normalize_window;
+            self.use_silence_stop = other.use_silence_stop;
+            self.silence_timeout = other.silence_timeout;
+            self.capture.store_from(&other.capture);
+        }
+        if sections.end_session {
+            self.end_session = other.end_session;
+            self.low_battery = other.low_battery;
+            self.battery_polling.store_from(&other.battery_polling);
+        }
+        if sections.device_config {
+            self.user_device_config_path = other.user_device_config_path;
+            self.watch_user_device_config = other.watch_user_device_config;
+        }
+        if sections.servers {
+            self.connections = other.connections;
+        }
+        if sections.device_slots {
+            self.device_slots = other.device_slots;
+            self.hidden_devices = other.hidden_devices;
+        }
+        if sections.presets {
+            self.presets = other.presets;
+        }
+        if sections.groups {
+            self.groups = other.groups;
+        }
+        if sections.snapshots {
+            self.snapshots = other.snapshots;
+        }
+    }
+
+    /// Remembers accepted devices. Unknown ones are kept too,
+    /// and their values apply once they connect.
+    pub fn import_devices(&mut self, imports: &[DeviceImport]) {
+        for import in imports.iter().filter(|x| x.accept) {
+            let snapshot = import.snapshot.clone();
+            self.saved_devices
+                .insert(snapshot.identifier.clone(), snapshot);
+        }
+    }
+
+    /// Saves snapshot of settings and given device values,
+    /// dropping oldest ones over limit
+    pub fn add_snapshot(&mut self, devices: Vec<DeviceSnapshot>) {
+        let mut values = ValueStorage::default();
+        self.save(&mut values);
+        values.0.remove(names::SNAPSHOTS);
+        let taken_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+        self.snapshots.push(Snapshot {
+            taken_at,
+            main_volume: self.main_volume,
+            values: values.0,
+            devices,
+        });
+        let excess = self.snapshots.len().saturating_sub(MAX_SNAPSHOTS);
+        self.snapshots.drain(..excess);
+    }
+
+    /// Restores settings from snapshot, except ones tied to this machine
+    /// or session, like servers and device config
+    pub fn restore_snapshot(&mut self, snapshot: &Snapshot) {
+        let other = Settings::load(&ValueStorage(snapshot.values.clone()));
+        let sections = Sections {
+            general: true,
+            audio: true,
+            end_session: true,
+            device_config: false,
+            servers: false,
+            device_slots: false,
+            presets: true,
+            groups: true,
+            snapshots: false,
+        };
+        self.import_sections(other, sections);
+    }
+
+    /// Remembers values of device included in persistence mask,
+    /// keeping previously remembered ones for the rest
+    pub fn remember_device(&mut self, snapshot: DeviceSnapshot) {
+        let mask = self.device_persistence;
+        let saved = self
+            .saved_devices
+            .entry(snapshot.identifier.clone())
+            .or_insert_with(|| snapshot.clone());
+        mask.apply(&snapshot, saved);
+    }
+
+    /// Returns slot of device, assigning lowest free one on first sight
+    pub fn device_slot(&mut self, identifier: &str) -> u32 {
+        let existing = self.device_slots.iter().find(|(_, x)| *x == identifier);
+        if let Some((&slot, _)) = existing {
+            return slot;
+        }
+        let slot = (1..)
+            .find(|x| !self.device_slots.contains_key(x))
+            .expect("ran out of slots");
+        self.device_slots.insert(slot, identifier.to_owned());
+        slot
+    }
+
+    /// Moves device to another slot, swapping with its current occupant
+    pub fn reassign_device_slot(&mut self, from: u32, to: u32) {
+        let moved = self.device_slots.remove(&from);
+        let occupant = self.device_slots.remove(&to);
+        if let Some(moved) = moved {
+            self.device_slots.insert(to, moved);
+        }
+        if let Some(occupant) = occupant {
+            self.device_slots.insert(from, occupant);
+        }
+    }
+
+    /// Output factor for dominant frequency `freq`, falling from 1 at low
+    /// end of range to [`MIN_PITCH_FACTOR`] at high end, evenly per octave.
+    /// `None` when there's no clear pitch.
+    pub fn pitch_factor(&self, freq: f32) -> Option<f32> {
+        if freq <= 0.0 {
+            return None;
+        }
+        let low = self.pitch_low_hz.max(1.0);
+        let high = self.pitch_high_hz.max(low * 1.01);
+        let t = ((freq / low).ln() / (high / low).ln()).clamp(0.0, 1.0);
+        Some(1.0 - t * (1.0 - MIN_PITCH_FACTOR))
+    }
+
+    pub fn level_delay(&self) -> Duration {
+        Duration::from_secs_f32(self.level_delay_ms.max(0.0) / 1000.0)
+    }
+
+    pub fn smoothing_time(&self) -> Duration {
+        Duration::from_secs_f32(self.smoothing_ms.max(0.0) / 1000.0)
+    }
+
+    pub fn envelope_params(&self) -> EnvelopeParams {
+        let ms = |x: f32| Duration::from_secs_f32(x.max(0.0) / 1000.0);
+        EnvelopeParams {
+            attack: ms(self.attack_ms),
+            hold: ms(self.hold_ms),
+            decay: ms(self.decay_ms),
+        }
+    }
+
+    pub fn compressor_params(&self) -> CompressorParams {
+        let (threshold, ratio) = match self.dynamics_mode {
+            DynamicsMode::Expand => {
+                (self.expander_threshold, self.expander_ratio)
+            }
+            DynamicsMode::Compress | DynamicsMode::Off => {
+                (self.compressor_threshold, self.compressor_ratio)
+            }
+        };
+        CompressorParams {
+            mode: self.dynamics_mode,
+            threshold,
+            ratio,
+            release: self.compressor_release_ms / 1000.0,
+        }
+    }
+
+    pub fn drop_boost_params(&self) -> DropBoostParams {
+        DropBoostParams {
+            sensitivity: self.drop_sensitivity,
+            boost: self.drop_boost,
+            duration: self.drop_boost_duration,
+        }
+    }
+
+    pub fn duck_params(&self) -> DuckParams {
+        DuckParams {
+            threshold: self.duck_threshold,
+            amount: self.duck_amount,
+            release: self.duck_release_ms / 1000.0,
+        }
+    }
+
+    pub fn pattern_params(&self) -> PatternParams {
+        PatternParams {
+            shape: self.pattern_shape,
+            rate: self.pattern_rate,
+            depth: self.pattern_depth,
+        }
+    }
+
+    /// Returns `None` if no user device config is set
+    pub fn read_user_device_config(&self) -> Option<std::io::Result<String>> {
+        if self.user_device_config_path.is_empty() {
+            return None;
+        }
+        Some(std::fs::read_to_string(&self.user_device_config_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bedroom() -> DeviceGroup {
+        let mut group = DeviceGroup::new("Bedroom".to_owned());
+        group.multiplier = 2.0;
+        group.max = 0.8;
+        group.members = ["a", "b", "gone"].map(str::to_owned).to_vec();
+        group
+    }
+
+    #[test]
+    fn group_counts_only_connected_members() {
+        let connected = ["b".to_owned(), "c".to_owned(), "a".to_owned()];
+        assert_eq!(bedroom().connected_count(&connected), 2);
+        assert_eq!(bedroom().connected_count(&[]), 0);
+    }
+
+    #[test]
+    fn dormant_members_survive_save_and_load() {
+        let mut settings = Settings::default();
+        settings.groups = vec![bedroom()];
+        let mut storage = ValueStorage::default();
+        settings.save(&mut storage);
+        let loaded = Settings::load(&storage);
+        assert!(loaded.groups == [bedroom()]);
+    }
+
+    #[test]
+    fn settings_without_groups_load_empty() {
+        let settings = Settings::load(&ValueStorage::default());
+        assert!(settings.groups.is_empty());
+    }
+
+    #[test]
+    fn group_with_missing_fields_gets_defaults() {
+        let mut storage = ValueStorage::default();
+        let old = r#"[(name: "Old", members: ["a"])]"#;
+        storage.set_string(names::GROUPS, old.to_owned());
+        let groups = Settings::load(&storage).groups;
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].is_enabled);
+        assert_eq!(groups[0].members, ["a"]);
+        assert_eq!(groups[0].multiplier, 1.0);
+    }
+
+    #[test]
+    fn assign_moves_between_groups() {
+        let mut groups = vec![bedroom(), DeviceGroup::new("Couch".to_owned())];
+        DeviceGroup::assign(&mut groups, "a", Some(1));
+        assert_eq!(groups[0].members, ["b", "gone"]);
+        assert_eq!(groups[1].members, ["a"]);
+        DeviceGroup::assign(&mut groups, "a", None);
+        assert!(groups[1].members.is_empty());
+    }
+
+    fn device(identifier: &str, multiplier: f32) -> DeviceSnapshot {
+        DeviceSnapshot {
+            identifier: identifier.to_owned(),
+            alias: None,
+            is_enabled: false,
+            band: Band::FullRange,
+            channel: Channel::Mix,
+            gamma: 1.0,
+            multiplier,
+            gate: Gate::default(),
+            max: 1.0,
+            use_pitch: false,
+            transient_blend: 0.0,
+            is_inverted: false,
+            width_depth: 0.0,
+            delay_ms: 0.0,
+            is_manual: false,
+            manual_level: 0.0,
+            low_pass_override: None,
+            band_weights: None,
+            vibrators: vec![],
+            rotators: vec![],
+            stroke: None,
+            alternate: AlternateSnapshot::default(),
+            scalars: vec![],
+            calibration: None,
+        }
+    }
+
+    fn devices(list: &[DeviceSnapshot]) -> BTreeMap<String, DeviceSnapshot> {
+        list.iter()
+            .map(|x| (x.identifier.clone(), x.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn import_sorts_devices_by_presence() {
+        let imported =
+            devices(&[device("a", 2.0), device("b", 2.0), device("c", 2.0)]);
+        let saved = devices(&[device("a", 1.0), device("b", 1.0)]);
+        let connected = [device("a", 1.5)];
+        let imports = DeviceImport::reconcile(&imported, &saved, &connected);
+        let statuses: Vec<_> = imports.iter().map(|x| x.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                ImportStatus::Connected,
+                ImportStatus::Offline,
+                ImportStatus::Unknown
+            ]
+        );
+        let accepted: Vec<_> = imports.iter().map(|x| x.accept).collect();
+        assert_eq!(accepted, [true, true, false]);
+    }
+
+    #[test]
+    fn import_diffs_against_current_values() {
+        let imported = devices(&[device("a", 2.0), device("b", 1.0)]);
+        let saved = devices(&[device("a", 1.0), device("b", 1.0)]);
+        // live values of connected device win over remembered ones
+        let connected = [device("a", 1.5)];
+        let imports = DeviceImport::reconcile(&imported, &saved, &connected);
+        let change = ValueChange {
+            name: "Multiplier",
+            current: "1.50".to_owned(),
+            imported: "2.00".to_owned(),
+        };
+        assert_eq!(imports[0].changes, [change]);
+        assert!(imports[1].changes.is_empty());
+        let unknown = DeviceImport::reconcile(&imported, &BTreeMap::new(), &[]);
+        assert!(unknown.iter().all(|x| x.changes.is_empty()));
+    }
+
+    #[test]
+    fn import_writes_only_accepted_devices() {
+        let mut settings = Settings::default();
+        settings.saved_devices = devices(&[device("b", 1.0)]);
+        let imported =
+            devices(&[device("a", 2.0), device("b", 2.0), device("c", 2.0)]);
+        let mut imports =
+            DeviceImport::reconcile(&imported, &settings.saved_devices, &[]);
+        // skip known device, keep unknown one dormant
+        imports[1].accept = false;
+        imports[2].accept = true;
+        settings.import_devices(&imports);
+        let saved: Vec<_> = settings
+            .saved_devices
+            .values()
+            .map(|x| (x.identifier.as_str(), x.multiplier))
+            .collect();
+        assert_eq!(saved, [("b", 1.0), ("c", 2.0)]);
     }
 }
@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant};
+
+// seconds, smoothing of measured intervals and latencies
+const AVERAGE_TIME: f32 = 1.0;
+// device counts as overloaded once latency exceeds send interval by this
+const OVERLOAD_RATIO: f32 = 1.2;
+// overload has to last this long to start or stop throttling
+const PERSIST_TIME: Duration = Duration::from_secs(2);
+
+/// Measures how long device takes to complete commands, and spaces commands
+/// out when they come faster than that, instead of queueing them up
+pub struct Throttle {
+    sender: flume::Sender<Duration>,
+    completions: flume::Receiver<Duration>,
+    /// Seconds, average time to complete a command
+    latency: Option<f32>,
+    /// Seconds, average time between send attempts, before throttling
+    interval: Option<f32>,
+    last_attempt: Option<Instant>,
+    last_send: Option<Instant>,
+    is_overloaded: bool,
+    /// When overload state started disagreeing with measurements
+    changing_since: Option<Instant>,
+}
+
+/// Reports completion time of one command
+pub struct CommandTimer {
+    sender: flume::Sender<Duration>,
+    start: Instant,
+}
+
+impl CommandTimer {
+    pub fn finish(self) {
+        self.finish_at(Instant::now());
+    }
+
+    fn finish_at(self, now: Instant) {
+        let _ = self.sender.send(now.saturating_duration_since(self.start));
+    }
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        let (sender, completions) = flume::unbounded();
+        Self {
+            sender,
+            completions,
+            latency: None,
+            interval: None,
+            last_attempt: None,
+            last_send: None,
+            is_overloaded: false,
+            changing_since: None,
+        }
+    }
+
+    /// Call whenever command could be sent. Returns timer if it should,
+    /// which has to be finished after command completes.
+    pub fn attempt(&mut self, is_auto: bool) -> Option<CommandTimer> {
+        self.attempt_at(Instant::now(), is_auto)
+    }
+
+    fn attempt_at(
+        &mut self,
+        now: Instant,
+        is_auto: bool,
+    ) -> Option<CommandTimer> {
+        for latency in self.completions.try_iter() {
+            let latency = latency.as_secs_f32();
+            self.latency = Some(smooth(self.latency, latency, latency));
+        }
+        if let Some(last) = self.last_attempt {
+            let dt = (now - last).as_secs_f32();
+            self.interval = Some(smooth(self.interval, dt, dt));
+        }
+        self.last_attempt = Some(now);
+        self.update_overload(now);
+
+        let effective = self.effective_interval(is_auto);
+        let is_due = match (effective, self.last_send) {
+            (Some(interval), Some(last)) => {
+                (now - last).as_secs_f32() >= interval
+            }
+            _ => true,
+        };
+        if !is_due {
+            return None;
+        }
+        self.last_send = Some(now);
+        Some(CommandTimer {
+            sender: self.sender.clone(),
+            start: now,
+        })
+    }
+
+    fn update_overload(&mut self, now: Instant) {
+        let (Some(latency), Some(interval)) = (self.latency, self.interval)
+        else {
+            return;
+        };
+        let should_be_overloaded = latency > interval * OVERLOAD_RATIO;
+        if should_be_overloaded == self.is_overloaded {
+            self.changing_since = None;
+            return;
+        }
+        let since = *self.changing_since.get_or_insert(now);
+        if now - since >= PERSIST_TIME {
+            self.is_overloaded = should_be_overloaded;
+            self.changing_since = None;
+        }
+    }
+
+    /// Seconds between commands while auto-throttled, `None` if not
+    fn effective_interval(&self, is_auto: bool) -> Option<f32> {
+        if is_auto && self.is_overloaded {
+            self.latency
+        } else {
+            None
+        }
+    }
+
+    /// Average latency, if commands are sent faster than device handles them
+    pub fn overload(&self) -> Option<Duration> {
+        self.latency
+            .filter(|_| self.is_overloaded)
+            .map(Duration::from_secs_f32)
+    }
+
+    /// Average time between commands, before throttling
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval.map(Duration::from_secs_f32)
+    }
+}
+
+/// Exponential average over `AVERAGE_TIME`, weighted by `dt` seconds
+fn smooth(average: Option<f32>, value: f32, dt: f32) -> f32 {
+    let Some(average) = average else {
+        return value;
+    };
+    let t = 1.0 - (-dt / AVERAGE_TIME).exp();
+    average + (value - average) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    /// Attempts every `STEP` for `duration`, with commands taking `latency`.
+    /// Returns times of sent commands.
+    fn drive(
+        throttle: &mut Throttle,
+        now: &mut Instant,
+        duration: Duration,
+        latency: Duration,
+    ) -> Vec<Instant> {
+        let mut sends = vec![];
+        let end = *now + duration;
+        while *now < end {
+            if let Some(timer) = throttle.attempt_at(*now, true) {
+                timer.finish_at(*now + latency);
+                sends.push(*now);
+            }
+            *now += STEP;
+        }
+        sends
+    }
+
+    /// Throttle overloaded by commands taking 50 ms, sent every 10 ms
+    fn overloaded(now: &mut Instant) -> Throttle {
+        let mut throttle = Throttle::new();
+        let slow = Duration::from_millis(50);
+        drive(&mut throttle, now, PERSIST_TIME + STEP * 2, slow);
+        throttle
+    }
+
+    #[test]
+    fn throttling_waits_for_overload_to_persist() {
+        let mut throttle = Throttle::new();
+        let mut now = Instant::now();
+        let slow = Duration::from_millis(50);
+        let sends = drive(&mut throttle, &mut now, PERSIST_TIME, slow);
+        assert_eq!(sends.len(), 200);
+        assert_eq!(throttle.overload(), None);
+        let sends = drive(&mut throttle, &mut now, STEP * 2, slow);
+        assert_eq!(sends.len(), 1);
+        let overload = throttle.overload().map(|x| x.as_secs_f32());
+        assert!(overload.is_some_and(|x| (x - 0.05).abs() < 1e-6));
+    }
+
+    #[test]
+    fn throttled_interval_follows_latency() {
+        let mut now = Instant::now();
+        let mut throttle = overloaded(&mut now);
+        let slow = Duration::from_millis(50);
+        let sends =
+            drive(&mut throttle, &mut now, Duration::from_secs(1), slow);
+        assert!(sends.len() >= 16, "{}", sends.len());
+        for gap in sends.windows(2).map(|x| x[1] - x[0]) {
+            assert!(gap >= slow && gap < slow + STEP, "{gap:?}");
+        }
+        // manual throttle mode doesn't space commands out
+        assert!(throttle.attempt_at(now, false).is_some());
+    }
+
+    #[test]
+    fn throttling_relaxes_once_latency_drops() {
+        let mut now = Instant::now();
+        let mut throttle = overloaded(&mut now);
+        let fast = Duration::from_millis(2);
+        // average follows completed commands, so it takes a while
+        let second = Duration::from_secs(1);
+        for _ in 0..60 {
+            drive(&mut throttle, &mut now, second, fast);
+            if throttle.overload().is_none() {
+                break;
+            }
+        }
+        assert_eq!(throttle.overload(), None);
+        let sends = drive(&mut throttle, &mut now, second, fast);
+        assert_eq!(sends.len(), 100);
+    }
+}
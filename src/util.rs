@@ -1,43 +1,122 @@
 use std::{
+    collections::{BTreeMap, VecDeque},
+    marker::PhantomData,
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
-    time::Duration,
+    time::Instant,
 };
 
 use buttplug::{
     client::{ButtplugClient, ButtplugClientError},
     core::{
         connector::{
+            ButtplugInProcessClientConnectorBuilder,
             ButtplugRemoteClientConnector as RemoteConn,
             ButtplugWebsocketClientTransport as WebsocketTransport,
         },
         message::serializer::ButtplugClientJSONSerializer as JsonSer,
     },
-    util::in_process_client,
+    server::{
+        device::hardware::communication::{
+            btleplug::BtlePlugCommunicationManagerBuilder,
+            lovense_dongle::{
+                LovenseHIDDongleCommunicationManagerBuilder,
+                LovenseSerialDongleCommunicationManagerBuilder,
+            },
+            serialport::SerialPortCommunicationManagerBuilder,
+            xinput::XInputDeviceCommunicationManagerBuilder,
+        },
+        ButtplugServer, ButtplugServerBuilder,
+    },
 };
+use serde::{Deserialize, Serialize};
 
-pub async fn start_bp_server(
-    server_addr: Option<String>,
+pub mod agc;
+pub mod delay;
+pub mod drop_boost;
+pub mod dynamics;
+pub mod envelope;
+pub mod jitter;
+pub mod normalize;
+pub mod onset;
+pub mod pattern;
+pub mod spectrum;
+pub mod tempo;
+
+const CLIENT_NAME: &str = "music-vibes";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ServerKind {
+    External,
+    InProcess,
+}
+
+pub async fn connect_external_server(
+    server_addr: Option<&str>,
 ) -> Result<ButtplugClient, ButtplugClientError> {
-    let addr = server_addr.as_deref().unwrap_or("ws://127.0.0.1:12345");
+    let addr = server_addr.unwrap_or("ws://127.0.0.1:12345");
     let remote_connector = RemoteConn::<_, JsonSer>::new(
         WebsocketTransport::new_insecure_connector(addr),
     );
-    let name = "music-vibes";
-    let mut client = ButtplugClient::new(name);
+    let client = ButtplugClient::new(CLIENT_NAME);
+    client.connect(remote_connector).await?;
+    Ok(client)
+}
+
+pub async fn start_bp_server(
+    server_addr: Option<String>,
+    user_device_config: Option<String>,
+) -> Result<(ButtplugClient, ServerKind), ButtplugClientError> {
+    let mut kind = ServerKind::External;
     // Fallback to in-process server
-    if let Err(e) = client.connect(remote_connector).await {
-        eprintln!("Couldn't connect to external server: {}", e);
-        eprintln!("Launching in-process server");
-        client = in_process_client(name, false).await;
-    }
+    let client = match connect_external_server(server_addr.as_deref()).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Couldn't connect to external server: {}", e);
+            eprintln!("Launching in-process server");
+            let server = build_in_process_server(user_device_config)
+                .unwrap_or_else(|e| {
+                    eprintln!("Couldn't apply user device config: {}", e);
+                    build_in_process_server(None).unwrap()
+                });
+            kind = ServerKind::InProcess;
+            connect_in_process_server(server).await?
+        }
+    };
 
     let server_name = client.server_name();
     let server_name = server_name.as_deref().unwrap_or("<unknown>");
     eprintln!("Server name: {}", server_name);
 
+    Ok((client, kind))
+}
+
+/// Fails if user device config couldn't be parsed
+pub fn build_in_process_server(
+    user_device_config: Option<String>,
+) -> Result<ButtplugServer, String> {
+    let mut builder = ButtplugServerBuilder::default();
+    builder
+        .name(CLIENT_NAME)
+        .user_device_configuration_json(user_device_config)
+        .comm_manager(BtlePlugCommunicationManagerBuilder::default())
+        .comm_manager(SerialPortCommunicationManagerBuilder::default())
+        .comm_manager(LovenseHIDDongleCommunicationManagerBuilder::default())
+        .comm_manager(LovenseSerialDongleCommunicationManagerBuilder::default())
+        .comm_manager(XInputDeviceCommunicationManagerBuilder::default());
+    builder.finish().map_err(|e| e.to_string())
+}
+
+pub async fn connect_in_process_server(
+    server: ButtplugServer,
+) -> Result<ButtplugClient, ButtplugClientError> {
+    let connector = ButtplugInProcessClientConnectorBuilder::default()
+        .server(server)
+        .finish();
+    let client = ButtplugClient::new(CLIENT_NAME);
+    client.connect(connector).await?;
     Ok(client)
 }
 
@@ -56,12 +135,146 @@ impl SharedF32 {
     pub fn load(&self) -> f32 {
         f32::from_bits(self.0.load(Ordering::Relaxed))
     }
+
+    /// Stores `v` if it's higher, for non-negative values only
+    pub fn fetch_max(&self, v: f32) {
+        // non-negative floats order same as their bits
+        self.0.fetch_max(v.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn swap(&self, v: f32) -> f32 {
+        f32::from_bits(self.0.swap(v.to_bits(), Ordering::Relaxed))
+    }
+
+    /// Takes highest of stored value and `latest`, restarting from 0.
+    /// `latest` stands in when nothing was stored since last take.
+    pub fn take_max(&self, latest: f32) -> f32 {
+        self.swap(0.0).max(latest)
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedBool(Arc<AtomicBool>);
+
+impl SharedBool {
+    pub fn new(v: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(v)))
+    }
+
+    pub fn store(&self, v: bool) {
+        self.0.store(v, Ordering::Relaxed);
+    }
+
+    pub fn load(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
+#[derive(Clone)]
+pub struct SharedCounter(Arc<AtomicU32>);
+
+impl SharedCounter {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU32::new(0)))
+    }
+
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn store(&self, v: u32) {
+        self.0.store(v, Ordering::Relaxed);
+    }
+
+    pub fn load(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct SharedString(Arc<parking_lot::Mutex<String>>);
+
+impl SharedString {
+    pub fn new(v: String) -> Self {
+        Self(Arc::new(parking_lot::Mutex::new(v)))
+    }
+
+    pub fn store(&self, v: String) {
+        *self.0.lock() = v;
+    }
+
+    pub fn load(&self) -> String {
+        self.0.lock().clone()
+    }
+}
+
+/// Fieldless enum, listing all of its variants
+pub trait Variants: Copy + PartialEq + 'static {
+    const VARIANTS: &'static [Self];
+}
+
+/// Stored as index into [`Variants::VARIANTS`]
+pub struct SharedEnum<T>(Arc<AtomicU32>, PhantomData<T>);
+
+impl<T> Clone for SharedEnum<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), PhantomData)
+    }
+}
+
+impl<T: Variants> SharedEnum<T> {
+    pub fn new(v: T) -> Self {
+        Self(Arc::new(AtomicU32::new(Self::index(v))), PhantomData)
+    }
+
+    fn index(v: T) -> u32 {
+        T::VARIANTS.iter().position(|x| *x == v).unwrap() as u32
+    }
+
+    pub fn store(&self, v: T) {
+        self.0.store(Self::index(v), Ordering::Relaxed);
+    }
+
+    pub fn load(&self) -> T {
+        T::VARIANTS[self.0.load(Ordering::Relaxed) as usize]
+    }
+}
+
+/// Custom downmix weights, keyed by channel count
+#[derive(Clone, Default)]
+pub struct SharedWeights(Arc<parking_lot::Mutex<BTreeMap<u16, Vec<f32>>>>);
+
+impl SharedWeights {
+    pub fn new(v: BTreeMap<u16, Vec<f32>>) -> Self {
+        Self(Arc::new(parking_lot::Mutex::new(v)))
+    }
+
+    /// Weights for `channels`, unset ones are 1
+    pub fn load(&self, channels: u16) -> Vec<f32> {
+        let mut weights =
+            self.0.lock().get(&channels).cloned().unwrap_or_default();
+        weights.resize(channels as usize, 1.0);
+        weights
+    }
+
+    pub fn store(&self, channels: u16, weights: Vec<f32>) {
+        self.0.lock().insert(channels, weights);
+    }
+
+    pub fn load_all(&self) -> BTreeMap<u16, Vec<f32>> {
+        self.0.lock().clone()
+    }
+
+    pub fn store_all(&self, v: BTreeMap<u16, Vec<f32>>) {
+        *self.0.lock() = v;
+    }
+}
+
+/// First order RC filter, `cutoff` in Hz
 pub fn low_pass(
     samples: &[f32],
-    time: Duration,
-    rc: f32,
+    sample_rate: u32,
+    cutoff: f32,
     channels: usize,
 ) -> Vec<f32> {
     let len = samples.len();
@@ -69,7 +282,7 @@ pub fn low_pass(
         return vec![];
     }
     let mut res = vec![0.0; len];
-    let dt = time.as_secs_f32();
+    let (dt, rc) = rc_constants(sample_rate, cutoff);
     let a = dt / (rc + dt);
     for c in 0..channels {
         res[c] = a * samples[c];
@@ -80,6 +293,349 @@ pub fn low_pass(
     res
 }
 
+/// First order RC filter, `cutoff` in Hz
+pub fn high_pass(
+    samples: &[f32],
+    sample_rate: u32,
+    cutoff: f32,
+    channels: usize,
+) -> Vec<f32> {
+    let len = samples.len();
+    if len < channels {
+        return vec![];
+    }
+    let mut res = vec![0.0; len];
+    let (dt, rc) = rc_constants(sample_rate, cutoff);
+    let a = rc / (rc + dt);
+    res[..channels].copy_from_slice(&samples[..channels]);
+    for i in channels..len {
+        res[i] = a * (res[i - channels] + samples[i] - samples[i - channels]);
+    }
+    res
+}
+
+/// Returns (sample period, time constant) of RC filter with `cutoff` in Hz
+fn rc_constants(sample_rate: u32, cutoff: f32) -> (f32, f32) {
+    let dt = 1.0 / sample_rate as f32;
+    let rc = 1.0 / (std::f32::consts::TAU * cutoff);
+    (dt, rc)
+}
+
+/// Second order IIR filter section
+#[derive(Clone, Copy)]
+pub struct Biquad {
+    b: [f64; 3],
+    // a0 normalized to 1
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// Bilinear transform of analog `(b0 + b1*s + b2*s^2) / (a0 + ...)`
+    pub fn from_analog(b: [f64; 3], a: [f64; 3], sample_rate: f64) -> Self {
+        let k = 2.0 * sample_rate;
+        let k2 = k * k;
+        let a0 = a[0] + a[1] * k + a[2] * k2;
+        Self {
+            b: [
+                (b[0] + b[1] * k + b[2] * k2) / a0,
+                (2.0 * b[0] - 2.0 * b[2] * k2) / a0,
+                (b[0] - b[1] * k + b[2] * k2) / a0,
+            ],
+            a: [
+                (2.0 * a[0] - 2.0 * a[2] * k2) / a0,
+                (a[0] - a[1] * k + a[2] * k2) / a0,
+            ],
+        }
+    }
+
+    /// Magnitude response at `freq`
+    pub fn gain_at(&self, freq: f64, sample_rate: f64) -> f64 {
+        let w = std::f64::consts::TAU * freq / sample_rate;
+        let response = |c: [f64; 3]| {
+            let re = c[0] + c[1] * w.cos() + c[2] * (2.0 * w).cos();
+            let im = c[1] * w.sin() + c[2] * (2.0 * w).sin();
+            re.hypot(im)
+        };
+        response(self.b) / response([1.0, self.a[0], self.a[1]])
+    }
+
+    pub fn scale(self, gain: f64) -> Self {
+        Self {
+            b: self.b.map(|x| x * gain),
+            a: self.a,
+        }
+    }
+
+    pub fn process(&self, samples: &[f32], channels: usize) -> Vec<f32> {
+        let mut res = vec![0.0; samples.len()];
+        for c in 0..channels {
+            let mut state = [0.0; 2];
+            for i in (c..samples.len()).step_by(channels) {
+                res[i] = self.tick(samples[i] as f64, &mut state) as f32;
+            }
+        }
+        res
+    }
+
+    /// Filters single sample, `state` is carried over to next one
+    pub fn tick(&self, x: f64, state: &mut [f64; 2]) -> f64 {
+        // transposed direct form II
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// Steepness of low pass filter
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterSlope {
+    /// First order RC filter, from before biquads
+    Legacy,
+    Db12,
+    Db24,
+    Db48,
+}
+
+impl FilterSlope {
+    pub const ALL: [Self; 4] =
+        [Self::Legacy, Self::Db12, Self::Db24, Self::Db48];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Legacy => "6 dB/oct (legacy)",
+            Self::Db12 => "12 dB/oct",
+            Self::Db24 => "24 dB/oct",
+            Self::Db48 => "48 dB/oct",
+        }
+    }
+
+    /// Number of biquad sections, `None` for legacy filter
+    pub fn sections(self) -> Option<usize> {
+        match self {
+            Self::Legacy => None,
+            Self::Db12 => Some(1),
+            Self::Db24 => Some(2),
+            Self::Db48 => Some(4),
+        }
+    }
+}
+
+impl Variants for FilterSlope {
+    const VARIANTS: &'static [Self] = &Self::ALL;
+}
+
+/// Butterworth low pass, made of biquad sections.
+/// Keeps its state between calls, so it's meant for streaming samples.
+pub struct LowPassFilter {
+    sections: Vec<Biquad>,
+    /// Per section, then per channel
+    state: Vec<Vec<[f64; 2]>>,
+    channels: usize,
+}
+
+impl LowPassFilter {
+    pub fn new() -> Self {
+        Self {
+            sections: vec![],
+            state: vec![],
+            channels: 0,
+        }
+    }
+
+    /// Recomputes coefficients. State is kept if layout doesn't change,
+    /// so moving cutoff doesn't click.
+    pub fn configure(
+        &mut self,
+        cutoff: f32,
+        section_count: usize,
+        sample_rate: u32,
+        channels: usize,
+    ) {
+        use std::f64::consts::PI;
+        let fs = sample_rate as f64;
+        let cutoff = (cutoff as f64).clamp(1.0, fs * 0.45);
+        // prewarped, so cutoff lands in right place after bilinear transform
+        let w = 2.0 * fs * (PI * cutoff / fs).tan();
+        let order = 2 * section_count;
+        self.sections = (0..section_count)
+            .map(|k| {
+                let angle = (2 * k + 1) as f64 * PI / (2 * order) as f64;
+                let q = 1.0 / (2.0 * angle.cos());
+                Biquad::from_analog([w * w, 0.0, 0.0], [w * w, w / q, 1.0], fs)
+            })
+            .collect();
+        if self.state.len() != section_count || self.channels != channels {
+            self.state = vec![vec![[0.0; 2]; channels]; section_count];
+            self.channels = channels;
+        }
+    }
+
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let channels = self.channels.max(1);
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let c = i % channels;
+                let y = self
+                    .sections
+                    .iter()
+                    .zip(&mut self.state)
+                    .fold(x as f64, |x, (section, state)| {
+                        section.tick(x, &mut state[c])
+                    });
+                y as f32
+            })
+            .collect()
+    }
+}
+
+// seconds, old and new notch are crossfaded over this after a change
+const NOTCH_CROSSFADE_TIME: f32 = 0.02;
+
+/// Biquad notch, removing narrow band around center frequency.
+/// Keeps its state between calls, so it's meant for streaming samples.
+pub struct NotchFilter {
+    section: Option<Biquad>,
+    /// Per channel
+    state: Vec<[f64; 2]>,
+    /// (center, q, sample rate), that section was made for
+    params: (f32, f32, u32),
+    /// Filter from before last change, faded out over `fade` frames
+    old: Option<(Biquad, Vec<[f64; 2]>)>,
+    fade: usize,
+    fade_len: usize,
+    channels: usize,
+}
+
+impl NotchFilter {
+    pub fn new() -> Self {
+        Self {
+            section: None,
+            state: vec![],
+            params: (0.0, 0.0, 0),
+            old: None,
+            fade: 0,
+            fade_len: 0,
+            channels: 0,
+        }
+    }
+
+    /// Recomputes coefficients if parameters changed. Moving center
+    /// crossfades from old filter, so power doesn't spike.
+    pub fn configure(
+        &mut self,
+        center: f32,
+        q: f32,
+        sample_rate: u32,
+        channels: usize,
+    ) {
+        use std::f64::consts::PI;
+        let params = (center, q, sample_rate);
+        if self.params == params && self.channels == channels {
+            return;
+        }
+        let fs = sample_rate as f64;
+        let center = (center as f64).clamp(1.0, fs * 0.45);
+        // prewarped, so center lands in right place after bilinear transform
+        let w = 2.0 * fs * (PI * center / fs).tan();
+        let q = (q as f64).max(0.1);
+        let section =
+            Biquad::from_analog([w * w, 0.0, 1.0], [w * w, w / q, 1.0], fs);
+        let is_same_layout =
+            self.channels == channels && self.params.2 == sample_rate;
+        match self.section {
+            Some(old) if is_same_layout => {
+                self.old = Some((old, self.state.clone()));
+                self.fade_len = ((sample_rate as f32 * NOTCH_CROSSFADE_TIME)
+                    as usize)
+                    .max(1);
+                self.fade = self.fade_len;
+            }
+            _ => {
+                self.state = vec![[0.0; 2]; channels];
+                self.old = None;
+                self.fade = 0;
+            }
+        }
+        self.section = Some(section);
+        self.params = params;
+        self.channels = channels;
+    }
+
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let Some(section) = self.section else {
+            return samples.to_vec();
+        };
+        let channels = self.channels.max(1);
+        let mut res = Vec::with_capacity(samples.len());
+        for frame in samples.chunks(channels) {
+            for (c, &x) in frame.iter().enumerate() {
+                let y = section.tick(x as f64, &mut self.state[c]);
+                let y = match &mut self.old {
+                    Some((old, state)) => {
+                        let t = self.fade as f64 / self.fade_len as f64;
+                        let old_y = old.tick(x as f64, &mut state[c]);
+                        old_y * t + y * (1.0 - t)
+                    }
+                    None => y,
+                };
+                res.push(y as f32);
+            }
+            if self.old.is_some() {
+                self.fade = self.fade.saturating_sub(1);
+                if self.fade == 0 {
+                    self.old = None;
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Approximate A-weighting, normalized to unity gain at 1 kHz
+pub fn a_weighting(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+) -> Vec<f32> {
+    use std::f64::consts::TAU;
+    let fs = sample_rate as f64;
+    let [w1, w2, w3, w4] = [20.6, 107.7, 737.9, 12194.0].map(|f| TAU * f);
+    let sections = [
+        Biquad::from_analog([0.0, 0.0, 1.0], [w1 * w1, 2.0 * w1, 1.0], fs),
+        Biquad::from_analog([0.0, 0.0, 1.0], [w2 * w3, w2 + w3, 1.0], fs),
+        Biquad::from_analog([w4 * w4, 0.0, 0.0], [w4 * w4, 2.0 * w4, 1.0], fs),
+    ];
+    let gain: f64 = sections.iter().map(|x| x.gain_at(1000.0, fs)).product();
+    let mut res = sections[0].scale(gain.recip()).process(samples, channels);
+    for section in &sections[1..] {
+        res = section.process(&res, channels);
+    }
+    res
+}
+
+// corner of bass emphasis shelf, full boost is reached well below it
+const BASS_EMPHASIS_FREQ: f64 = 100.0;
+
+/// First order low shelf, boosting low frequencies by `gain_db`,
+/// normalized to unity gain at 1 kHz
+pub fn bass_emphasis(
+    samples: &[f32],
+    gain_db: f32,
+    sample_rate: u32,
+    channels: usize,
+) -> Vec<f32> {
+    use std::f64::consts::TAU;
+    let fs = sample_rate as f64;
+    let gain = 10f64.powf(gain_db as f64 / 20.0);
+    let w = TAU * BASS_EMPHASIS_FREQ;
+    let shelf = Biquad::from_analog([gain * w, 1.0, 0.0], [w, 1.0, 0.0], fs);
+    let norm = shelf.gain_at(1000.0, fs).recip();
+    shelf.scale(norm).process(samples, channels)
+}
+
 pub fn calculate_power(samples: &[f32], channels: usize) -> Vec<f32> {
     let mut sums = vec![0.0; channels];
     for frame in samples.chunks_exact(channels) {
@@ -94,21 +650,525 @@ pub fn calculate_power(samples: &[f32], channels: usize) -> Vec<f32> {
     sums
 }
 
+/// Side energy relative to mid of interleaved stereo samples,
+/// 0 for identical channels, 1 when side is as loud as mid or louder
+pub fn stereo_width(samples: &[f32]) -> f32 {
+    let (mut mid, mut side) = (0.0, 0.0);
+    for frame in samples.chunks_exact(2) {
+        mid += ((frame[0] + frame[1]) / 2.0).powi(2);
+        side += ((frame[0] - frame[1]) / 2.0).powi(2);
+    }
+    if mid + side <= 0.0 {
+        return 0.0;
+    }
+    (side / mid.max(f32::EPSILON)).min(1.0)
+}
+
+/// Max absolute sample of each channel
+pub fn calculate_peak(samples: &[f32], channels: usize) -> Vec<f32> {
+    let mut peaks = vec![0.0f32; channels];
+    for frame in samples.chunks_exact(channels) {
+        for (peak, sample) in peaks.iter_mut().zip(frame) {
+            *peak = peak.max(sample.abs().min(1.0));
+        }
+    }
+    peaks
+}
+
+/// How levels of more than 2 channels are combined into one
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Downmix {
+    /// All channels count the same
+    Average,
+    /// Front left and right only
+    Front,
+    /// Subwoofer channel only, average if source has none
+    Lfe,
+    /// User set weight per channel
+    Custom,
+}
+
+impl Downmix {
+    pub const ALL: [Self; 4] =
+        [Self::Average, Self::Front, Self::Lfe, Self::Custom];
+
+    // standard speaker order puts LFE 4th, after front left, right, center
+    const LFE_INDEX: usize = 3;
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Average => "Average",
+            Self::Front => "Front L/R",
+            Self::Lfe => "LFE",
+            Self::Custom => "Custom",
+        }
+    }
+
+    pub fn weights(self, channels: u16, custom: &SharedWeights) -> Vec<f32> {
+        let len = channels as usize;
+        let mut weights = vec![1.0; len];
+        // mono and stereo have nothing to drop
+        if len <= 2 {
+            return weights;
+        }
+        match self {
+            Self::Average => {}
+            Self::Front => weights[2..].fill(0.0),
+            Self::Lfe if len >= 6 => {
+                weights.fill(0.0);
+                weights[Self::LFE_INDEX] = 1.0;
+            }
+            Self::Lfe => {}
+            Self::Custom => weights = custom.load(channels),
+        }
+        weights
+    }
+}
+
+impl Variants for Downmix {
+    const VARIANTS: &'static [Self] = &Self::ALL;
+}
+
+/// Name of channel at `index` in standard speaker order
+pub fn channel_name(index: usize) -> String {
+    const NAMES: [&str; 8] = [
+        "Front left",
+        "Front right",
+        "Center",
+        "LFE",
+        "Back left",
+        "Back right",
+        "Side left",
+        "Side right",
+    ];
+    NAMES
+        .get(index)
+        .map_or_else(|| format!("Channel {}", index + 1), |x| x.to_string())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DetectionMode {
+    /// Follow signal power
+    Rms,
+    /// Follow loudest sample in window
+    Peak,
+    /// Loudest sample over last few windows
+    PeakHold,
+    /// Pulse on detected transients
+    Onset,
+}
+
+impl DetectionMode {
+    pub const ALL: [Self; 4] =
+        [Self::Rms, Self::Peak, Self::PeakHold, Self::Onset];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rms => "RMS",
+            Self::Peak => "Peak",
+            Self::PeakHold => "Peak-hold",
+            Self::Onset => "Onset",
+        }
+    }
+
+    pub fn uses_peak(self) -> bool {
+        matches!(self, Self::Peak | Self::PeakHold)
+    }
+}
+
+impl Variants for DetectionMode {
+    const VARIANTS: &'static [Self] = &Self::ALL;
+}
+
+/// Audio channel driving a device
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Channel {
+    Mix,
+    Left,
+    Right,
+}
+
+impl Channel {
+    pub const ALL: [Self; 3] = [Self::Mix, Self::Left, Self::Right];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mix => "Mix",
+            Self::Left => "Left",
+            Self::Right => "Right",
+        }
+    }
+}
+
+/// Power of left and right channel, written by capture thread.
+/// Both are equal to mix for mono audio
+#[derive(Clone)]
+pub struct ChannelPowers([SharedF32; 2]);
+
+impl ChannelPowers {
+    pub fn new() -> Self {
+        Self(std::array::from_fn(|_| SharedF32::new(0.0)))
+    }
+
+    pub fn store(&self, powers: [f32; 2]) {
+        for (shared, power) in self.0.iter().zip(powers) {
+            shared.store(power);
+        }
+    }
+
+    pub fn load(&self) -> [f32; 2] {
+        std::array::from_fn(|i| self.0[i].load())
+    }
+}
+
+// seconds peak marker stays at maximum, before falling
+const PEAK_MARKER_HOLD: f32 = 1.5;
+// level per second, once hold is over
+const PEAK_MARKER_FALL: f32 = 0.5;
+
+/// Recent maximum of a meter, held for a moment and then falling.
+/// For display only, unlike [`PeakHold`].
+pub struct PeakMarker {
+    value: f32,
+    /// Seconds since value was last reached
+    since_peak: f32,
+    last_update: Instant,
+}
+
+impl PeakMarker {
+    pub fn new() -> Self {
+        Self {
+            value: 0.0,
+            since_peak: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn process(&mut self, level: f32) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(level, dt)
+    }
+
+    /// `dt` is time since last call, in seconds
+    pub fn process_dt(&mut self, level: f32, dt: f32) -> f32 {
+        self.since_peak += dt;
+        if level >= self.value {
+            self.value = level;
+            self.since_peak = 0.0;
+        } else if self.since_peak > PEAK_MARKER_HOLD {
+            self.value = (self.value - PEAK_MARKER_FALL * dt).max(level);
+        }
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.since_peak = 0.0;
+    }
+}
+
+/// Max over last few values
+pub struct PeakHold(VecDeque<f32>);
+
+impl PeakHold {
+    pub fn new() -> Self {
+        Self(VecDeque::new())
+    }
+
+    pub fn process(&mut self, level: f32, windows: usize) -> f32 {
+        self.0.push_front(level);
+        self.0.truncate(windows.max(1));
+        self.0.iter().copied().fold(0.0, f32::max)
+    }
+}
+
 pub fn avg(samples: &[f32]) -> f32 {
     let len = samples.len();
     samples.iter().sum::<f32>() / len as f32
 }
 
-pub trait MinCutoff {
-    fn min_cutoff(self, min: Self) -> Self;
+/// Average with each value scaled by its weight, missing weights are 0.
+/// All zero weights fall back to plain average.
+pub fn weighted_avg(values: &[f32], weights: &[f32]) -> f32 {
+    let total = weights.iter().take(values.len()).sum::<f32>();
+    if total <= 0.0 {
+        return avg(values);
+    }
+    let sum = values
+        .iter()
+        .zip(weights)
+        .map(|(value, weight)| value * weight)
+        .sum::<f32>();
+    sum / total
+}
+
+/// Cuts off values below `min`, fading in over `knee` on both sides of it,
+/// instead of jumping from 0. Zero knee is a hard cutoff.
+pub fn soft_cutoff(value: f32, min: f32, knee: f32) -> f32 {
+    if knee <= 0.0 {
+        return if value >= min { value } else { 0.0 };
+    }
+    let t = ((value - (min - knee)) / (2.0 * knee)).clamp(0.0, 1.0);
+    value * t * t * (3.0 - 2.0 * t)
 }
 
-impl MinCutoff for f32 {
-    fn min_cutoff(self, min: Self) -> Self {
-        if self < min {
+/// Noise gate with hysteresis: opens at `open` level, and closes only after
+/// level stays below `close` for `hold` seconds. With equal thresholds and
+/// no hold it's a plain cutoff.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Gate {
+    pub open: f32,
+    pub close: f32,
+    pub hold: f32,
+    /// Output fades in around close threshold, over this much on each side
+    #[serde(default)]
+    pub knee: f32,
+    #[serde(skip)]
+    is_open: bool,
+    #[serde(skip)]
+    below_since: Option<Instant>,
+}
+
+impl Gate {
+    fn close_threshold(&self) -> f32 {
+        self.close.min(self.open)
+    }
+
+    pub fn process(&mut self, level: f32) -> f32 {
+        let knee = self.knee.max(0.0);
+        if level >= self.open - knee {
+            self.is_open = true;
+            self.below_since = None;
+        } else if level >= self.close_threshold() - knee {
+            self.below_since = None;
+        } else if self.is_open {
+            let since = *self.below_since.get_or_insert_with(Instant::now);
+            if since.elapsed().as_secs_f32() >= self.hold {
+                self.is_open = false;
+                self.below_since = None;
+            }
+        }
+        if !self.is_open {
             0.0
+        } else if knee > 0.0 {
+            soft_cutoff(level, self.close_threshold(), knee)
         } else {
-            self
+            level
+        }
+    }
+
+    /// Would level pass through, without updating gate state
+    pub fn is_passing(&self, level: f32) -> bool {
+        let knee = self.knee.max(0.0);
+        level >= self.open - knee
+            || (self.is_open && level >= self.close_threshold() - knee)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_sample_spike() {
+        let mut samples = vec![0.0; 1024];
+        samples[500] = 1.0;
+        let rms = calculate_power(&samples, 1)[0];
+        let peak = calculate_peak(&samples, 1)[0];
+        assert_eq!(peak, 1.0);
+        assert!(rms < 0.05, "rms {rms}");
+    }
+
+    #[test]
+    fn peak_per_channel() {
+        // interleaved stereo, spike only on right
+        let mut samples = vec![0.1; 512];
+        samples[301] = -0.9;
+        assert_eq!(calculate_peak(&samples, 2), [0.1, 0.9]);
+    }
+
+    #[test]
+    fn peak_hold_keeps_max_of_windows() {
+        let mut hold = PeakHold::new();
+        assert_eq!(hold.process(0.8, 3), 0.8);
+        assert_eq!(hold.process(0.1, 3), 0.8);
+        assert_eq!(hold.process(0.2, 3), 0.8);
+        // spike has left the window
+        assert_eq!(hold.process(0.1, 3), 0.2);
+    }
+
+    fn sine(freq: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        use std::f32::consts::TAU;
+        (0..len)
+            .map(|i| (TAU * freq * i as f32 / sample_rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    #[test]
+    fn a_weighting_cuts_rumble() {
+        let rate = 48000;
+        // a few seconds, so filters settle on 10 Hz
+        let len = rate as usize * 4;
+        let settled = |x: Vec<f32>| calculate_power(&x[len / 2..], 1)[0];
+        let rumble = settled(a_weighting(&sine(10.0, rate, len), rate, 1));
+        let tone = settled(a_weighting(&sine(1000.0, rate, len), rate, 1));
+        // unity at 1 kHz
+        assert!((tone - 0.5 / 2f32.sqrt()).abs() < 0.01, "{tone}");
+        // A-weighting is about -70 dB at 10 Hz
+        assert!(rumble < tone * 0.001, "{rumble} vs {tone}");
+    }
+
+    #[test]
+    fn bass_emphasis_shifts_power_to_bass() {
+        let rate = 48000;
+        let len = rate as usize;
+        let settled = |x: &[f32]| calculate_power(&x[len / 2..], 1)[0];
+        let bass = sine(60.0, rate, len);
+        let treble = sine(2000.0, rate, len);
+        let mixed: Vec<_> =
+            bass.iter().zip(&treble).map(|(a, b)| a + b).collect();
+        let emphasized =
+            |x: &[f32], gain| settled(&bass_emphasis(x, gain, rate, 1));
+        // flat when slider is at 0
+        assert!((emphasized(&mixed, 0.0) - settled(&mixed)).abs() < 0.01);
+        let powers = [0.0, 6.0, 12.0].map(|gain| emphasized(&mixed, gain));
+        assert!(powers[0] < powers[1] && powers[1] < powers[2], "{powers:?}");
+        // it's bass getting louder, treble stays put
+        let bass_gain = emphasized(&bass, 12.0) / settled(&bass);
+        let treble_gain = emphasized(&treble, 12.0) / settled(&treble);
+        assert!(bass_gain > 3.0, "{bass_gain}");
+        assert!((treble_gain - 1.0).abs() < 0.1, "{treble_gain}");
+    }
+
+    #[test]
+    fn rc_filters_cut_at_cutoff() {
+        let rate = 48000;
+        let len = rate as usize;
+        let gain = |x: Vec<f32>| {
+            let power = calculate_power(&x[len / 2..], 1)[0];
+            power / (0.5 / 2f32.sqrt())
+        };
+        let low =
+            |freq| gain(low_pass(&sine(freq, rate, len), rate, 1000.0, 1));
+        let high =
+            |freq| gain(high_pass(&sine(freq, rate, len), rate, 1000.0, 1));
+        // -3 dB at cutoff
+        assert!((low(1000.0) - 0.707).abs() < 0.03, "{}", low(1000.0));
+        assert!((high(1000.0) - 0.707).abs() < 0.03, "{}", high(1000.0));
+        // 20 dB per decade
+        assert!(low(100.0) > 0.95 && low(10000.0) < 0.11);
+        assert!(high(10000.0) > 0.9 && high(100.0) < 0.11);
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn weighted_avg_scales_by_weight() {
+        assert_near(weighted_avg(&[1.0, 0.0], &[3.0, 1.0]), 0.75);
+        // missing weights count as 0
+        assert_near(weighted_avg(&[0.4, 1.0, 1.0], &[1.0]), 0.4);
+        // all zero falls back to plain average
+        assert_near(weighted_avg(&[0.2, 0.6], &[0.0, 0.0]), 0.4);
+    }
+
+    fn downmix(mode: Downmix, levels: &[f32]) -> f32 {
+        let custom = SharedWeights::new(BTreeMap::new());
+        let weights = mode.weights(levels.len() as u16, &custom);
+        weighted_avg(levels, &weights)
+    }
+
+    #[test]
+    fn downmix_picks_channels_in_speaker_order() {
+        // 5.1, only subwoofer playing
+        let lfe_only = [0.0, 0.0, 0.0, 0.9, 0.0, 0.0];
+        assert_eq!(channel_name(3), "LFE");
+        assert_near(downmix(Downmix::Lfe, &lfe_only), 0.9);
+        assert_near(downmix(Downmix::Front, &lfe_only), 0.0);
+        assert_near(downmix(Downmix::Average, &lfe_only), 0.15);
+        let front_only = [0.8, 0.4, 0.0, 0.0, 0.0, 0.0];
+        assert_eq!(channel_name(0), "Front left");
+        assert_near(downmix(Downmix::Front, &front_only), 0.6);
+    }
+
+    #[test]
+    fn downmix_leaves_stereo_alone() {
+        for mode in Downmix::ALL {
+            assert_near(downmix(mode, &[0.2, 0.6]), 0.4);
+        }
+        // quad has no subwoofer
+        assert_near(downmix(Downmix::Lfe, &[0.4; 4]), 0.4);
+    }
+
+    #[test]
+    fn custom_downmix_uses_stored_weights() {
+        let custom = SharedWeights::new(BTreeMap::new());
+        custom.store(6, vec![0.0, 0.0, 1.0]);
+        let weights = Downmix::Custom.weights(6, &custom);
+        // unset weights are 1
+        assert_eq!(weights, [0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn peak_marker_holds_then_falls() {
+        let mut marker = PeakMarker::new();
+        assert_eq!(marker.process_dt(0.8, 0.1), 0.8);
+        // held, even though level dropped
+        assert_eq!(marker.process_dt(0.1, 1.0), 0.8);
+        assert_eq!(marker.process_dt(0.1, 0.4), 0.8);
+        // hold is over, falls at fixed rate
+        assert_near(marker.process_dt(0.1, 0.2), 0.7);
+        // never below current level
+        assert_eq!(marker.process_dt(0.1, 10.0), 0.1);
+    }
+
+    #[test]
+    fn peak_marker_follows_new_peak() {
+        let mut marker = PeakMarker::new();
+        marker.process_dt(0.5, 0.1);
+        marker.process_dt(0.2, 1.4);
+        // new maximum restarts hold
+        assert_eq!(marker.process_dt(0.6, 0.1), 0.6);
+        assert_eq!(marker.process_dt(0.2, 1.0), 0.6);
+    }
+
+    #[test]
+    fn peak_marker_resets() {
+        let mut marker = PeakMarker::new();
+        marker.process_dt(0.9, 0.1);
+        marker.reset();
+        assert_eq!(marker.process_dt(0.0, 0.1), 0.0);
+    }
+
+    #[test]
+    fn soft_cutoff_boundaries() {
+        // fully cut below knee, unchanged above it
+        assert_eq!(soft_cutoff(0.19, 0.3, 0.1), 0.0);
+        assert_near(soft_cutoff(0.2, 0.3, 0.1), 0.0);
+        assert_near(soft_cutoff(0.4, 0.3, 0.1), 0.4);
+        assert_eq!(soft_cutoff(0.9, 0.3, 0.1), 0.9);
+        // halfway at threshold
+        assert_near(soft_cutoff(0.3, 0.3, 0.1), 0.15);
+    }
+
+    #[test]
+    fn soft_cutoff_rises_smoothly() {
+        let mut prev = 0.0;
+        for i in 0..=100 {
+            let value = i as f32 / 100.0;
+            let out = soft_cutoff(value, 0.3, 0.1);
+            assert!(out >= prev, "{value}");
+            assert!(out <= value, "{value}");
+            prev = out;
         }
     }
+
+    #[test]
+    fn zero_knee_is_hard_cutoff() {
+        assert_eq!(soft_cutoff(0.29, 0.3, 0.0), 0.0);
+        assert_eq!(soft_cutoff(0.3, 0.3, 0.0), 0.3);
+        assert_eq!(soft_cutoff(0.8, 0.3, 0.0), 0.8);
+    }
 }
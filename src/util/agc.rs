@@ -0,0 +1,41 @@
+use std::time::Instant;
+
+// keeps noise from being amplified too much, even above floor
+const MAX_GAIN: f32 = 20.0;
+
+/// Scales level, so that recent peak maps to target level
+pub struct AutoGain {
+    peak: f32,
+    last_update: Instant,
+}
+
+impl AutoGain {
+    pub fn new() -> Self {
+        Self {
+            peak: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// `adaptation_time` is in seconds. Below `floor` gain is frozen,
+    /// so silence doesn't get amplified into noise.
+    pub fn process(
+        &mut self,
+        level: f32,
+        target: f32,
+        adaptation_time: f32,
+        floor: f32,
+    ) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        if level >= floor {
+            let decayed = self.peak * (-dt / adaptation_time.max(0.1)).exp();
+            self.peak = decayed.max(level);
+        }
+        if self.peak < floor {
+            return level;
+        }
+        let gain = (target / self.peak).min(MAX_GAIN);
+        (level * gain).clamp(0.0, 1.0)
+    }
+}
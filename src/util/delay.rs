@@ -0,0 +1,34 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Timestamped values, read back as they were some time ago
+pub struct DelayLine<T> {
+    /// Oldest first
+    values: VecDeque<(Instant, T)>,
+}
+
+impl<T: Clone> DelayLine<T> {
+    pub fn new() -> Self {
+        Self {
+            values: VecDeque::new(),
+        }
+    }
+
+    /// Stores `value`, and returns newest one that's at least `delay` old,
+    /// or oldest one if there's none yet
+    pub fn process(&mut self, value: T, delay: Duration) -> T {
+        let now = Instant::now();
+        self.values.push_back((now, value));
+        // keep one value that's old enough, drop ones before it
+        while self.values.get(1).is_some_and(|(t, _)| now - *t >= delay) {
+            self.values.pop_front();
+        }
+        self.values[0].1.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Instant;
+
+// seconds
+const SHORT_AVERAGE_TIME: f32 = 2.0;
+const LONG_AVERAGE_TIME: f32 = 15.0;
+const RAMP_TIME: f32 = 0.5;
+// long average below this is treated as silence, so noise can't trigger
+const NOISE_FLOOR: f32 = 0.02;
+
+#[derive(Clone, Copy)]
+pub struct DropBoostParams {
+    /// 0.0 to 1.0, higher fires on smaller level jumps
+    pub sensitivity: f32,
+    /// Multiplier at full boost
+    pub boost: f32,
+    /// Seconds, including ramps
+    pub duration: f32,
+}
+
+/// Boosts level for a while, when recent average jumps well above
+/// long-term average, like when a chorus or a drop starts
+pub struct DropBoost {
+    short_average: f32,
+    long_average: f32,
+    last_update: Instant,
+    started: Instant,
+    boost_start: Option<Instant>,
+    /// Cleared after firing, until short average falls back below threshold
+    is_armed: bool,
+}
+
+impl DropBoost {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            short_average: 0.0,
+            long_average: 0.0,
+            last_update: now,
+            started: now,
+            boost_start: None,
+            is_armed: true,
+        }
+    }
+
+    /// Returns multiplier to apply, and if boost just fired
+    pub fn process(
+        &mut self,
+        level: f32,
+        params: DropBoostParams,
+    ) -> (f32, bool) {
+        self.process_at(level, params, Instant::now())
+    }
+
+    pub fn process_at(
+        &mut self,
+        level: f32,
+        params: DropBoostParams,
+        now: Instant,
+    ) -> (f32, bool) {
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+        let smooth = |average: f32, time: f32| {
+            average + (level - average) * (1.0 - (-dt / time).exp())
+        };
+        self.short_average = smooth(self.short_average, SHORT_AVERAGE_TIME);
+        self.long_average = smooth(self.long_average, LONG_AVERAGE_TIME);
+
+        let required_ratio = 1.2 + 1.8 * (1.0 - params.sensitivity);
+        let is_jump = self.short_average > self.long_average * required_ratio;
+        let is_warmed_up =
+            (now - self.started).as_secs_f32() >= LONG_AVERAGE_TIME;
+        let mut fired = false;
+        if !is_jump {
+            self.is_armed = true;
+        } else if self.is_armed
+            && is_warmed_up
+            && self.boost_start.is_none()
+            && self.long_average >= NOISE_FLOOR
+        {
+            self.boost_start = Some(now);
+            self.is_armed = false;
+            fired = true;
+        }
+
+        let Some(start) = self.boost_start else {
+            return (1.0, fired);
+        };
+        let t = (now - start).as_secs_f32();
+        if t >= params.duration {
+            self.boost_start = None;
+            return (1.0, fired);
+        }
+        let ramp = RAMP_TIME.min(params.duration / 2.0);
+        let amount = (t / ramp).min((params.duration - t) / ramp).min(1.0);
+        (1.0 + (params.boost - 1.0) * amount, fired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const PARAMS: DropBoostParams = DropBoostParams {
+        sensitivity: 0.5,
+        boost: 2.0,
+        duration: 4.0,
+    };
+    const STEP: Duration = Duration::from_millis(50);
+
+    /// Feeds `level` for `seconds`, returning outputs
+    fn feed(
+        boost: &mut DropBoost,
+        now: &mut Instant,
+        level: f32,
+        seconds: f32,
+    ) -> Vec<(f32, bool)> {
+        let steps = (seconds / STEP.as_secs_f32()) as usize;
+        (0..steps)
+            .map(|_| {
+                *now += STEP;
+                boost.process_at(level, PARAMS, *now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn steady_level_never_fires() {
+        let mut boost = DropBoost::new();
+        let mut now = Instant::now();
+        let out = feed(&mut boost, &mut now, 0.3, 30.0);
+        assert!(out.iter().all(|&x| x == (1.0, false)));
+    }
+
+    #[test]
+    fn jump_fires_once_and_ramps() {
+        let mut boost = DropBoost::new();
+        let mut now = Instant::now();
+        feed(&mut boost, &mut now, 0.1, 20.0);
+        let out = feed(&mut boost, &mut now, 0.8, 8.0);
+        assert_eq!(out.iter().filter(|x| x.1).count(), 1);
+        let fired = out.iter().position(|x| x.1).unwrap();
+        let boosted = &out[fired..];
+        // ramps in instead of jumping
+        assert!(boosted[0].0 < 1.2, "{}", boosted[0].0);
+        assert!(boosted.iter().any(|x| x.0 == PARAMS.boost));
+        // back to normal once duration is over
+        assert_eq!(out.last().unwrap().0, 1.0);
+    }
+
+    #[test]
+    fn no_firing_before_long_average_fills() {
+        let mut boost = DropBoost::new();
+        let mut now = Instant::now();
+        feed(&mut boost, &mut now, 0.1, 5.0);
+        let out = feed(&mut boost, &mut now, 0.8, 5.0);
+        assert!(out.iter().all(|x| !x.1));
+    }
+
+    #[test]
+    fn noise_never_fires() {
+        let mut boost = DropBoost::new();
+        let mut now = Instant::now();
+        feed(&mut boost, &mut now, 0.001, 20.0);
+        let out = feed(&mut boost, &mut now, 0.015, 10.0);
+        assert!(out.iter().all(|x| !x.1));
+    }
+}
@@ -0,0 +1,316 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+// levels below this are treated as silence, instead of going to -inf dB
+const MIN_DB: f32 = -100.0;
+// width of soft knee around threshold
+const KNEE_DB: f32 = 6.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DynamicsMode {
+    Off,
+    /// Reduce level above threshold
+    Compress,
+    /// Push level below threshold further down, exaggerating quiet dynamics
+    Expand,
+}
+
+impl DynamicsMode {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Compress, Self::Expand];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Compress => "Compress",
+            Self::Expand => "Expand",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CompressorParams {
+    pub mode: DynamicsMode,
+    /// Level above which gain is reduced when compressing,
+    /// or below which when expanding, 0.0 to 1.0
+    pub threshold: f32,
+    /// 1.0 for no effect, higher compresses or expands harder
+    pub ratio: f32,
+    /// Time constant of gain recovery, in seconds
+    pub release: f32,
+}
+
+/// Soft-knee compressor or expander on a 0.0 to 1.0 level. Gain changes
+/// that let hits through apply instantly: reduction when compressing,
+/// recovery when expanding. Opposite direction glides over release time.
+pub struct Compressor {
+    /// Current gain reduction in dB, zero or negative
+    reduction: f32,
+    last_update: Instant,
+}
+
+impl Compressor {
+    pub fn new() -> Self {
+        Self {
+            reduction: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn process(&mut self, level: f32, params: CompressorParams) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(level, dt, params)
+    }
+
+    /// `dt` is time since last call, in seconds
+    pub fn process_dt(
+        &mut self,
+        level: f32,
+        dt: f32,
+        params: CompressorParams,
+    ) -> f32 {
+        if params.mode == DynamicsMode::Off || params.ratio <= 1.0 {
+            self.reduction = 0.0;
+            return level;
+        }
+        let input_db = to_db(level);
+        let threshold = to_db(params.threshold);
+        let (target, is_instant) = match params.mode {
+            DynamicsMode::Compress => {
+                let target =
+                    static_curve(input_db, threshold, params.ratio) - input_db;
+                (target, target < self.reduction)
+            }
+            DynamicsMode::Expand | DynamicsMode::Off => {
+                let target =
+                    expand_curve(input_db, threshold, params.ratio) - input_db;
+                (target, target > self.reduction)
+            }
+        };
+        self.reduction = if is_instant {
+            target
+        } else {
+            // exact for any dt, so uneven frame times don't matter
+            let decay = (-dt / params.release.max(0.001)).exp();
+            target + (self.reduction - target) * decay
+        };
+        (level * from_db(self.reduction)).clamp(0.0, 1.0)
+    }
+}
+
+// seconds, how quickly ducking kicks in once mic is loud
+const DUCK_ATTACK: f32 = 0.05;
+
+#[derive(Clone, Copy)]
+pub struct DuckParams {
+    /// Mic level above which output is ducked
+    pub threshold: f32,
+    /// 0.0 to 1.0, how much of output is taken away
+    pub amount: f32,
+    /// Time constant of recovery after mic goes quiet, in seconds
+    pub release: f32,
+}
+
+/// Lowers output while side-chain level is over threshold,
+/// gliding in and out, so it doesn't pump
+pub struct Ducker {
+    gain: f32,
+    last_update: Instant,
+}
+
+impl Ducker {
+    pub fn new() -> Self {
+        Self {
+            gain: 1.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Returns gain to apply to output
+    pub fn process(&mut self, side_chain: f32, params: DuckParams) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(side_chain, dt, params)
+    }
+
+    /// `dt` is time since last call, in seconds
+    pub fn process_dt(
+        &mut self,
+        side_chain: f32,
+        dt: f32,
+        params: DuckParams,
+    ) -> f32 {
+        let target = if side_chain > params.threshold {
+            1.0 - params.amount.clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let time = if target < self.gain {
+            DUCK_ATTACK
+        } else {
+            params.release.max(0.001)
+        };
+        self.gain = target + (self.gain - target) * (-dt / time).exp();
+        self.gain
+    }
+
+    pub fn is_ducking(&self) -> bool {
+        self.gain < 0.99
+    }
+}
+
+/// Output level in dB for given input level
+fn static_curve(input: f32, threshold: f32, ratio: f32) -> f32 {
+    let over = input - threshold;
+    if 2.0 * over < -KNEE_DB {
+        input
+    } else if 2.0 * over <= KNEE_DB {
+        let x = over + KNEE_DB / 2.0;
+        input + (ratio.recip() - 1.0) * x * x / (2.0 * KNEE_DB)
+    } else {
+        threshold + over / ratio
+    }
+}
+
+/// Output level in dB for given input level, mirror of `static_curve`
+/// with knee below threshold
+fn expand_curve(input: f32, threshold: f32, ratio: f32) -> f32 {
+    let under = input - threshold;
+    if 2.0 * under > KNEE_DB {
+        input
+    } else if 2.0 * under >= -KNEE_DB {
+        let x = under - KNEE_DB / 2.0;
+        input - (ratio - 1.0) * x * x / (2.0 * KNEE_DB)
+    } else {
+        threshold + under * ratio
+    }
+}
+
+fn to_db(level: f32) -> f32 {
+    (20.0 * level.log10()).max(MIN_DB)
+}
+
+fn from_db(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compress(threshold: f32, ratio: f32) -> CompressorParams {
+        CompressorParams {
+            mode: DynamicsMode::Compress,
+            threshold,
+            ratio,
+            release: 0.2,
+        }
+    }
+
+    #[test]
+    fn ratio_of_one_is_bypass() {
+        let mut compressor = Compressor::new();
+        for level in [0.0, 0.3, 0.9, 1.0] {
+            assert_eq!(
+                compressor.process_dt(level, 0.016, compress(0.1, 1.0)),
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn quiet_level_passes() {
+        let mut compressor = Compressor::new();
+        // well below knee
+        let level = compressor.process_dt(0.05, 0.016, compress(0.5, 4.0));
+        assert!((level - 0.05).abs() < 1e-6, "{level}");
+    }
+
+    #[test]
+    fn spike_is_reduced_instantly() {
+        let mut compressor = Compressor::new();
+        let params = compress(0.25, 4.0);
+        compressor.process_dt(0.05, 0.016, params);
+        let level = compressor.process_dt(1.0, 0.016, params);
+        // 12 dB over threshold at 4:1 comes out 3 dB over
+        let expected = 0.25 * from_db(3.0);
+        assert!((level - expected).abs() < 0.01, "{level}");
+    }
+
+    #[test]
+    fn gain_recovers_over_release() {
+        let mut compressor = Compressor::new();
+        let params = compress(0.25, 4.0);
+        compressor.process_dt(1.0, 0.016, params);
+        let right_after = compressor.process_dt(0.05, 0.016, params);
+        assert!(right_after < 0.05 * 0.9, "{right_after}");
+        let later = compressor.process_dt(0.05, 2.0, params);
+        assert!((later - 0.05).abs() < 1e-3, "{later}");
+    }
+
+    #[test]
+    fn release_is_independent_of_frame_times() {
+        let params = compress(0.25, 4.0);
+        let mut even = Compressor::new();
+        let mut uneven = Compressor::new();
+        even.process_dt(1.0, 0.016, params);
+        uneven.process_dt(1.0, 0.016, params);
+        for _ in 0..10 {
+            even.process_dt(0.1, 0.01, params);
+        }
+        uneven.process_dt(0.1, 0.07, params);
+        uneven.process_dt(0.1, 0.03, params);
+        let even = even.process_dt(0.1, 0.028, params);
+        let uneven = uneven.process_dt(0.1, 0.028, params);
+        assert!((even - uneven).abs() < 1e-4, "{even} vs {uneven}");
+    }
+
+    fn expand(threshold: f32, ratio: f32) -> CompressorParams {
+        CompressorParams {
+            mode: DynamicsMode::Expand,
+            threshold,
+            ratio,
+            release: 0.2,
+        }
+    }
+
+    #[test]
+    fn expander_passes_loud_level() {
+        let mut expander = Compressor::new();
+        // well above knee
+        let level = expander.process_dt(0.8, 0.016, expand(0.1, 2.0));
+        assert!((level - 0.8).abs() < 1e-6, "{level}");
+    }
+
+    #[test]
+    fn expander_pushes_quiet_level_down() {
+        let mut expander = Compressor::new();
+        let params = expand(0.1, 2.0);
+        // settles on reduction, which glides in over release
+        let mut level = 0.0;
+        for _ in 0..200 {
+            level = expander.process_dt(0.025, 0.016, params);
+        }
+        // 12 dB under threshold at 1:2 comes out 24 dB under
+        let expected = 0.1 * from_db(-24.0);
+        assert!((level - expected).abs() < 1e-3, "{level}");
+    }
+
+    #[test]
+    fn expander_recovers_instantly_on_hit() {
+        let mut expander = Compressor::new();
+        let params = expand(0.1, 2.0);
+        for _ in 0..200 {
+            expander.process_dt(0.01, 0.016, params);
+        }
+        let hit = expander.process_dt(0.8, 0.016, params);
+        assert!((hit - 0.8).abs() < 1e-6, "{hit}");
+    }
+
+    #[test]
+    fn expander_ratio_of_one_is_bypass() {
+        let mut expander = Compressor::new();
+        assert_eq!(expander.process_dt(0.01, 0.016, expand(0.5, 1.0)), 0.01);
+    }
+}
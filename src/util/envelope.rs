@@ -0,0 +1,311 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+pub struct EnvelopeParams {
+    /// Ramp up time, zero follows input immediately
+    pub attack: Duration,
+    /// How long peaks are held at full level
+    pub hold: Duration,
+    /// Time constant of exponential decay after hold, zero drops immediately
+    pub decay: Duration,
+}
+
+/// Attack smoothing, followed by peak hold and decay.
+/// Hold and decay are computed from time of last peak, not accumulated
+/// per update, so they stay exact no matter how irregular updates are.
+pub struct Envelope {
+    attack_level: f32,
+    last_update: Instant,
+    peak: f32,
+    peak_time: Instant,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            attack_level: 0.0,
+            last_update: now,
+            peak: 0.0,
+            peak_time: now,
+        }
+    }
+
+    pub fn process(&mut self, input: f32, params: EnvelopeParams) -> f32 {
+        self.process_at(input, params, Instant::now())
+    }
+
+    pub fn process_at(
+        &mut self,
+        input: f32,
+        params: EnvelopeParams,
+        now: Instant,
+    ) -> f32 {
+        let dt = now.saturating_duration_since(self.last_update);
+        self.last_update = now;
+        if input <= self.attack_level || params.attack.is_zero() {
+            self.attack_level = input;
+        } else {
+            let alpha =
+                1.0 - (-dt.as_secs_f32() / params.attack.as_secs_f32()).exp();
+            self.attack_level += alpha * (input - self.attack_level);
+        }
+
+        // rising level takes over from decaying one, once it gets above it
+        let held = self.held_at(now, params);
+        if self.attack_level >= held {
+            self.peak = self.attack_level;
+            self.peak_time = now;
+            self.attack_level
+        } else {
+            held
+        }
+    }
+
+    fn held_at(&self, now: Instant, params: EnvelopeParams) -> f32 {
+        let since_peak = now.saturating_duration_since(self.peak_time);
+        if since_peak <= params.hold {
+            return self.peak;
+        }
+        if params.decay.is_zero() {
+            return 0.0;
+        }
+        let decaying = (since_peak - params.hold).as_secs_f32();
+        self.peak * (-decaying / params.decay.as_secs_f32()).exp()
+    }
+}
+
+/// Exponential moving average, applied alike to rises and falls
+pub struct Smoother {
+    level: f32,
+    last_update: Instant,
+}
+
+impl Smoother {
+    pub fn new() -> Self {
+        Self {
+            level: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Zero `time_constant` follows input immediately
+    pub fn process(&mut self, input: f32, time_constant: Duration) -> f32 {
+        let dt = self.last_update.elapsed();
+        self.last_update = Instant::now();
+        self.process_dt(input, dt, time_constant)
+    }
+
+    /// `dt` is time since last call
+    pub fn process_dt(
+        &mut self,
+        input: f32,
+        dt: Duration,
+        time_constant: Duration,
+    ) -> f32 {
+        if time_constant.is_zero() {
+            self.level = input;
+        } else {
+            let alpha =
+                1.0 - (-dt.as_secs_f32() / time_constant.as_secs_f32()).exp();
+            self.level += alpha * (input - self.level);
+        }
+        self.level
+    }
+}
+
+// time constants of envelopes whose difference is transient level
+const TRANSIENT_FAST: Duration = Duration::from_millis(10);
+const TRANSIENT_SLOW: Duration = Duration::from_millis(300);
+// difference of envelopes is small next to level, so it's scaled up
+const TRANSIENT_GAIN: f32 = 2.0;
+
+/// Splits out sudden rises in level, as fast envelope minus slow one.
+/// Sustained level is the input itself, so it's not computed here.
+pub struct TransientFollower {
+    fast: Smoother,
+    slow: Smoother,
+}
+
+impl TransientFollower {
+    pub fn new() -> Self {
+        Self {
+            fast: Smoother::new(),
+            slow: Smoother::new(),
+        }
+    }
+
+    /// Returns transient level, 0.0 to 1.0
+    pub fn process(&mut self, level: f32) -> f32 {
+        let fast = self.fast.process(level, TRANSIENT_FAST);
+        let slow = self.slow.process(level, TRANSIENT_SLOW);
+        ((fast - slow) * TRANSIENT_GAIN).clamp(0.0, 1.0)
+    }
+
+    /// `dt` is time since last call
+    pub fn process_dt(&mut self, level: f32, dt: Duration) -> f32 {
+        let fast = self.fast.process_dt(level, dt, TRANSIENT_FAST);
+        let slow = self.slow.process_dt(level, dt, TRANSIENT_SLOW);
+        ((fast - slow) * TRANSIENT_GAIN).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn params(attack_ms: u32, hold_ms: u32, decay_ms: u32) -> EnvelopeParams {
+        EnvelopeParams {
+            attack: MS * attack_ms,
+            hold: MS * hold_ms,
+            decay: MS * decay_ms,
+        }
+    }
+
+    #[test]
+    fn zero_attack_is_instant() {
+        let mut env = Envelope::new();
+        let start = Instant::now();
+        let level = env.process_at(0.9, params(0, 0, 0), start + MS);
+        assert_eq!(level, 0.9);
+    }
+
+    #[test]
+    fn attack_ramps_up() {
+        let mut env = Envelope::new();
+        let start = Instant::now();
+        let p = params(50, 0, 0);
+        // one time constant in, ~63% of the way up
+        let level = env.process_at(1.0, p, start + MS * 50);
+        assert!((level - 0.632).abs() < 0.01, "{level}");
+        let level = env.process_at(1.0, p, start + MS * 500);
+        assert!(level > 0.99, "{level}");
+    }
+
+    #[test]
+    fn rise_during_decay_takes_over_smoothly() {
+        let mut env = Envelope::new();
+        let start = Instant::now();
+        let p = params(50, 0, 200);
+        env.process_at(1.0, params(0, 0, 200), start);
+        let decaying = env.process_at(0.5, p, start + MS * 100);
+        // attack drops instantly to input, while output keeps decaying
+        assert!(decaying > 0.5 && decaying < 1.0, "{decaying}");
+        let mut prev = decaying;
+        for i in 101..1000 {
+            let level = env.process_at(0.8, p, start + MS * i);
+            // no jump, output only moves a little per step
+            assert!((level - prev).abs() < 0.02, "{prev} -> {level}");
+            prev = level;
+        }
+        assert!((prev - 0.8).abs() < 0.01, "{prev}");
+    }
+
+    #[test]
+    fn hold_then_decay() {
+        let mut env = Envelope::new();
+        let start = Instant::now();
+        let p = params(0, 100, 200);
+        env.process_at(1.0, p, start);
+        assert_eq!(env.process_at(0.0, p, start + MS * 100), 1.0);
+        let level = env.process_at(0.0, p, start + MS * 101);
+        assert!(level < 1.0 && level > 0.99, "{level}");
+        // one decay time constant past hold
+        let level = env.process_at(0.0, p, start + MS * 300);
+        assert!((level - (-1f32).exp()).abs() < 0.001, "{level}");
+    }
+
+    #[test]
+    fn peak_during_hold_restarts_it() {
+        let mut env = Envelope::new();
+        let start = Instant::now();
+        let p = params(0, 100, 200);
+        env.process_at(0.8, p, start);
+        env.process_at(0.8, p, start + MS * 80);
+        // held from second peak, first one's hold would be over
+        assert_eq!(env.process_at(0.0, p, start + MS * 150), 0.8);
+        assert!(env.process_at(0.0, p, start + MS * 181) < 0.8);
+    }
+
+    #[test]
+    fn lower_peak_during_hold_is_ignored() {
+        let mut env = Envelope::new();
+        let start = Instant::now();
+        let p = params(0, 100, 200);
+        env.process_at(1.0, p, start);
+        assert_eq!(env.process_at(0.5, p, start + MS * 50), 1.0);
+        assert!(env.process_at(0.0, p, start + MS * 101) < 1.0);
+    }
+
+    #[test]
+    fn gap_matches_regular_updates() {
+        let p = params(0, 100, 200);
+        let start = Instant::now();
+        let mut stalled = Envelope::new();
+        let mut regular = Envelope::new();
+        stalled.process_at(1.0, p, start);
+        regular.process_at(1.0, p, start);
+        for i in 1..=125 {
+            regular.process_at(0.0, p, start + MS * (i * 16));
+        }
+        let end = start + MS * 2000;
+        let expected = (-1900.0 / 200f32).exp();
+        let stalled = stalled.process_at(0.0, p, end);
+        let regular = regular.process_at(0.0, p, end);
+        assert!((stalled - expected).abs() < 1e-6, "{stalled}");
+        assert!((regular - expected).abs() < 1e-6, "{regular}");
+    }
+
+    #[test]
+    fn smoother_zero_time_follows_input() {
+        let mut smoother = Smoother::new();
+        assert_eq!(smoother.process_dt(0.7, MS * 16, Duration::ZERO), 0.7);
+    }
+
+    #[test]
+    fn smoother_is_exact_for_any_step() {
+        let mut one = Smoother::new();
+        let mut many = Smoother::new();
+        let time = MS * 100;
+        let single = one.process_dt(1.0, MS * 100, time);
+        let mut split = 0.0;
+        for _ in 0..10 {
+            split = many.process_dt(1.0, MS * 10, time);
+        }
+        assert!((single - split).abs() < 1e-5, "{single} vs {split}");
+        assert!((single - 0.632).abs() < 0.01, "{single}");
+    }
+
+    fn follow(follower: &mut TransientFollower, level: f32, ms: u32) -> f32 {
+        let mut transient = 0.0;
+        for _ in 0..ms {
+            transient = follower.process_dt(level, MS);
+        }
+        transient
+    }
+
+    #[test]
+    fn steady_level_has_no_transient() {
+        let mut follower = TransientFollower::new();
+        assert!(follow(&mut follower, 0.6, 3000) < 1e-3);
+    }
+
+    #[test]
+    fn rise_is_transient_then_sustain() {
+        let mut follower = TransientFollower::new();
+        follow(&mut follower, 0.1, 3000);
+        let hit = follow(&mut follower, 0.8, 30);
+        assert!(hit > 0.5, "{hit}");
+        let held = follow(&mut follower, 0.8, 3000);
+        assert!(held < 1e-3, "{held}");
+    }
+
+    #[test]
+    fn fall_is_not_transient() {
+        let mut follower = TransientFollower::new();
+        follow(&mut follower, 0.8, 3000);
+        assert_eq!(follow(&mut follower, 0.1, 30), 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Random variation, gliding between random targets instead of changing
+/// every frame
+pub struct Jitter {
+    /// xorshift state, never zero
+    state: u64,
+    /// Targets, -1.0 to 1.0, of current glide
+    from: f32,
+    to: f32,
+    /// 0.0 to 1.0, position between targets
+    phase: f32,
+    last_update: Instant,
+}
+
+impl Jitter {
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_nanos() as u64);
+        Self::with_seed(seed)
+    }
+
+    /// Same seed gives same sequence of targets
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            state: seed.max(1),
+            from: 0.0,
+            to: 0.0,
+            phase: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Returns factor to multiply level by
+    pub fn process(&mut self, amount: f32, rate: f32) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(dt, amount, rate)
+    }
+
+    /// `dt` is time since last call in seconds, `amount` is largest
+    /// relative change, `rate` is new targets per second
+    pub fn process_dt(&mut self, dt: f32, amount: f32, rate: f32) -> f32 {
+        self.phase += dt * rate.max(0.0);
+        // long pauses don't need to catch up on every skipped target
+        if self.phase >= 2.0 {
+            self.phase = self.phase.fract() + 1.0;
+        }
+        while self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.from = self.to;
+            self.to = self.next_random() * 2.0 - 1.0;
+        }
+        if amount <= 0.0 {
+            return 1.0;
+        }
+        // smoothstep, so glides have no corners
+        let t = self.phase * self.phase * (3.0 - 2.0 * self.phase);
+        let value = self.from + (self.to - self.from) * t;
+        1.0 + amount * value
+    }
+
+    /// 0.0 to 1.0
+    fn next_random(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(jitter: &mut Jitter, amount: f32) -> Vec<f32> {
+        (0..1000)
+            .map(|_| jitter.process_dt(0.016, amount, 5.0))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_output() {
+        let a = run(&mut Jitter::with_seed(42), 0.3);
+        let b = run(&mut Jitter::with_seed(42), 0.3);
+        let c = run(&mut Jitter::with_seed(43), 0.3);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn zero_amount_is_exactly_one() {
+        let out = run(&mut Jitter::with_seed(7), 0.0);
+        assert!(out.iter().all(|&x| x == 1.0));
+    }
+
+    #[test]
+    fn stays_within_amount() {
+        for seed in [0, 1, 12345, u64::MAX] {
+            let out = run(&mut Jitter::with_seed(seed), 0.25);
+            assert!(out.iter().all(|x| (0.75..=1.25).contains(x)), "{seed}");
+            // actually varies
+            assert!(out.iter().any(|&x| x != 1.0), "{seed}");
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Instant;
+
+// maximum never decays below this, so silence doesn't scale noise up
+const MIN_MAX: f32 = 0.05;
+
+/// Scales level by maximum seen over recent window, so output spans full
+/// range of recent content. Maximum decays once it's older than window.
+pub struct RollingMax {
+    max: f32,
+    /// Seconds since maximum was last reached
+    age: f32,
+    last_update: Instant,
+}
+
+impl RollingMax {
+    pub fn new() -> Self {
+        Self {
+            max: 0.0,
+            age: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// `window` in seconds
+    pub fn process(&mut self, level: f32, window: f32) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(level, dt, window)
+    }
+
+    /// `dt` is time since last call, `window` in seconds
+    pub fn process_dt(&mut self, level: f32, dt: f32, window: f32) -> f32 {
+        let window = window.max(0.001);
+        if level >= self.max {
+            self.max = level;
+            self.age = 0.0;
+        } else {
+            self.age += dt;
+            if self.age > window {
+                let decay = (-dt / window).exp();
+                let target = level.max(MIN_MAX);
+                self.max = target + (self.max - target) * decay;
+            }
+        }
+        (level / self.max.max(MIN_MAX)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 0.016;
+
+    #[test]
+    fn silence_at_startup_stays_silent() {
+        let mut max = RollingMax::new();
+        assert_eq!(max.process_dt(0.0, DT, 5.0), 0.0);
+        assert_eq!(max.process_dt(0.0, DT, 5.0), 0.0);
+    }
+
+    #[test]
+    fn noise_at_startup_isnt_full() {
+        let mut max = RollingMax::new();
+        let level = max.process_dt(0.01, DT, 5.0);
+        assert!((level - 0.2).abs() < 1e-6, "{level}");
+    }
+
+    #[test]
+    fn peak_maps_to_full() {
+        let mut max = RollingMax::new();
+        assert_eq!(max.process_dt(0.4, DT, 5.0), 1.0);
+        assert_eq!(max.process_dt(0.2, DT, 5.0), 0.5);
+    }
+
+    #[test]
+    fn long_silence_keeps_noise_low() {
+        let mut max = RollingMax::new();
+        max.process_dt(0.8, DT, 5.0);
+        let mut level = 0.0;
+        for _ in 0..6000 {
+            level = max.process_dt(0.01, DT, 5.0);
+        }
+        assert!(level <= 0.2 + 1e-3, "{level}");
+    }
+
+    #[test]
+    fn max_holds_for_window_then_decays() {
+        let mut max = RollingMax::new();
+        max.process_dt(0.8, DT, 1.0);
+        let held = max.process_dt(0.4, 0.9, 1.0);
+        assert_eq!(held, 0.5);
+        let decayed = max.process_dt(0.4, 1.0, 1.0);
+        assert!(decayed > 0.5, "{decayed}");
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+// time constant of running average energy, onsets are measured against it
+const AVERAGE_TIME: Duration = Duration::from_secs(1);
+// time for pulse to decay to about a third
+const DECAY_TIME: Duration = Duration::from_millis(150);
+const MIN_ONSET_INTERVAL: Duration = Duration::from_millis(100);
+// energy below this is treated as silence, so noise floor doesn't fire
+const NOISE_FLOOR: f32 = 0.01;
+
+/// Energy-delta onset detector with adaptive threshold
+pub struct OnsetDetector {
+    average: f32,
+    level: f32,
+    last_update: Instant,
+    last_onset: Instant,
+}
+
+impl OnsetDetector {
+    pub fn new() -> Self {
+        Self {
+            average: 0.0,
+            level: 0.0,
+            last_update: Instant::now(),
+            last_onset: Instant::now(),
+        }
+    }
+
+    /// Feeds current energy, returns pulse level.
+    /// Higher `sensitivity` (0..=1) lowers threshold above running average.
+    pub fn process(&mut self, energy: f32, sensitivity: f32) -> f32 {
+        let now = Instant::now();
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        self.level *= (-dt / DECAY_TIME.as_secs_f32()).exp();
+
+        let ratio = 1.2 + 1.8 * (1.0 - sensitivity.clamp(0.0, 1.0));
+        let threshold = (self.average * ratio).max(NOISE_FLOOR);
+        let is_onset =
+            energy > threshold && now - self.last_onset >= MIN_ONSET_INTERVAL;
+        if is_onset {
+            self.level = self.level.max(energy.clamp(0.0, 1.0));
+            self.last_onset = now;
+        }
+
+        let alpha = 1.0 - (-dt / AVERAGE_TIME.as_secs_f32()).exp();
+        self.average += alpha * (energy - self.average);
+        self.level
+    }
+}
@@ -0,0 +1,103 @@
+use std::{f32::consts::TAU, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatternShape {
+    Sine,
+    Triangle,
+    /// On for first half of each period
+    Pulse,
+}
+
+impl PatternShape {
+    pub const ALL: [Self; 3] = [Self::Sine, Self::Triangle, Self::Pulse];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sine => "Sine",
+            Self::Triangle => "Triangle",
+            Self::Pulse => "Pulse",
+        }
+    }
+
+    /// `phase` in 0.0 to 1.0, returns 0.0 to 1.0, starting from 0.0
+    fn value(self, phase: f32) -> f32 {
+        match self {
+            Self::Sine => 0.5 - 0.5 * (phase * TAU).cos(),
+            Self::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+            Self::Pulse => (phase < 0.5) as u32 as f32,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PatternParams {
+    pub shape: PatternShape,
+    /// In Hz
+    pub rate: f32,
+    /// Peak level, 0.0 to 1.0
+    pub depth: f32,
+}
+
+/// Periodic level, independent of audio
+pub struct Pattern {
+    /// 0.0 to 1.0, position within period
+    phase: f32,
+    last_update: Instant,
+}
+
+impl Pattern {
+    pub fn new() -> Self {
+        Self {
+            phase: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn process(&mut self, params: PatternParams) -> f32 {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(dt, params)
+    }
+
+    /// `dt` is time since last call, in seconds
+    pub fn process_dt(&mut self, dt: f32, params: PatternParams) -> f32 {
+        // rate changes don't make level jump, as phase carries over
+        self.phase = (self.phase + dt * params.rate).fract();
+        params.shape.value(self.phase) * params.depth.clamp(0.0, 1.0)
+    }
+}
+
+/// Slow wave passed around motors of one device, so they take turns
+pub struct Alternation {
+    /// 0.0 to 1.0, position within period
+    phase: f32,
+    last_update: Instant,
+}
+
+impl Alternation {
+    pub fn new() -> Self {
+        Self {
+            phase: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Moves wave by time since last call, `rate` in Hz
+    pub fn advance(&mut self, rate: f32) {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.phase = (self.phase + dt * rate).fract();
+    }
+
+    /// Level factor of motor `index` out of `count`, offset by its share of
+    /// period. Always 1 at depth 0, or with single motor.
+    pub fn factor(&self, index: usize, count: usize, depth: f32) -> f32 {
+        if count < 2 {
+            return 1.0;
+        }
+        let phase = (self.phase + index as f32 / count as f32).fract();
+        1.0 - depth.clamp(0.0, 1.0) * PatternShape::Sine.value(phase)
+    }
+}
@@ -0,0 +1,418 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    f32::consts::TAU,
+    ops::Range,
+    sync::Arc,
+};
+
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use super::{SharedBool, SharedF32, Variants};
+
+pub const BAND_COUNT: usize = 4;
+/// Number of log-spaced bins in spectrum view
+pub const SPECTRUM_BINS: usize = 48;
+pub const SPECTRUM_MIN_FREQ: f32 = 20.0;
+pub const SPECTRUM_MAX_FREQ: f32 = 20_000.0;
+// range searched for dominant frequency, above it harmonics and hiss win
+const PITCH_MIN_FREQ: f32 = 30.0;
+const PITCH_MAX_FREQ: f32 = 4_000.0;
+// strongest bin has to stand out this much over average power, else it's
+// noise or a chord without clear root
+const PITCH_CLARITY: f32 = 8.0;
+// RMS of strongest bin, below it's treated as silence
+const PITCH_MIN_LEVEL: f32 = 0.001;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Band {
+    /// Whole filtered signal, same as without spectrum analysis
+    FullRange,
+    SubBass,
+    Bass,
+    Mids,
+    Highs,
+}
+
+impl Band {
+    pub const ALL: [Self; BAND_COUNT + 1] = [
+        Self::FullRange,
+        Self::SubBass,
+        Self::Bass,
+        Self::Mids,
+        Self::Highs,
+    ];
+    /// Bands reported by [`SpectrumAnalyzer`]
+    pub const SPLIT: [Self; BAND_COUNT] =
+        [Self::SubBass, Self::Bass, Self::Mids, Self::Highs];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FullRange => "Full range",
+            Self::SubBass => "Sub-bass",
+            Self::Bass => "Bass",
+            Self::Mids => "Mids",
+            Self::Highs => "Highs",
+        }
+    }
+
+    /// Frequency range in Hz, `None` for full range
+    pub fn range(self) -> Option<Range<f32>> {
+        match self {
+            Self::FullRange => None,
+            Self::SubBass => Some(20.0..60.0),
+            Self::Bass => Some(60.0..250.0),
+            Self::Mids => Some(250.0..4_000.0),
+            Self::Highs => Some(4_000.0..20_000.0),
+        }
+    }
+
+    /// Index into [`Band::SPLIT`]
+    pub fn split_index(self) -> Option<usize> {
+        Self::SPLIT.iter().position(|x| *x == self)
+    }
+}
+
+impl Variants for Band {
+    const VARIANTS: &'static [Self] = &Self::ALL;
+}
+
+/// Share of bass, mids and highs in weighted band mix,
+/// normalized when mixing, so only their ratios matter
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BandWeights {
+    /// Also applies to sub-bass
+    pub bass: f32,
+    pub mids: f32,
+    pub highs: f32,
+}
+
+impl Default for BandWeights {
+    fn default() -> Self {
+        Self {
+            bass: 1.0,
+            mids: 1.0,
+            highs: 1.0,
+        }
+    }
+}
+
+impl BandWeights {
+    /// Combines powers of [`Band::SPLIT`] by energy, so equal weights give
+    /// RMS of whole spectrum, all zero weights give 0
+    pub fn mix(&self, powers: &[f32; BAND_COUNT]) -> f32 {
+        let weights =
+            [self.bass, self.bass, self.mids, self.highs].map(|x| x.max(0.0));
+        let total = weights[1..].iter().sum::<f32>();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let energy = weights
+            .iter()
+            .zip(powers)
+            .map(|(weight, power)| weight * power * power)
+            .sum::<f32>();
+        // scaled so that equal weights are each worth 1
+        (energy * 3.0 / total).sqrt().clamp(0.0, 1.0)
+    }
+}
+
+/// Power below each low pass cutoff, by cutoff in Hz
+pub type CutoffPowers = BTreeMap<u32, f32>;
+
+/// Power of each band in [`Band::SPLIT`], and below each low pass cutoff
+/// devices override, written by capture thread
+#[derive(Clone)]
+pub struct BandPowers {
+    split: [SharedF32; BAND_COUNT],
+    /// GUI picks which cutoffs are computed
+    cutoffs: Arc<parking_lot::Mutex<CutoffPowers>>,
+}
+
+impl BandPowers {
+    pub fn new() -> Self {
+        Self {
+            split: std::array::from_fn(|_| SharedF32::new(0.0)),
+            cutoffs: Default::default(),
+        }
+    }
+
+    pub fn store(&self, powers: [f32; BAND_COUNT]) {
+        for (shared, power) in self.split.iter().zip(powers) {
+            shared.store(power);
+        }
+    }
+
+    pub fn load(&self) -> [f32; BAND_COUNT] {
+        std::array::from_fn(|i| self.split[i].load())
+    }
+
+    /// Sets which cutoffs are computed. Kept ones keep their power,
+    /// so levels of other devices don't jump.
+    pub fn want_cutoffs(&self, wanted: &BTreeSet<u32>) {
+        let mut cutoffs = self.cutoffs.lock();
+        cutoffs.retain(|cutoff, _| wanted.contains(cutoff));
+        for &cutoff in wanted {
+            cutoffs.entry(cutoff).or_insert(0.0);
+        }
+    }
+
+    /// Updates power of each wanted cutoff with `power_below`
+    pub fn store_cutoffs(&self, power_below: impl Fn(f32) -> f32) {
+        let wanted: Vec<_> = self.cutoffs.lock().keys().copied().collect();
+        let powers: Vec<_> =
+            wanted.iter().map(|&x| power_below(x as f32)).collect();
+        let mut cutoffs = self.cutoffs.lock();
+        for (cutoff, power) in wanted.into_iter().zip(powers) {
+            // might have been dropped in the meantime
+            if let Some(shared) = cutoffs.get_mut(&cutoff) {
+                *shared = power;
+            }
+        }
+    }
+
+    pub fn load_cutoffs(&self) -> CutoffPowers {
+        self.cutoffs.lock().clone()
+    }
+}
+
+/// Magnitudes for spectrum view, only computed while it's shown
+#[derive(Clone)]
+pub struct SpectrumBins {
+    pub is_enabled: SharedBool,
+    bins: [SharedF32; SPECTRUM_BINS],
+}
+
+impl SpectrumBins {
+    pub fn new() -> Self {
+        Self {
+            is_enabled: SharedBool::new(false),
+            bins: std::array::from_fn(|_| SharedF32::new(0.0)),
+        }
+    }
+
+    pub fn store(&self, bins: [f32; SPECTRUM_BINS]) {
+        for (shared, bin) in self.bins.iter().zip(bins) {
+            shared.store(bin);
+        }
+    }
+
+    pub fn load(&self) -> [f32; SPECTRUM_BINS] {
+        std::array::from_fn(|i| self.bins[i].load())
+    }
+
+    /// Lower edge of bin, bins are spaced evenly on log scale
+    pub fn bin_start(index: usize) -> f32 {
+        let ratio = SPECTRUM_MAX_FREQ / SPECTRUM_MIN_FREQ;
+        SPECTRUM_MIN_FREQ * ratio.powf(index as f32 / SPECTRUM_BINS as f32)
+    }
+}
+
+pub struct SpectrumAnalyzer {
+    planner: FftPlanner<f32>,
+    buffer: Vec<Complex<f32>>,
+    // of last analyzed buffer, for scaling
+    window_power: f32,
+    sample_rate: u32,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            planner: FftPlanner::new(),
+            buffer: vec![],
+            window_power: 0.0,
+            sample_rate: 0,
+        }
+    }
+
+    /// Returns RMS of each band in [`Band::SPLIT`]. Channels get downmixed,
+    /// and buffer is zero-padded to a power of two.
+    pub fn analyze(
+        &mut self,
+        samples: &[f32],
+        channels: usize,
+        sample_rate: u32,
+    ) -> [f32; BAND_COUNT] {
+        let mut powers = [0.0; BAND_COUNT];
+        let frames = samples.len() / channels;
+        if frames < 2 {
+            self.buffer.clear();
+            return powers;
+        }
+        let len = frames.next_power_of_two();
+
+        self.buffer.clear();
+        let mut window_power = 0.0;
+        for (i, frame) in samples.chunks_exact(channels).enumerate() {
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            let w = hann(i, frames);
+            window_power += w * w;
+            self.buffer.push(Complex::new(mono * w, 0.0));
+        }
+        self.buffer.resize(len, Complex::default());
+        self.planner.plan_fft_forward(len).process(&mut self.buffer);
+        self.window_power = window_power;
+        self.sample_rate = sample_rate;
+
+        let bin_width = sample_rate as f32 / len as f32;
+        // skip dc, and mirrored upper half
+        for (bin, value) in self.buffer[..len / 2].iter().enumerate().skip(1) {
+            let freq = bin as f32 * bin_width;
+            let band = Band::SPLIT
+                .iter()
+                .position(|x| x.range().is_some_and(|r| r.contains(&freq)));
+            if let Some(i) = band {
+                powers[i] += value.norm_sqr();
+            }
+        }
+        // parseval, doubled for the mirrored half, compensating for window
+        for power in &mut powers {
+            *power = (2.0 * *power / (len as f32 * window_power))
+                .sqrt()
+                .clamp(0.0, 1.0);
+        }
+        powers
+    }
+
+    /// RMS within each spectrum view bin, from last [`Self::analyze`] call
+    pub fn log_bins(&self) -> [f32; SPECTRUM_BINS] {
+        let mut bins = [0.0; SPECTRUM_BINS];
+        let len = self.buffer.len();
+        if len == 0 {
+            return bins;
+        }
+        let bin_width = self.sample_rate as f32 / len as f32;
+        for (i, value) in self.buffer[..len / 2].iter().enumerate().skip(1) {
+            let freq = i as f32 * bin_width;
+            if !(SPECTRUM_MIN_FREQ..SPECTRUM_MAX_FREQ).contains(&freq) {
+                continue;
+            }
+            let position = (freq / SPECTRUM_MIN_FREQ).ln()
+                / (SPECTRUM_MAX_FREQ / SPECTRUM_MIN_FREQ).ln();
+            let bin = (position * SPECTRUM_BINS as f32) as usize;
+            bins[bin.min(SPECTRUM_BINS - 1)] += value.norm_sqr();
+        }
+        for bin in &mut bins {
+            *bin = (2.0 * *bin / (len as f32 * self.window_power))
+                .sqrt()
+                .clamp(0.0, 1.0);
+        }
+        bins
+    }
+
+    /// RMS below `cutoff` Hz, from last [`Self::analyze`] call
+    pub fn power_below(&self, cutoff: f32) -> f32 {
+        let len = self.buffer.len();
+        if len == 0 || self.window_power <= 0.0 {
+            return 0.0;
+        }
+        let bin_width = self.sample_rate as f32 / len as f32;
+        let last = ((cutoff / bin_width) as usize).clamp(1, len / 2);
+        // skip dc, same as bands
+        let sum = self.buffer[1..last]
+            .iter()
+            .map(|x| x.norm_sqr())
+            .sum::<f32>();
+        (2.0 * sum / (len as f32 * self.window_power))
+            .sqrt()
+            .clamp(0.0, 1.0)
+    }
+
+    /// Frequency of strongest bin from last [`Self::analyze`] call,
+    /// `None` when silent or no bin stands out
+    pub fn dominant_freq(&self) -> Option<f32> {
+        let len = self.buffer.len();
+        if len < 4 || self.window_power <= 0.0 {
+            return None;
+        }
+        let bin_width = self.sample_rate as f32 / len as f32;
+        let first = ((PITCH_MIN_FREQ / bin_width).ceil() as usize).max(1);
+        let last = ((PITCH_MAX_FREQ / bin_width) as usize).min(len / 2 - 1);
+        if first >= last {
+            return None;
+        }
+        let bins = &self.buffer[first..=last];
+        let (peak, peak_power) = bins
+            .iter()
+            .map(|x| x.norm_sqr())
+            .enumerate()
+            .fold((0, 0.0), |max, x| if x.1 > max.1 { x } else { max });
+        let mean =
+            bins.iter().map(|x| x.norm_sqr()).sum::<f32>() / bins.len() as f32;
+        let level =
+            (2.0 * peak_power / (len as f32 * self.window_power)).sqrt();
+        if level < PITCH_MIN_LEVEL || peak_power < mean * PITCH_CLARITY {
+            return None;
+        }
+        // parabola through peak and neighbours, finer than bin width
+        let i = first + peak;
+        let [a, b, c] = [i - 1, i, i + 1]
+            .map(|j| self.buffer[j].norm().max(f32::MIN_POSITIVE).ln());
+        let curvature = a - 2.0 * b + c;
+        let offset = if curvature < 0.0 {
+            (0.5 * (a - c) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some((i as f32 + offset) * bin_width)
+    }
+}
+
+fn hann(i: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (TAU * i as f32 / (len - 1) as f32).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POWERS: [f32; BAND_COUNT] = [0.1, 0.2, 0.3, 0.4];
+
+    fn weights(bass: f32, mids: f32, highs: f32) -> BandWeights {
+        BandWeights { bass, mids, highs }
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn equal_weights_give_whole_spectrum() {
+        let whole = POWERS.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert_near(BandWeights::default().mix(&POWERS), whole);
+    }
+
+    #[test]
+    fn only_ratios_matter() {
+        let mix = BandWeights::default().mix(&POWERS);
+        assert_near(weights(2.0, 2.0, 2.0).mix(&POWERS), mix);
+        assert_near(
+            weights(0.5, 1.0, 0.0).mix(&POWERS),
+            weights(1.0, 2.0, 0.0).mix(&POWERS),
+        );
+    }
+
+    #[test]
+    fn single_weight_picks_its_bands() {
+        // bass includes sub-bass
+        let bass = (3.0 * (0.1f32.powi(2) + 0.2f32.powi(2))).sqrt();
+        assert_near(weights(1.0, 0.0, 0.0).mix(&POWERS), bass);
+        assert_near(weights(0.0, 0.0, 1.0).mix(&POWERS), 3f32.sqrt() * 0.4);
+    }
+
+    #[test]
+    fn zero_and_negative_weights() {
+        assert_eq!(weights(0.0, 0.0, 0.0).mix(&POWERS), 0.0);
+        assert_eq!(weights(-1.0, -1.0, -1.0).mix(&POWERS), 0.0);
+        assert_near(
+            weights(-1.0, 1.0, 1.0).mix(&POWERS),
+            weights(0.0, 1.0, 1.0).mix(&POWERS),
+        );
+    }
+
+    #[test]
+    fn mix_is_clamped() {
+        assert_eq!(weights(0.0, 1.0, 0.0).mix(&[0.0, 0.0, 0.9, 0.0]), 1.0);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::VecDeque, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+// onset strength is resampled to this rate, in Hz
+const FRAME_RATE: f32 = 100.0;
+// seconds of onset history searched for periodicity
+const HISTORY: f32 = 6.0;
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 180.0;
+// most music sits around here, so it's preferred over half or double
+const PREFERRED_BPM: f32 = 120.0;
+// seconds between tempo estimates, phase runs free in between
+const ESTIMATE_INTERVAL: f32 = 0.5;
+// share of onset variance explained by best period, below tempo is unclear
+const MIN_CONFIDENCE: f32 = 0.3;
+// how quickly each pulse fades, higher is sharper
+const PULSE_DECAY: f32 = 5.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BeatPulse {
+    /// Follow level only
+    Off,
+    Quarter,
+    Eighth,
+}
+
+impl BeatPulse {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Quarter, Self::Eighth];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Quarter => "Quarter notes",
+            Self::Eighth => "Eighth notes",
+        }
+    }
+
+    /// Gain starting at 1.0 on each pulse and decaying until next one,
+    /// `phase` is position within beat, 0.0 to 1.0
+    pub fn gain(self, phase: f32) -> f32 {
+        let per_beat = match self {
+            Self::Off => return 1.0,
+            Self::Quarter => 1.0,
+            Self::Eighth => 2.0,
+        };
+        (-(phase * per_beat).fract() * PULSE_DECAY).exp()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Beat {
+    pub bpm: f32,
+    /// Position within current beat, 0.0 on the beat
+    pub phase: f32,
+}
+
+/// Estimates tempo from periodicity of rises in energy, and follows beat
+/// phase between estimates
+pub struct TempoTracker {
+    /// Onset strength per frame, oldest first
+    frames: VecDeque<f32>,
+    /// Strength gathered for frame in progress
+    current: f32,
+    /// Seconds into frame in progress
+    frame_time: f32,
+    last_energy: f32,
+    since_estimate: f32,
+    /// Beats per second, 0 while unclear
+    tempo: f32,
+    phase: f32,
+    last_update: Instant,
+}
+
+impl TempoTracker {
+    pub fn new() -> Self {
+        Self {
+            frames: VecDeque::new(),
+            current: 0.0,
+            frame_time: 0.0,
+            last_energy: 0.0,
+            since_estimate: 0.0,
+            tempo: 0.0,
+            phase: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Feeds current energy, returns `None` while tempo is unclear
+    pub fn process(&mut self, energy: f32) -> Option<Beat> {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        self.process_dt(energy, dt)
+    }
+
+    /// `dt` is time since last call, in seconds
+    pub fn process_dt(&mut self, energy: f32, dt: f32) -> Option<Beat> {
+        self.current += (energy - self.last_energy).max(0.0);
+        self.last_energy = energy;
+        // long stalls only need enough empty frames to fill history
+        self.frame_time = (self.frame_time + dt).min(HISTORY);
+        while self.frame_time >= FRAME_RATE.recip() {
+            self.frame_time -= FRAME_RATE.recip();
+            self.frames.push_back(self.current);
+            self.current = 0.0;
+            if self.frames.len() > (HISTORY * FRAME_RATE) as usize {
+                self.frames.pop_front();
+            }
+        }
+        self.phase = (self.phase + dt * self.tempo).fract();
+        self.since_estimate += dt;
+        if self.since_estimate >= ESTIMATE_INTERVAL {
+            self.since_estimate = 0.0;
+            self.estimate();
+        }
+        (self.tempo > 0.0).then_some(Beat {
+            bpm: self.tempo * 60.0,
+            phase: self.phase,
+        })
+    }
+
+    fn estimate(&mut self) {
+        let min_lag = (FRAME_RATE * 60.0 / MAX_BPM) as usize;
+        let max_lag = (FRAME_RATE * 60.0 / MIN_BPM) as usize;
+        // at least two beats at slowest tempo
+        if self.frames.len() < 2 * max_lag + 1 {
+            self.tempo = 0.0;
+            return;
+        }
+        let frames = self.frames.make_contiguous();
+        let mean = frames.iter().sum::<f32>() / frames.len() as f32;
+        let x: Vec<f32> = frames.iter().map(|x| x - mean).collect();
+        let variance = x.iter().map(|x| x * x).sum::<f32>();
+        if variance <= 0.0 {
+            self.tempo = 0.0;
+            return;
+        }
+        // scaled up for lost overlap, so long lags aren't penalized
+        let correlation = |lag: usize| {
+            let sum = x.iter().zip(&x[lag..]).map(|(a, b)| a * b).sum::<f32>();
+            sum * x.len() as f32 / (x.len() - lag) as f32
+        };
+        let preference = |lag: usize| {
+            let bpm = FRAME_RATE * 60.0 / lag as f32;
+            let octaves = (bpm / PREFERRED_BPM).log2();
+            (-0.5 * octaves * octaves).exp()
+        };
+        let (lag, best, _) = (min_lag..=max_lag)
+            .map(|lag| {
+                let c = correlation(lag);
+                (lag, c, c * preference(lag))
+            })
+            .fold(
+                (0, 0.0, f32::MIN),
+                |max, x| if x.2 > max.2 { x } else { max },
+            );
+        if best / variance < MIN_CONFIDENCE {
+            self.tempo = 0.0;
+            return;
+        }
+        // parabola through neighbours, finer than frame length
+        let (a, c) = (correlation(lag - 1), correlation(lag + 1));
+        let curvature = a - 2.0 * best + c;
+        let offset = if curvature < 0.0 {
+            (0.5 * (a - c) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let period = lag as f32 + offset;
+        // frames since last beat, where onsets one period apart line up best
+        let comb = |since: usize| {
+            (0..)
+                .map(|k| since as f32 + k as f32 * period)
+                .take_while(|&pos| (pos as usize) < x.len())
+                .map(|pos| x[x.len() - 1 - pos as usize])
+                .sum::<f32>()
+        };
+        let since_beat = (0..lag)
+            .map(|since| (since, comb(since)))
+            .fold((0, f32::MIN), |max, x| if x.1 > max.1 { x } else { max })
+            .0;
+        self.tempo = FRAME_RATE / period;
+        // newest frame ended `frame_time` ago
+        let elapsed = since_beat as f32 / FRAME_RATE + self.frame_time;
+        self.phase = (elapsed * self.tempo).fract();
+    }
+}
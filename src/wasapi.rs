@@ -0,0 +1,612 @@
+use std::{
+    collections::BTreeSet, ffi::c_void, mem::ManuallyDrop, ptr, slice,
+    time::Duration,
+};
+
+use windows::{
+    core::{
+        implement, ComInterface, Error, Result, GUID, HSTRING, PCWSTR, PWSTR,
+    },
+    Win32::{
+        Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+        Foundation::{CloseHandle, E_FAIL, HANDLE, HRESULT, WAIT_OBJECT_0},
+        Media::Audio::{
+            eCapture, eCommunications, eConsole, eRender,
+            ActivateAudioInterfaceAsync, Endpoints::IAudioEndpointVolume,
+            IActivateAudioInterfaceAsyncOperation,
+            IActivateAudioInterfaceCompletionHandler,
+            IActivateAudioInterfaceCompletionHandler_Impl, IAudioCaptureClient,
+            IAudioClient, IAudioSessionControl2, IAudioSessionManager2,
+            IMMDevice, IMMDeviceEnumerator, IMMEndpoint, ISimpleAudioVolume,
+            MMDeviceEnumerator, AUDCLNT_BUFFERFLAGS_SILENT,
+            AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+            AUDCLNT_STREAMFLAGS_LOOPBACK, AUDIOCLIENT_ACTIVATION_PARAMS,
+            AUDIOCLIENT_ACTIVATION_PARAMS_0,
+            AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+            AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS, DEVICE_STATE_ACTIVE,
+            PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+            VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK, WAVEFORMATEX,
+            WAVEFORMATEXTENSIBLE,
+        },
+        System::{
+            Com::{
+                CoCreateInstance, CoInitializeEx, CoTaskMemFree, IAgileObject,
+                IAgileObject_Impl,
+                StructuredStorage::{
+                    PropVariantClear, PROPVARIANT, PROPVARIANT_0,
+                    PROPVARIANT_0_0, PROPVARIANT_0_0_0,
+                },
+                BLOB, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ, VT_BLOB,
+            },
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Process32FirstW, Process32NextW,
+                PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+            },
+            Threading::{CreateEventW, WaitForSingleObject},
+        },
+    },
+};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+const KSDATAFORMAT_SUBTYPE_PCM: GUID =
+    GUID::from_u128(0x00000001_0000_0010_8000_00aa00389b71);
+const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: GUID =
+    GUID::from_u128(0x00000003_0000_0010_8000_00aa00389b71);
+// process loopback has no mix format, so samples are converted to this
+const PROCESS_FORMAT: Format = Format {
+    channels: 2,
+    sample_rate: 48_000,
+    sample_kind: SampleKind::F32,
+};
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+pub struct Format {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub sample_kind: SampleKind,
+}
+
+/// How samples are stored in capture buffer, converted to `f32` on read
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SampleKind {
+    F32,
+    I16,
+    /// Packed, 3 bytes per sample
+    I24,
+    /// 24-bit samples padded to 32 bits are read the same way
+    I32,
+}
+
+impl SampleKind {
+    fn bytes(self) -> usize {
+        match self {
+            SampleKind::I16 => 2,
+            SampleKind::I24 => 3,
+            SampleKind::F32 | SampleKind::I32 => 4,
+        }
+    }
+}
+
+/// Audio device, as listed in Windows sound settings
+#[derive(Clone)]
+pub struct Endpoint {
+    pub id: String,
+    pub name: String,
+    /// Microphone or line-in, captured directly instead of through loopback
+    pub is_input: bool,
+}
+
+/// Lists active output devices, then input ones
+pub fn endpoints() -> Result<Vec<Endpoint>> {
+    init_com();
+    let mut endpoints = vec![];
+    unsafe {
+        let enumerator = device_enumerator()?;
+        for (data_flow, is_input) in [(eRender, false), (eCapture, true)] {
+            let collection = enumerator
+                .EnumAudioEndpoints(data_flow, DEVICE_STATE_ACTIVE)?;
+            for i in 0..collection.GetCount()? {
+                let device = collection.Item(i)?;
+                endpoints.push(Endpoint {
+                    id: device_id(&device)?,
+                    name: friendly_name(&device)?,
+                    is_input,
+                });
+            }
+        }
+    }
+    Ok(endpoints)
+}
+
+/// Names of processes playing audio on any output device, sorted
+pub fn audio_apps() -> Result<Vec<String>> {
+    init_com();
+    let processes = processes()?;
+    let mut names = BTreeSet::new();
+    unsafe {
+        let enumerator = device_enumerator()?;
+        let collection =
+            enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+        for i in 0..collection.GetCount()? {
+            let device = collection.Item(i)?;
+            let manager: IAudioSessionManager2 =
+                device.Activate(CLSCTX_ALL, None)?;
+            let sessions = manager.GetSessionEnumerator()?;
+            for j in 0..sessions.GetCount()? {
+                let session: IAudioSessionControl2 =
+                    sessions.GetSession(j)?.cast()?;
+                // system sounds
+                let Ok(id @ 1..) = session.GetProcessId() else {
+                    continue;
+                };
+                let name = processes.iter().find(|x| x.id == id);
+                names.extend(name.map(|x| x.name.clone()));
+            }
+        }
+    }
+    Ok(names.into_iter().collect())
+}
+
+pub struct Process {
+    pub id: u32,
+    /// Executable file name
+    pub name: String,
+}
+
+pub fn processes() -> Result<Vec<Process>> {
+    let mut processes = vec![];
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)?;
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut has_entry = Process32FirstW(snapshot, &mut entry).as_bool();
+        while has_entry {
+            let len = entry
+                .szExeFile
+                .iter()
+                .position(|x| *x == 0)
+                .unwrap_or(entry.szExeFile.len());
+            processes.push(Process {
+                id: entry.th32ProcessID,
+                name: String::from_utf16_lossy(&entry.szExeFile[..len]),
+            });
+            has_entry = Process32NextW(snapshot, &mut entry).as_bool();
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    Ok(processes)
+}
+
+/// Finds process by executable name, ignoring case
+pub fn find_process(name: &str) -> Result<Option<u32>> {
+    let processes = processes()?;
+    let process = processes.iter().find(|x| x.name.eq_ignore_ascii_case(name));
+    Ok(process.map(|x| x.id))
+}
+
+pub fn is_process_running(id: u32) -> bool {
+    // if listing fails, assume it's still there
+    let Ok(processes) = processes() else {
+        return true;
+    };
+    processes.iter().any(|x| x.id == id)
+}
+
+/// Id of default output device, same one `Capture::open(None, ..)` opens
+pub fn default_output() -> Result<String> {
+    init_com();
+    unsafe {
+        let enumerator = device_enumerator()?;
+        let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?;
+        device_id(&device)
+    }
+}
+
+/// Id of default input device for voice chat
+pub fn default_voice_input() -> Result<String> {
+    init_com();
+    unsafe {
+        let enumerator = device_enumerator()?;
+        let device =
+            enumerator.GetDefaultAudioEndpoint(eCapture, eCommunications)?;
+        device_id(&device)
+    }
+}
+
+/// Loopback capture of an output device, or capture of an input device,
+/// in shared mode
+pub struct Capture {
+    client: IAudioClient,
+    capture_client: IAudioCaptureClient,
+    format: Format,
+    is_input: bool,
+    mute_source: MuteSource,
+    /// Signaled by audio engine when a packet is ready
+    event: HANDLE,
+    pub buffer_frame_size: u32,
+}
+
+/// Where mute state of captured audio is read from
+enum MuteSource {
+    Endpoint(IAudioEndpointVolume),
+    /// Process loopback is taken before endpoint volume,
+    /// so both endpoint and process sessions are checked
+    Process(u32),
+}
+
+impl Capture {
+    /// Opens device with given id, or default output for `None`,
+    /// and starts it
+    pub fn open(
+        device_id: Option<&str>,
+        buffer_duration: Duration,
+    ) -> Result<Self> {
+        init_com();
+        unsafe {
+            let enumerator = device_enumerator()?;
+            let device = match device_id {
+                Some(id) => enumerator.GetDevice(&HSTRING::from(id))?,
+                None => {
+                    enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?
+                }
+            };
+            let endpoint: IMMEndpoint = device.cast()?;
+            let is_input = endpoint.GetDataFlow()? == eCapture;
+            let client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
+            let volume: IAudioEndpointVolume =
+                device.Activate(CLSCTX_ALL, None)?;
+            let mix_format = client.GetMixFormat()?;
+            let result = read_format(mix_format).and_then(|format| {
+                Self::start(
+                    client,
+                    mix_format,
+                    format,
+                    is_input,
+                    MuteSource::Endpoint(volume),
+                    buffer_duration,
+                )
+            });
+            CoTaskMemFree(Some(mix_format as *const c_void));
+            result
+        }
+    }
+
+    /// Captures audio of process and its children, from all devices
+    pub fn open_process(
+        process_id: u32,
+        buffer_duration: Duration,
+    ) -> Result<Self> {
+        init_com();
+        let params = AUDIOCLIENT_ACTIVATION_PARAMS {
+            ActivationType: AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+            Anonymous: AUDIOCLIENT_ACTIVATION_PARAMS_0 {
+                ProcessLoopbackParams: AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS {
+                    TargetProcessId: process_id,
+                    ProcessLoopbackMode:
+                        PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+                },
+            },
+        };
+        let variant = PROPVARIANT {
+            Anonymous: PROPVARIANT_0 {
+                Anonymous: ManuallyDrop::new(PROPVARIANT_0_0 {
+                    vt: VT_BLOB,
+                    wReserved1: 0,
+                    wReserved2: 0,
+                    wReserved3: 0,
+                    Anonymous: PROPVARIANT_0_0_0 {
+                        blob: BLOB {
+                            cbSize: std::mem::size_of_val(&params) as u32,
+                            pBlobData: &params as *const _ as *mut u8,
+                        },
+                    },
+                }),
+            },
+        };
+        let (sender, receiver) = flume::bounded(1);
+        let handler: IActivateAudioInterfaceCompletionHandler =
+            ActivationHandler(sender).into();
+        unsafe {
+            let operation = ActivateAudioInterfaceAsync(
+                VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK,
+                &IAudioClient::IID,
+                Some(&variant as *const _),
+                &handler,
+            )?;
+            receiver.recv_timeout(ACTIVATION_TIMEOUT).map_err(|_| {
+                Error::new(E_FAIL, "activation timed out".into())
+            })?;
+            let mut result = HRESULT(0);
+            let mut interface = None;
+            operation.GetActivateResult(&mut result, &mut interface)?;
+            result.ok()?;
+            let client: IAudioClient =
+                interface.ok_or_else(|| Error::from(E_FAIL))?.cast()?;
+            let format = PROCESS_FORMAT;
+            let block_align = format.channels * 4;
+            let wave_format = WAVEFORMATEX {
+                wFormatTag: WAVE_FORMAT_IEEE_FLOAT,
+                nChannels: format.channels,
+                nSamplesPerSec: format.sample_rate,
+                nAvgBytesPerSec: format.sample_rate * block_align as u32,
+                nBlockAlign: block_align,
+                wBitsPerSample: 32,
+                cbSize: 0,
+            };
+            Self::start(
+                client,
+                &wave_format,
+                format,
+                false,
+                MuteSource::Process(process_id),
+                buffer_duration,
+            )
+        }
+    }
+
+    unsafe fn start(
+        client: IAudioClient,
+        wave_format: *const WAVEFORMATEX,
+        format: Format,
+        is_input: bool,
+        mute_source: MuteSource,
+        buffer_duration: Duration,
+    ) -> Result<Self> {
+        // in 100ns units
+        let duration = buffer_duration.as_nanos() as i64 / 100;
+        let flags = if is_input {
+            AUDCLNT_STREAMFLAGS_EVENTCALLBACK
+        } else {
+            AUDCLNT_STREAMFLAGS_EVENTCALLBACK | AUDCLNT_STREAMFLAGS_LOOPBACK
+        };
+        client.Initialize(
+            AUDCLNT_SHAREMODE_SHARED,
+            flags,
+            duration,
+            0,
+            wave_format,
+            None,
+        )?;
+        // not supported by process loopback
+        let buffer_frame_size = client.GetBufferSize().unwrap_or_else(|_| {
+            (buffer_duration.as_secs_f32() * format.sample_rate as f32) as u32
+        });
+        let capture_client: IAudioCaptureClient = client.GetService()?;
+        let event = CreateEventW(None, false, false, PCWSTR::null())?;
+        let result = client.SetEventHandle(event).and_then(|()| client.Start());
+        if let Err(e) = result {
+            let _ = CloseHandle(event);
+            return Err(e);
+        }
+        Ok(Self {
+            client,
+            capture_client,
+            format,
+            is_input,
+            mute_source,
+            event,
+            buffer_frame_size,
+        })
+    }
+
+    /// Source is muted in Windows, so only silence gets captured.
+    /// If mute state can't be read, it's assumed to be unmuted.
+    pub fn is_muted(&self) -> bool {
+        unsafe {
+            match &self.mute_source {
+                MuteSource::Endpoint(volume) => {
+                    volume.GetMute().is_ok_and(|x| x.as_bool())
+                }
+                MuteSource::Process(id) => {
+                    is_process_muted(*id).unwrap_or(false)
+                }
+            }
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Blocks until a packet is ready or `timeout` passes,
+    /// returns `false` on timeout.
+    /// Loopback of silent output gets no packets, so `timeout` keeps
+    /// caller from stalling.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let ms = timeout.as_millis().min(u32::MAX as u128) as u32;
+        unsafe { WaitForSingleObject(self.event, ms) == WAIT_OBJECT_0 }
+    }
+
+    pub fn is_input(&self) -> bool {
+        self.is_input
+    }
+
+    /// Calls `f` with interleaved samples of each available packet
+    pub fn read_samples(&mut self, mut f: impl FnMut(&[f32])) -> Result<()> {
+        let channels = self.format.channels as usize;
+        let kind = self.format.sample_kind;
+        let mut converted = Vec::new();
+        unsafe {
+            while self.capture_client.GetNextPacketSize()? > 0 {
+                let mut data = ptr::null_mut();
+                let mut frames = 0;
+                let mut flags = 0;
+                self.capture_client.GetBuffer(
+                    &mut data,
+                    &mut frames,
+                    &mut flags,
+                    None,
+                    None,
+                )?;
+                let len = frames as usize * channels;
+                if flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 {
+                    f(&vec![0.0; len]);
+                } else if kind == SampleKind::F32 {
+                    f(slice::from_raw_parts(data as *const f32, len));
+                } else {
+                    let bytes = slice::from_raw_parts(data, len * kind.bytes());
+                    converted.clear();
+                    converted.extend(bytes.chunks_exact(kind.bytes()).map(
+                        |b| match kind {
+                            SampleKind::I16 => {
+                                i16::from_le_bytes([b[0], b[1]]) as f32
+                                    / 32_768.
+                            }
+                            SampleKind::I24 => {
+                                // shift into top of i32 to sign-extend
+                                i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32
+                                    / 2_147_483_648.
+                            }
+                            SampleKind::I32 | SampleKind::F32 => {
+                                i32::from_le_bytes([b[0], b[1], b[2], b[3]])
+                                    as f32
+                                    / 2_147_483_648.
+                            }
+                        },
+                    ));
+                    f(&converted);
+                }
+                self.capture_client.ReleaseBuffer(frames)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.client.Stop();
+            let _ = CloseHandle(self.event);
+        }
+    }
+}
+
+// handler has to be agile, it's called from another thread
+#[implement(IActivateAudioInterfaceCompletionHandler, IAgileObject)]
+struct ActivationHandler(flume::Sender<()>);
+
+impl IAgileObject_Impl for ActivationHandler {}
+
+impl IActivateAudioInterfaceCompletionHandler_Impl for ActivationHandler {
+    fn ActivateCompleted(
+        &self,
+        _operation: Option<&IActivateAudioInterfaceAsyncOperation>,
+    ) -> Result<()> {
+        let _ = self.0.send(());
+        Ok(())
+    }
+}
+
+fn init_com() {
+    // fails harmlessly if thread already has com initialized
+    let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+}
+
+unsafe fn device_enumerator() -> Result<IMMDeviceEnumerator> {
+    CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+}
+
+/// Default output is muted, or every session of process is
+unsafe fn is_process_muted(process_id: u32) -> Result<bool> {
+    let enumerator = device_enumerator()?;
+    let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?;
+    let volume: IAudioEndpointVolume = device.Activate(CLSCTX_ALL, None)?;
+    if volume.GetMute()?.as_bool() {
+        return Ok(true);
+    }
+    let collection =
+        enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+    let mut has_session = false;
+    for i in 0..collection.GetCount()? {
+        let manager: IAudioSessionManager2 =
+            collection.Item(i)?.Activate(CLSCTX_ALL, None)?;
+        let sessions = manager.GetSessionEnumerator()?;
+        for j in 0..sessions.GetCount()? {
+            let session: IAudioSessionControl2 =
+                sessions.GetSession(j)?.cast()?;
+            if session.GetProcessId().ok() != Some(process_id) {
+                continue;
+            }
+            has_session = true;
+            let volume: ISimpleAudioVolume = session.cast()?;
+            if !volume.GetMute()?.as_bool() {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(has_session)
+}
+
+unsafe fn device_id(device: &IMMDevice) -> Result<String> {
+    let id: PWSTR = device.GetId()?;
+    let res = id.to_string().map_err(|_| Error::from(E_FAIL));
+    CoTaskMemFree(Some(id.0 as *const c_void));
+    res
+}
+
+unsafe fn friendly_name(device: &IMMDevice) -> Result<String> {
+    let store = device.OpenPropertyStore(STGM_READ)?;
+    let mut value = store.GetValue(&PKEY_Device_FriendlyName)?;
+    let name = value.Anonymous.Anonymous.Anonymous.pwszVal.to_string();
+    PropVariantClear(&mut value)?;
+    name.map_err(|_| Error::from(E_FAIL))
+}
+
+/// 32-bit float and 16/24/32-bit integer samples are supported
+unsafe fn read_format(format: *const WAVEFORMATEX) -> Result<Format> {
+    let header = ptr::read_unaligned(format);
+    let (is_float, is_pcm) = match header.wFormatTag {
+        WAVE_FORMAT_IEEE_FLOAT => (true, false),
+        WAVE_FORMAT_PCM => (false, true),
+        WAVE_FORMAT_EXTENSIBLE => {
+            let ext = format as *const WAVEFORMATEXTENSIBLE;
+            let sub_format =
+                ptr::read_unaligned(ptr::addr_of!((*ext).SubFormat));
+            (
+                sub_format == KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
+                sub_format == KSDATAFORMAT_SUBTYPE_PCM,
+            )
+        }
+        _ => (false, false),
+    };
+    let bits = header.wBitsPerSample;
+    let sample_kind = match (is_float, is_pcm, bits) {
+        (true, _, 32) => SampleKind::F32,
+        (_, true, 16) => SampleKind::I16,
+        (_, true, 24) => SampleKind::I24,
+        (_, true, 32) => SampleKind::I32,
+        _ => {
+            let kind = if is_float {
+                "float"
+            } else if is_pcm {
+                "integer"
+            } else {
+                "unknown"
+            };
+            return Err(Error::new(
+                E_FAIL,
+                format!("unsupported sample format: {bits}-bit {kind}").into(),
+            ));
+        }
+    };
+    let channels = header.nChannels;
+    // frames are read as whole sample slices, padding would misalign them
+    let block_align = channels as usize * sample_kind.bytes();
+    if channels == 0
+        || header.nSamplesPerSec == 0
+        || header.nBlockAlign as usize != block_align
+    {
+        return Err(Error::new(E_FAIL, "unexpected capture format".into()));
+    }
+    Ok(Format {
+        channels,
+        sample_rate: header.nSamplesPerSec,
+        sample_kind,
+    })
+}